@@ -29,6 +29,13 @@ pub const APP_VERSION_KEY: MetadataKey = MetadataKey::new("version");
 pub const APP_DESCRIPTION_KEY: MetadataKey = MetadataKey::new("description");
 /// MetadataKey for extracting the OCI image digest.
 pub const OCI_IMAGE_DIGEST_KEY: MetadataKey = MetadataKey::new("oci_image_digest");
+/// MetadataKey for extracting a component's resource limits.
+pub const COMPONENT_LIMITS_KEY: MetadataKey<locked::ComponentLimits> = MetadataKey::new("limits");
+/// MetadataKey for extracting the number of invocations an instance of a
+/// component may serve.
+pub const COMPONENT_INSTANCE_REUSE_KEY: MetadataKey<u32> = MetadataKey::new("instance_reuse");
+/// MetadataKey for extracting whether a component should be pre-initialized.
+pub const COMPONENT_PREINIT_KEY: MetadataKey<bool> = MetadataKey::new("preinit");
 
 /// Validation function type for ensuring that applications meet requirements
 /// even with components filtered out.
@@ -11,6 +11,7 @@
 pub mod arg_parser;
 pub mod assert;
 pub mod data_dir;
+pub mod migrations;
 pub mod paths;
 pub mod sha256;
 pub mod sloth;
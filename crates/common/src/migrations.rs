@@ -0,0 +1,31 @@
+//! Reading SQL schema migrations from a directory
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::ui::quoted_path;
+
+/// Reads the migrations in a directory: the `.sql` files it contains, in file
+/// name order, as `(name, sql)` pairs.
+pub fn read_migrations_dir(dir: &Path) -> Result<Vec<(String, String)>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read migrations directory {}", quoted_path(dir)))?;
+    let mut migrations = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "sql") {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("Migration file name {} is not UTF-8", quoted_path(&path)))?
+            .to_owned();
+        let sql = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read migration {}", quoted_path(&path)))?;
+        migrations.push((name, sql));
+    }
+    migrations.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(migrations)
+}
@@ -34,6 +34,26 @@ pub async fn compose<L: ComponentSourceLoader>(
     Composer::new(loader).compose(component).await
 }
 
+/// Returns the names of the imports of the given component.
+pub fn component_imports(component: &[u8]) -> anyhow::Result<Vec<String>> {
+    let mut graph = CompositionGraph::new();
+    let package = Package::from_bytes("component", None, component.to_vec(), graph.types_mut())?;
+    Ok(graph.types()[package.ty()]
+        .imports
+        .keys()
+        .cloned()
+        .collect())
+}
+
+/// Returns true if a dependency with the given name would be used to satisfy
+/// the import with the given name.
+pub fn dependency_matches_import(
+    dependency_name: &DependencyName,
+    import_name: &str,
+) -> anyhow::Result<bool> {
+    matches_import(dependency_name, import_name)
+}
+
 /// A Spin component dependency. This abstracts over the metadata associated with the
 /// dependency. The abstraction allows both manifest and lockfile types to participate in composition.
 #[async_trait::async_trait]
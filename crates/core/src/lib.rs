@@ -25,6 +25,8 @@ pub use wasmtime::{
     Instance as ModuleInstance, Module, Trap,
 };
 
+pub use limits::{is_cpu_limit_exceeded, CpuLimitExceeded};
+pub use store::guest_backtrace;
 pub use store::{AsState, Store, StoreBuilder};
 
 /// The default [`EngineBuilder::epoch_tick_interval`].
@@ -38,6 +40,22 @@ const GB: usize = 1 << 30;
 /// This is currently only used for advanced (undocumented) use cases.
 pub struct Config {
     inner: wasmtime::Config,
+    pooling_limits: Option<PoolingLimits>,
+    consume_fuel: bool,
+}
+
+/// Per-instance capacity of the pooling instance allocator.
+///
+/// Instances that need more than this cannot be allocated from the pool, so
+/// per-instance limits larger than these values can never be reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolingLimits {
+    /// The maximum size of each linear memory in bytes.
+    pub max_memory_size: usize,
+    /// The maximum number of elements in each table.
+    pub max_table_elements: usize,
+    /// The maximum number of core instances per component instance.
+    pub max_core_instances: usize,
 }
 
 impl Config {
@@ -65,6 +83,37 @@ impl Config {
     pub fn disable_pooling(&mut self) -> &mut Self {
         self.inner
             .allocation_strategy(wasmtime::InstanceAllocationStrategy::OnDemand);
+        self.pooling_limits = None;
+        self
+    }
+
+    /// Returns the per-instance capacity of the pooling instance allocator, or
+    /// `None` if pooling is disabled.
+    pub fn pooling_limits(&self) -> Option<PoolingLimits> {
+        self.pooling_limits
+    }
+
+    /// Enable fuel consumption, which is required by
+    /// [`StoreBuilder::max_fuel`].
+    ///
+    /// Fuel metering slows down Wasm execution, so this should only be
+    /// enabled if fuel limits will be used.
+    pub fn consume_fuel(&mut self) -> &mut Self {
+        self.inner.consume_fuel(true);
+        self.consume_fuel = true;
+        self
+    }
+
+    /// Returns true if fuel consumption has been enabled with
+    /// [`Config::consume_fuel`].
+    pub fn consumes_fuel(&self) -> bool {
+        self.consume_fuel
+    }
+
+    /// Capture a core dump when an instance traps, which can then be written
+    /// out with [`Store::write_core_dump`].
+    pub fn coredump_on_trap(&mut self) -> &mut Self {
+        self.inner.coredump_on_trap(true);
         self
     }
 }
@@ -75,12 +124,21 @@ impl Default for Config {
         inner.async_support(true);
         inner.epoch_interruption(true);
         inner.wasm_component_model(true);
+        // Symbolicate guest backtraces with DWARF debug info, if present,
+        // unless `WASMTIME_BACKTRACE_DETAILS` says otherwise
+        let backtrace_details = if std::env::var_os("WASMTIME_BACKTRACE_DETAILS").is_some() {
+            wasmtime::WasmBacktraceDetails::Environment
+        } else {
+            wasmtime::WasmBacktraceDetails::Enable
+        };
+        inner.wasm_backtrace_details(backtrace_details);
         // If targeting musl, disable native unwind to address this issue:
         // https://github.com/spinframework/spin/issues/2889
         // TODO: remove this when wasmtime is updated to >= v27.0.0
         #[cfg(all(target_os = "linux", target_env = "musl"))]
         inner.native_unwind_info(false);
 
+        let mut pooling_limits = None;
         if use_pooling_allocator_by_default() {
             // Baseline for the maximum number of instances in spin through
             // which a number of other defaults are derived below.
@@ -94,6 +152,13 @@ impl Default for Config {
             // knobs for each of these settings just yet and instead they're
             // generally set to defaults. Environment-variable-based fallbacks are
             // supported though as an escape valve for if this is a problem.
+            let limits = PoolingLimits {
+                max_memory_size: 4 * GB,
+                max_table_elements: env("SPIN_WASMTIME_INSTANCE_TABLE_ELEMENTS", 100_000),
+                max_core_instances: env("SPIN_WASMTIME_CORE_INSTANCE_COUNT", 200),
+            };
+            pooling_limits = Some(limits);
+
             let mut pooling_config = PoolingAllocationConfig::default();
             pooling_config
                 // Configuration parameters which affect the total size of the
@@ -129,7 +194,7 @@ impl Default for Config {
                 .total_tables(env("SPIN_WASMTIME_TOTAL_TABLES", 2 * max_instances))
                 .total_stacks(env("SPIN_WASMTIME_TOTAL_STACKS", max_instances))
                 .total_core_instances(env("SPIN_WASMTIME_TOTAL_CORE_INSTANCES", 4 * max_instances))
-                .table_elements(env("SPIN_WASMTIME_INSTANCE_TABLE_ELEMENTS", 100_000))
+                .table_elements(limits.max_table_elements)
                 // This number accounts for internal data structures that Wasmtime allocates for each instance.
                 // Instance allocation is proportional to the number of "things" in a wasm module like functions,
                 // globals, memories, etc. Instance allocations are relatively small and are largely inconsequential
@@ -153,7 +218,7 @@ impl Default for Config {
                 // * Tables here are roughly similar to memories but are set a
                 //   bit higher as it's more likely to have more tables than
                 //   memories in a component.
-                .max_core_instances_per_component(env("SPIN_WASMTIME_CORE_INSTANCE_COUNT", 200))
+                .max_core_instances_per_component(limits.max_core_instances as u32)
                 .max_tables_per_component(env("SPIN_WASMTIME_INSTANCE_TABLES", 64))
                 .max_memories_per_component(env("SPIN_WASMTIME_INSTANCE_MEMORIES", 32))
                 // Similar knobs as above, but as specified per-module instead
@@ -164,7 +229,7 @@ impl Default for Config {
                 // Nothing is lost from allowing the maximum size of memory for
                 // all instance as it's still limited through other the normal
                 // `StoreLimitsAsync` accounting method too.
-                .max_memory_size(4 * GB)
+                .max_memory_size(limits.max_memory_size)
                 // These numbers are completely arbitrary at something above 0.
                 .linear_memory_keep_resident(env(
                     "SPIN_WASMTIME_LINEAR_MEMORY_KEEP_RESIDENT",
@@ -174,7 +239,11 @@ impl Default for Config {
             inner.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config));
         }
 
-        return Self { inner };
+        return Self {
+            inner,
+            pooling_limits,
+            consume_fuel: false,
+        };
 
         fn env<T>(name: &str, default: T) -> T
         where
@@ -270,6 +339,7 @@ pub struct EngineBuilder<T: 'static> {
     linker: Linker<T>,
     epoch_tick_interval: Duration,
     epoch_ticker_thread: bool,
+    pooling_limits: Option<PoolingLimits>,
 }
 
 impl<T: 'static> EngineBuilder<T> {
@@ -281,6 +351,7 @@ impl<T: 'static> EngineBuilder<T> {
             linker,
             epoch_tick_interval: DEFAULT_EPOCH_TICK_INTERVAL,
             epoch_ticker_thread: true,
+            pooling_limits: config.pooling_limits,
         })
     }
 
@@ -333,6 +404,7 @@ impl<T: 'static> EngineBuilder<T> {
             inner: self.engine,
             linker: self.linker,
             epoch_tick_interval: self.epoch_tick_interval,
+            pooling_limits: self.pooling_limits,
         }
     }
 }
@@ -343,6 +415,7 @@ pub struct Engine<T: 'static> {
     inner: wasmtime::Engine,
     linker: Linker<T>,
     epoch_tick_interval: Duration,
+    pooling_limits: Option<PoolingLimits>,
 }
 
 impl<T: 'static> Engine<T> {
@@ -356,6 +429,12 @@ impl<T: 'static> Engine<T> {
         StoreBuilder::new(self.inner.clone(), self.epoch_tick_interval)
     }
 
+    /// Returns the per-instance capacity of the pooling instance allocator, or
+    /// `None` if pooling is disabled.
+    pub fn pooling_limits(&self) -> Option<PoolingLimits> {
+        self.pooling_limits
+    }
+
     /// Creates a new [`InstancePre`] for the given [`Component`].
     #[instrument(skip_all, level = "debug")]
     pub fn instantiate_pre(&self, component: &Component) -> Result<InstancePre<T>> {
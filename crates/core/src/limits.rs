@@ -8,6 +8,7 @@ use wasmtime::ResourceLimiterAsync;
 pub struct StoreLimitsAsync {
     max_memory_size: Option<usize>,
     max_table_elements: Option<usize>,
+    max_instances: Option<usize>,
     memory_consumed: u64,
 }
 
@@ -53,17 +54,61 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
         };
         Ok(can_grow)
     }
+
+    fn instances(&self) -> usize {
+        self.max_instances
+            .unwrap_or(wasmtime::DEFAULT_INSTANCE_LIMIT)
+    }
+}
+
+/// The error returned when an instance exceeds its CPU time limit.
+///
+/// See [`StoreBuilder::max_cpu_time`](crate::StoreBuilder::max_cpu_time).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuLimitExceeded;
+
+impl std::fmt::Display for CpuLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("instance exceeded its CPU time limit")
+    }
+}
+
+impl std::error::Error for CpuLimitExceeded {}
+
+/// Returns true if the error was caused by an instance exceeding its CPU
+/// time or fuel limit.
+pub fn is_cpu_limit_exceeded(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.is::<CpuLimitExceeded>() || cause.downcast_ref() == Some(&wasmtime::Trap::OutOfFuel)
+    })
 }
 
 impl StoreLimitsAsync {
-    pub fn new(max_memory_size: Option<usize>, max_table_elements: Option<usize>) -> Self {
+    pub fn new(
+        max_memory_size: Option<usize>,
+        max_table_elements: Option<usize>,
+        max_instances: Option<usize>,
+    ) -> Self {
         Self {
             max_memory_size,
             max_table_elements,
+            max_instances,
             memory_consumed: 0,
         }
     }
 
+    /// Replaces the limits, keeping track of the memory already consumed.
+    pub(crate) fn set_limits(
+        &mut self,
+        max_memory_size: Option<usize>,
+        max_table_elements: Option<usize>,
+        max_instances: Option<usize>,
+    ) {
+        self.max_memory_size = max_memory_size;
+        self.max_table_elements = max_table_elements;
+        self.max_instances = max_instances;
+    }
+
     /// How much memory has been consumed in bytes
     pub fn memory_consumed(&self) -> u64 {
         self.memory_consumed
@@ -95,4 +140,12 @@ mod tests {
         assert!(limits.table_growing(9, 10, None).await.unwrap());
         assert!(!limits.table_growing(10, 11, None).await.unwrap());
     }
+
+    #[test]
+    fn test_store_limits_instances() {
+        let limits = StoreLimitsAsync::new(None, None, Some(3));
+        assert_eq!(limits.instances(), 3);
+        let limits = StoreLimitsAsync::default();
+        assert_eq!(limits.instances(), wasmtime::DEFAULT_INSTANCE_LIMIT);
+    }
 }
@@ -1,7 +1,17 @@
-use anyhow::Result;
-use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::{limits::StoreLimitsAsync, State, WasmtimeEngine};
+use crate::{
+    limits::{is_cpu_limit_exceeded, CpuLimitExceeded, StoreLimitsAsync},
+    State, WasmtimeEngine,
+};
 
 #[cfg(doc)]
 use crate::EngineBuilder;
@@ -9,12 +19,34 @@ use crate::EngineBuilder;
 /// A `Store` holds the runtime state of a Spin instance.
 ///
 /// In general, a `Store` is expected to live only for the lifetime of a single
-/// Spin trigger invocation.
+/// Spin trigger invocation, unless it is explicitly prepared for reuse with
+/// [`StoreBuilder::reuse`].
 ///
 /// A `Store` can be built with a [`StoreBuilder`].
 pub struct Store<T: 'static> {
     inner: wasmtime::Store<T>,
     epoch_tick_interval: Duration,
+    // Set when a CPU time limit or profiler is in effect; the epoch deadline
+    // is then owned by a callback run on every epoch tick and the wall-clock
+    // deadline is checked there instead.
+    ticker: Option<Arc<Ticker>>,
+    core_dumps: Option<CoreDumps>,
+    profile: Option<Profile>,
+}
+
+struct CoreDumps {
+    dir: PathBuf,
+    component_id: String,
+}
+
+struct Ticker {
+    ticks: AtomicU64,
+    deadline: Mutex<Option<Instant>>,
+}
+
+struct Profile {
+    profiler: Arc<Mutex<Option<wasmtime::GuestProfiler>>>,
+    path: PathBuf,
 }
 
 impl<T: 'static> Store<T> {
@@ -26,6 +58,10 @@ impl<T: 'static> Store<T> {
     ///
     /// See [`wasmtime::Store::set_epoch_deadline`](https://docs.rs/wasmtime/latest/wasmtime/struct.Store.html#method.set_epoch_deadline).
     pub fn set_deadline(&mut self, deadline: Instant) {
+        if let Some(ticker) = &self.ticker {
+            *ticker.deadline.lock().unwrap() = Some(deadline);
+            return;
+        }
         let now = Instant::now();
         let duration = deadline - now;
         let ticks = if duration.is_zero() {
@@ -39,6 +75,65 @@ impl<T: 'static> Store<T> {
         self.inner.set_epoch_deadline(ticks);
     }
 
+    /// Handles the failure of an invocation in this store, recording on the
+    /// current span whether the instance exceeded its fuel or CPU time limit
+    /// and writing a core dump if enabled (see [`Store::write_core_dump`]).
+    pub fn record_failure(&mut self, err: &anyhow::Error) {
+        if is_cpu_limit_exceeded(err) {
+            tracing::Span::current().record("error.type", "cpu_limit_exceeded");
+        }
+        self.write_core_dump(err);
+    }
+
+    /// Writes a core dump of the instance to the directory set with
+    /// [`StoreBuilder::core_dump_dir`], if the given error is a trap for which
+    /// a core dump was captured (see [`Config::coredump_on_trap`]).
+    ///
+    /// Core dumps can't be written for components built with a WASI preview 1
+    /// adapter. Failures, including such components, are logged rather than
+    /// returned, as this is only called when handling another error.
+    ///
+    /// [`Config::coredump_on_trap`]: crate::Config::coredump_on_trap
+    pub fn write_core_dump(&mut self, err: &anyhow::Error) {
+        let Some(core_dumps) = &self.core_dumps else {
+            return;
+        };
+        let Some(core_dump) = err.downcast_ref::<wasmtime::WasmCoreDump>() else {
+            return;
+        };
+        match write_core_dump(core_dump, &mut self.inner, core_dumps) {
+            Ok(path) => tracing::info!("Wrote guest core dump to {}", path.display()),
+            Err(err) => tracing::warn!("Failed to write guest core dump: {err:#}"),
+        }
+    }
+
+    /// Finishes the guest profile enabled with [`StoreBuilder::profile`], if
+    /// any, and writes it out on a blocking thread. This should be called at
+    /// the end of each invocation, whether or not it succeeded.
+    ///
+    /// Failures are logged rather than returned.
+    pub async fn finish_profile(&mut self) {
+        let Some(Profile { profiler, path }) = self.profile.take() else {
+            return;
+        };
+        let Some(profiler) = profiler.lock().unwrap().take() else {
+            return;
+        };
+        let result = tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::create(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            profiler.finish(std::io::BufWriter::new(file))?;
+            Ok::<_, anyhow::Error>(path)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+        match result {
+            Ok(path) => tracing::debug!("Wrote guest profile to {}", path.display()),
+            Err(err) => tracing::warn!("Failed to write guest profile: {err:#}"),
+        }
+    }
+
     /// Provides access to the inner [`wasmtime::Store`]'s data.
     pub fn data(&self) -> &T {
         self.inner.data()
@@ -76,13 +171,61 @@ impl<T: 'static> wasmtime::AsContextMut for Store<T> {
     }
 }
 
+fn write_core_dump<T>(
+    core_dump: &wasmtime::WasmCoreDump,
+    store: &mut wasmtime::Store<T>,
+    core_dumps: &CoreDumps,
+) -> Result<PathBuf> {
+    // Distinguishes dumps written by concurrent instances in the same millisecond
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+    let CoreDumps { dir, component_id } = core_dumps;
+    // wasmtime can't serialize dumps of instances containing the modules
+    // wit-component adds to adapt WASI preview 1 modules
+    if let Some(module) = core_dump
+        .modules()
+        .iter()
+        .filter_map(|module| module.name())
+        .find(|name| name.starts_with("wit-component:"))
+    {
+        anyhow::bail!(
+            "core dumps are not supported for components built with a WASI preview 1 adapter (found module `{module}`)"
+        );
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let pid = std::process::id();
+    let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("{component_id}-{timestamp}-{pid}-{seq}.coredump"));
+    let bytes = core_dump.serialize(store, component_id);
+    std::fs::write(&path, bytes).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Returns the guest backtrace captured when an instance trapped, if any.
+///
+/// Frames are symbolicated using the component's name section and, if
+/// present, its DWARF debug info.
+pub fn guest_backtrace(err: &anyhow::Error) -> Option<&wasmtime::WasmBacktrace> {
+    err.downcast_ref::<wasmtime::WasmBacktrace>()
+}
+
 /// A builder interface for configuring a new [`Store`].
 ///
 /// A new [`StoreBuilder`] can be obtained with [`crate::Engine::store_builder`].
 pub struct StoreBuilder {
     engine: WasmtimeEngine,
     epoch_tick_interval: Duration,
-    store_limits: StoreLimitsAsync,
+    max_memory_size: Option<usize>,
+    max_table_elements: Option<usize>,
+    max_instances: Option<usize>,
+    max_fuel: Option<u64>,
+    max_cpu_time: Option<Duration>,
+    core_dumps: Option<CoreDumps>,
+    profile: Option<Profile>,
 }
 
 impl StoreBuilder {
@@ -91,7 +234,13 @@ impl StoreBuilder {
         Self {
             engine,
             epoch_tick_interval,
-            store_limits: StoreLimitsAsync::default(),
+            max_memory_size: None,
+            max_table_elements: None,
+            max_instances: None,
+            max_fuel: None,
+            max_cpu_time: None,
+            core_dumps: None,
+            profile: None,
         }
     }
 
@@ -100,7 +249,71 @@ impl StoreBuilder {
     /// See [`wasmtime::ResourceLimiter::memory_growing`] (`maximum`) for
     /// details on how this limit is enforced.
     pub fn max_memory_size(&mut self, max_memory_size: usize) {
-        self.store_limits = StoreLimitsAsync::new(Some(max_memory_size), None);
+        self.max_memory_size = Some(max_memory_size);
+    }
+
+    /// Sets a maximum number of elements per table.
+    ///
+    /// See [`wasmtime::ResourceLimiter::table_growing`] (`maximum`) for
+    /// details on how this limit is enforced.
+    pub fn max_table_elements(&mut self, max_table_elements: usize) {
+        self.max_table_elements = Some(max_table_elements);
+    }
+
+    /// Sets a maximum number of core instances that may be created in the
+    /// store.
+    ///
+    /// See [`wasmtime::ResourceLimiter::instances`].
+    pub fn max_instances(&mut self, max_instances: usize) {
+        self.max_instances = Some(max_instances);
+    }
+
+    /// Sets the amount of fuel available to the instance.
+    ///
+    /// The engine must have been configured with [`Config::consume_fuel`];
+    /// [`StoreBuilder::build`] will fail otherwise. An instance that runs out
+    /// of fuel traps with [`wasmtime::Trap::OutOfFuel`].
+    ///
+    /// [`Config::consume_fuel`]: crate::Config::consume_fuel
+    pub fn max_fuel(&mut self, max_fuel: u64) {
+        self.max_fuel = Some(max_fuel);
+    }
+
+    /// Sets a limit on the CPU time the instance may use.
+    ///
+    /// CPU time is measured in epoch ticks (see
+    /// [`EngineBuilder::epoch_tick_interval`]) during which the instance is
+    /// executing Wasm; time spent waiting on host calls is not counted. An
+    /// instance that exceeds this limit traps with a [`CpuLimitExceeded`]
+    /// error.
+    pub fn max_cpu_time(&mut self, max_cpu_time: Duration) {
+        self.max_cpu_time = Some(max_cpu_time);
+    }
+
+    /// Sets the directory that [`Store::write_core_dump`] writes to. Core
+    /// dump file names are prefixed with the given component ID.
+    pub fn core_dump_dir(&mut self, dir: impl Into<PathBuf>, component_id: impl Into<String>) {
+        self.core_dumps = Some(CoreDumps {
+            dir: dir.into(),
+            component_id: component_id.into(),
+        });
+    }
+
+    /// Enables sampling of the instance's stack on every epoch tick (see
+    /// [`EngineBuilder::epoch_tick_interval`]). The profile is written to
+    /// `path` by [`Store::finish_profile`] in the format used by the
+    /// [Firefox profiler](https://profiler.firefox.com/).
+    pub fn profile(&mut self, component: &crate::Component, name: &str, path: impl Into<PathBuf>) {
+        let profiler = wasmtime::GuestProfiler::new_component(
+            name,
+            self.epoch_tick_interval,
+            component.clone(),
+            [],
+        );
+        self.profile = Some(Profile {
+            profiler: Arc::new(Mutex::new(Some(profiler))),
+            path: path.into(),
+        });
     }
 
     /// Builds a [`Store`] from this builder with given host state data.
@@ -108,10 +321,41 @@ impl StoreBuilder {
     /// The `T` parameter must provide access to a [`State`] via `impl
     /// AsMut<State>`.
     pub fn build<T: AsState>(self, mut data: T) -> Result<Store<T>> {
-        data.as_state().store_limits = self.store_limits;
+        data.as_state().store_limits = StoreLimitsAsync::new(
+            self.max_memory_size,
+            self.max_table_elements,
+            self.max_instances,
+        );
 
         let mut inner = wasmtime::Store::new(&self.engine, data);
         inner.limiter_async(|data| &mut data.as_state().store_limits);
+        let mut store = Store {
+            inner,
+            epoch_tick_interval: self.epoch_tick_interval,
+            ticker: None,
+            core_dumps: None,
+            profile: None,
+        };
+        self.configure(&mut store)?;
+        Ok(store)
+    }
+
+    /// Prepares a [`Store`] which has already served an invocation of an
+    /// instantiated component for another invocation, replacing its limits
+    /// and other settings with those of this builder. Fuel and CPU time
+    /// budgets start afresh and any deadline is cleared.
+    pub fn reuse<T: AsState>(self, store: &mut Store<T>) -> Result<()> {
+        store.inner.data_mut().as_state().store_limits.set_limits(
+            self.max_memory_size,
+            self.max_table_elements,
+            self.max_instances,
+        );
+        self.configure(store)
+    }
+
+    // Applies the settings common to new and reused stores.
+    fn configure<T: AsState>(self, store: &mut Store<T>) -> Result<()> {
+        let inner = &mut store.inner;
 
         // With epoch interruption enabled, there must be _some_ deadline set
         // or execution will trap immediately. Since this is a delta, we need
@@ -119,10 +363,68 @@ impl StoreBuilder {
         // forever" for any plausible tick interval.
         inner.set_epoch_deadline(u64::MAX / 2);
 
-        Ok(Store {
-            inner,
-            epoch_tick_interval: self.epoch_tick_interval,
-        })
+        match self.max_fuel {
+            Some(max_fuel) => inner
+                .set_fuel(max_fuel)
+                .context("fuel limits require an engine with fuel consumption enabled")?,
+            // An engine consuming fuel traps stores without any, so stores
+            // without a fuel limit get an effectively unlimited amount
+            None => _ = inner.set_fuel(u64::MAX),
+        }
+
+        // Replaces any callback installed for a previous invocation
+        inner.epoch_deadline_trap();
+        let ticker = (self.max_cpu_time.is_some() || self.profile.is_some()).then(|| {
+            let ticker = Arc::new(Ticker {
+                ticks: AtomicU64::new(0),
+                deadline: Mutex::new(None),
+            });
+            let epoch_tick_interval = self.epoch_tick_interval;
+            let max_cpu_time = self.max_cpu_time;
+            let max_ticks = max_cpu_time.map(|max_cpu_time| {
+                (max_cpu_time.as_micros() / epoch_tick_interval.as_micros().max(1))
+                    .clamp(1, u64::MAX as u128) as u64
+            });
+            let profiler = self
+                .profile
+                .as_ref()
+                .map(|profile| profile.profiler.clone());
+            let callback_ticker = ticker.clone();
+            // The callback runs once per epoch tick observed while Wasm is
+            // executing, so counting invocations approximates CPU time.
+            inner.set_epoch_deadline(1);
+            inner.epoch_deadline_callback(move |store| {
+                if let Some(profiler) = &profiler {
+                    if let Some(profiler) = profiler.lock().unwrap().as_mut() {
+                        profiler.sample(&store, epoch_tick_interval);
+                    }
+                }
+                if callback_ticker
+                    .deadline
+                    .lock()
+                    .unwrap()
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    return Err(wasmtime::Trap::Interrupt.into());
+                }
+                let ticks = callback_ticker.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+                if max_ticks.is_some_and(|max_ticks| ticks > max_ticks) {
+                    tracing::warn!(
+                        "error.type" = "cpu_limit_exceeded",
+                        ?max_cpu_time,
+                        "instance CPU time limit exceeded",
+                    );
+                    return Err(CpuLimitExceeded.into());
+                }
+                Ok(wasmtime::UpdateDeadline::Continue(1))
+            });
+            ticker
+        });
+
+        store.ticker = ticker;
+        store.core_dumps = self.core_dumps;
+        store.profile = self.profile;
+        Ok(())
     }
 }
 
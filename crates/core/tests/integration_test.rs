@@ -47,6 +47,21 @@ async fn test_max_memory_size_violated() {
     assert_eq!(trap.0, 1);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_instances_violated() {
+    // A componentized command contains several core instances (the adapter
+    // and the main module, at least)
+    run_test(
+        ["noop"],
+        |store_builder| {
+            store_builder.max_instances(1);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+}
+
 // FIXME: racy timing test
 #[tokio::test(flavor = "multi_thread")]
 async fn test_set_deadline_obeyed() {
@@ -76,6 +91,110 @@ async fn test_set_deadline_violated() {
     assert_eq!(trap, Trap::Interrupt);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_fuel_obeyed() {
+    run_test_with_config(
+        ["noop"],
+        |config| {
+            config.consume_fuel();
+        },
+        |store_builder, _| {
+            store_builder.max_fuel(u64::MAX / 2);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_fuel_violated() {
+    let err = run_test_with_config(
+        ["busy", "10000"],
+        |config| {
+            config.consume_fuel();
+        },
+        |store_builder, _| {
+            store_builder.max_fuel(1_000_000);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+    assert!(spin_core::is_cpu_limit_exceeded(&err), "{err:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_consume_fuel_without_max_fuel() {
+    run_test_with_config(
+        ["noop"],
+        |config| {
+            config.consume_fuel();
+        },
+        |_, _| {},
+        |_| {},
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_fuel_requires_consume_fuel() {
+    run_test(
+        ["noop"],
+        |store_builder| {
+            store_builder.max_fuel(1_000_000);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_cpu_time_ignores_host_waits() {
+    run_test(
+        ["sleep", "100"],
+        |store_builder| {
+            store_builder.max_cpu_time(Duration::from_millis(50));
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_cpu_time_violated() {
+    let err = run_test(
+        ["busy", "10000"],
+        |store_builder| {
+            store_builder.max_cpu_time(Duration::from_millis(50));
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+    assert!(spin_core::is_cpu_limit_exceeded(&err), "{err:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_cpu_time_with_deadline_violated() {
+    let err = run_test(
+        ["sleep", "100"],
+        |store_builder| {
+            store_builder.max_cpu_time(Duration::from_secs(10));
+        },
+        |store| {
+            store.set_deadline(Instant::now() + Duration::from_millis(10));
+        },
+    )
+    .await
+    .unwrap_err();
+    let trap = err.downcast::<Trap>().expect("trap");
+    assert_eq!(trap, Trap::Interrupt);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_panic() {
     let err = run_test(["panic"], |_| {}, |_| {}).await.unwrap_err();
@@ -83,6 +202,84 @@ async fn test_panic() {
     assert_eq!(trap, Trap::UnreachableCodeReached);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trap_backtrace_and_core_dump() {
+    let dir = tempfile::tempdir().unwrap();
+    let err = run_test_with_config(
+        ["panic"],
+        |config| {
+            config.coredump_on_trap();
+        },
+        |store_builder, _| {
+            store_builder.core_dump_dir(dir.path(), "panicky");
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+    assert!(spin_core::guest_backtrace(&err).is_some());
+    // This guest is built with the WASI preview 1 adapter, so no dump is written
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_core_dump_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.coredump_on_trap();
+    let engine = Engine::<State>::builder(&config).unwrap().build();
+    let component = Component::new(
+        engine.as_ref(),
+        r#"(component
+            (core module $m
+                (memory (export "memory") 1)
+                (func (export "trap") unreachable))
+            (core instance $i (instantiate $m))
+            (func (export "trap") (canon lift (core func $i "trap"))))"#,
+    )
+    .unwrap();
+    let mut store_builder = engine.store_builder();
+    store_builder.core_dump_dir(dir.path(), "trappy");
+    let mut store = store_builder.build(State::default()).unwrap();
+    let instance = engine
+        .instantiate_pre(&component)
+        .unwrap()
+        .instantiate_async(&mut store)
+        .await
+        .unwrap();
+    let trap = instance
+        .get_typed_func::<(), ()>(&mut store, "trap")
+        .unwrap();
+    let err = trap.call_async(&mut store, ()).await.unwrap_err();
+    store.write_core_dump(&err);
+
+    let dumps = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(dumps.len(), 1);
+    assert!(dumps[0].starts_with("trappy-"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_profile_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("profile.json");
+    run_test_with_config(
+        ["noop"],
+        |_| {},
+        |store_builder, component| {
+            store_builder.profile(component, "test-component", &path);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+    let profile: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert!(profile.is_object());
+}
+
 #[derive(RuntimeFactors)]
 struct TestFactors {
     wasi: WasiFactor,
@@ -109,6 +306,21 @@ async fn run_test(
     args: impl IntoIterator<Item = &'_ str>,
     update_store_builder: impl FnOnce(&mut StoreBuilder),
     update_store: impl FnOnce(&mut Store<TestState>),
+) -> anyhow::Result<()> {
+    run_test_with_config(
+        args,
+        |_| {},
+        |store_builder, _| update_store_builder(store_builder),
+        update_store,
+    )
+    .await
+}
+
+async fn run_test_with_config(
+    args: impl IntoIterator<Item = &'_ str>,
+    update_config: impl FnOnce(&mut Config),
+    update_store_builder: impl FnOnce(&mut StoreBuilder, &Component),
+    update_store: impl FnOnce(&mut Store<TestState>),
 ) -> anyhow::Result<()> {
     let mut factors = TestFactors {
         wasi: WasiFactor::new(DummyFilesMounter),
@@ -118,13 +330,19 @@ async fn run_test(
     config
         .wasmtime_config()
         .wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable);
+    update_config(&mut config);
 
     let mut builder = Engine::builder(&config).unwrap();
     factors.init(builder.linker())?;
     let engine = builder.build();
 
+    let module_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../target/test-programs/core-wasi-test.wasm");
+    let component = spin_componentize::componentize_command(&fs::read(module_path).await?)?;
+    let component = Component::new(engine.as_ref(), &component)?;
+
     let mut store_builder = engine.store_builder();
-    update_store_builder(&mut store_builder);
+    update_store_builder(&mut store_builder, &component);
 
     let locked: LockedApp = serde_json::from_value(json!({
         "spin_lock_version": 1,
@@ -150,10 +368,6 @@ async fn run_test(
     let mut store = store_builder.build(state)?;
     update_store(&mut store);
 
-    let module_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("../../target/test-programs/core-wasi-test.wasm");
-    let component = spin_componentize::componentize_command(&fs::read(module_path).await?)?;
-    let component = Component::new(engine.as_ref(), &component)?;
     let instance_pre = engine.instantiate_pre(&component)?;
     let instance = instance_pre.instantiate_async(&mut store).await?;
     let func = {
@@ -164,10 +378,12 @@ async fn run_test(
         instance.get_typed_func::<(), (Result<(), ()>,)>(&mut store, &func)?
     };
 
-    func.call_async(&mut store, ())
-        .await?
-        .0
-        .map_err(|()| anyhow::anyhow!("command failed"))
+    let result = func.call_async(&mut store, ()).await;
+    store.finish_profile().await;
+    if let Err(err) = &result {
+        store.write_core_dump(err);
+    }
+    result?.0.map_err(|()| anyhow::anyhow!("command failed"))
 }
 
 // Write with `print!`, required for test output capture
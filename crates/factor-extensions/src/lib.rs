@@ -0,0 +1,106 @@
+//! A factor for host extensions.
+//!
+//! A host extension is a Wasm component that provides interfaces to guests
+//! without them being declared as dependencies in the app manifest. Host
+//! extensions are registered at runtime, either in the runtime config or with
+//! [`HostExtensionsFactor::register`], and are composed into each component
+//! that imports one of the interfaces they provide.
+
+pub mod runtime_config;
+
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use anyhow::ensure;
+use runtime_config::RuntimeConfig;
+use spin_factors::{ConfigureAppContext, Factor, InitContext, PrepareContext, RuntimeFactors};
+use spin_serde::DependencyName;
+
+/// A host extension.
+#[derive(Clone, Debug)]
+pub struct HostExtension {
+    /// The name identifying the extension.
+    pub name: String,
+    /// The path of the extension's Wasm component.
+    pub source: PathBuf,
+    /// The interfaces, exported by the extension, that it provides to guests.
+    /// Each may name a package (`foo:bar`), an interface (`foo:bar/baz`) or
+    /// either with a version (`foo:bar/baz@0.1.0`).
+    pub provides: Vec<DependencyName>,
+}
+
+/// A factor for composing host extensions into components.
+#[derive(Default)]
+pub struct HostExtensionsFactor {
+    registered: Vec<HostExtension>,
+}
+
+impl HostExtensionsFactor {
+    /// Creates a new `HostExtensionsFactor`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a host extension in addition to any in the runtime config.
+    pub fn register(&mut self, extension: HostExtension) {
+        self.registered.push(extension);
+    }
+}
+
+impl Factor for HostExtensionsFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = ();
+
+    fn init(&mut self, _ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        // Extensions are composed into components rather than linked
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let mut extensions = self.registered.clone();
+        extensions.extend(ctx.take_runtime_config().unwrap_or_default().extensions);
+
+        let mut names = HashSet::new();
+        for extension in &extensions {
+            ensure!(
+                names.insert(&extension.name),
+                "host extension {:?} is defined more than once",
+                extension.name
+            );
+            ensure!(
+                !extension.provides.is_empty(),
+                "host extension {:?} does not provide any interfaces",
+                extension.name
+            );
+            ensure!(
+                extension.source.is_file(),
+                "host extension {:?} source {} does not exist",
+                extension.name,
+                extension.source.display()
+            );
+        }
+
+        Ok(AppState {
+            extensions: extensions.into(),
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(&self, _ctx: PrepareContext<T, Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// The host extensions available to an app.
+pub struct AppState {
+    extensions: Arc<[HostExtension]>,
+}
+
+impl AppState {
+    /// Returns the host extensions available to the app.
+    pub fn extensions(&self) -> Arc<[HostExtension]> {
+        self.extensions.clone()
+    }
+}
@@ -0,0 +1,10 @@
+pub mod spin;
+
+use crate::HostExtension;
+
+/// Runtime configuration for host extensions.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// The host extensions to compose into components.
+    pub extensions: Vec<HostExtension>,
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+use spin_serde::DependencyName;
+
+use crate::HostExtension;
+
+/// Get the runtime configuration for host extensions from a TOML table.
+///
+/// Relative extension sources are resolved against `base_dir` if given.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [extension.acme-cache]
+/// source = "extensions/cache.wasm"
+/// provides = ["acme:cache/store@0.1.0"]
+/// ```
+pub fn config_from_table(
+    table: &impl GetTomlValue,
+    base_dir: Option<&Path>,
+) -> anyhow::Result<Option<super::RuntimeConfig>> {
+    let Some(extensions) = table.get("extension") else {
+        return Ok(None);
+    };
+    let extensions: BTreeMap<String, ExtensionToml> = extensions.clone().try_into()?;
+    let extensions = extensions
+        .into_iter()
+        .map(|(name, extension)| {
+            let source = match base_dir {
+                Some(base_dir) => base_dir.join(extension.source),
+                None => extension.source,
+            };
+            HostExtension {
+                name,
+                source,
+                provides: extension.provides,
+            }
+        })
+        .collect();
+    Ok(Some(super::RuntimeConfig { extensions }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtensionToml {
+    source: PathBuf,
+    provides: Vec<DependencyName>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_are_resolved_from_table() -> anyhow::Result<()> {
+        let table: toml::Table = toml::from_str(
+            r#"
+            [extension.acme-cache]
+            source = "extensions/cache.wasm"
+            provides = ["acme:cache/store@0.1.0"]
+            "#,
+        )?;
+        let config = config_from_table(&table, Some(Path::new("/app")))?.unwrap();
+        let [extension] = config.extensions.as_slice() else {
+            panic!("expected one extension");
+        };
+        assert_eq!(extension.name, "acme-cache");
+        assert_eq!(extension.source, Path::new("/app/extensions/cache.wasm"));
+        assert_eq!(extension.provides[0].to_string(), "acme:cache/store@0.1.0");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_interface_names_are_rejected() -> anyhow::Result<()> {
+        let table: toml::Table = toml::from_str(
+            r#"
+            [extension.acme-cache]
+            source = "cache.wasm"
+            provides = ["not a name"]
+            "#,
+        )?;
+        assert!(config_from_table(&table, None).is_err());
+        Ok(())
+    }
+}
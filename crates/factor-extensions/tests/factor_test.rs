@@ -0,0 +1,75 @@
+use spin_factor_extensions::{runtime_config::RuntimeConfig, HostExtension, HostExtensionsFactor};
+use spin_factors::{anyhow, RuntimeFactors};
+use spin_factors_test::TestEnvironment;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    extensions: HostExtensionsFactor,
+}
+
+fn extension(name: &str, source: &std::path::Path) -> HostExtension {
+    HostExtension {
+        name: name.into(),
+        source: source.into(),
+        provides: vec!["acme:cache/store".to_string().try_into().unwrap()],
+    }
+}
+
+#[tokio::test]
+async fn registered_and_configured_extensions_are_available() -> anyhow::Result<()> {
+    let source = tempfile::NamedTempFile::new()?;
+    let mut factors = TestFactors {
+        extensions: HostExtensionsFactor::new(),
+    };
+    factors
+        .extensions
+        .register(extension("registered", source.path()));
+    let env = TestEnvironment::new(factors).runtime_config(TestFactorsRuntimeConfig {
+        extensions: Some(RuntimeConfig {
+            extensions: vec![extension("configured", source.path())],
+        }),
+    })?;
+
+    let configured_app = env.build_configured_app().await?;
+    let extensions = configured_app
+        .app_state::<HostExtensionsFactor>()?
+        .extensions();
+    let names = extensions
+        .iter()
+        .map(|e| e.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["registered", "configured"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn duplicate_extension_names_are_rejected() -> anyhow::Result<()> {
+    let source = tempfile::NamedTempFile::new()?;
+    let env = TestEnvironment::new(TestFactors {
+        extensions: HostExtensionsFactor::new(),
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        extensions: Some(RuntimeConfig {
+            extensions: vec![
+                extension("cache", source.path()),
+                extension("cache", source.path()),
+            ],
+        }),
+    })?;
+    assert!(env.build_configured_app().await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn missing_extension_source_is_rejected() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        extensions: HostExtensionsFactor::new(),
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        extensions: Some(RuntimeConfig {
+            extensions: vec![extension("cache", "does-not-exist.wasm".as_ref())],
+        }),
+    })?;
+    assert!(env.build_configured_app().await.is_err());
+    Ok(())
+}
@@ -1,15 +1,26 @@
 use super::{Cas, SwapError};
+use crate::metrics::MeteredStore;
+use crate::quota::{QuotaStore, StoreQuota};
 use anyhow::{Context, Result};
+use futures::stream::BoxStream;
 use spin_core::{async_trait, wasmtime::component::Resource};
 use spin_resource_table::Table;
 use spin_telemetry::traces::{self, Blame};
+use spin_world::spin::key_value::key_value as v3;
 use spin_world::v2::key_value;
 use spin_world::wasi::keyvalue as wasi_keyvalue;
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use tracing::instrument;
 
 const DEFAULT_STORE_TABLE_CAPACITY: u32 = 256;
 
+/// The maximum number of keys returned by a single call to list keys.
+const MAX_LIST_KEYS_LIMIT: u32 = 1000;
+
 pub use key_value::Error;
 
 #[async_trait]
@@ -31,17 +42,154 @@ pub trait Store: Sync + Send {
     async fn after_open(&self) -> Result<(), Error> {
         Ok(())
     }
+    /// The optional features supported by this store.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
     async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error>;
+    /// Set the value for `key`, expiring it once `ttl` has elapsed.
+    ///
+    /// Unlike `set`, which removes any expiry, this replaces the key's expiry with `ttl`.
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let _ = (key, value, ttl);
+        Err(Error::Other(
+            "this key-value store does not support expiring keys".to_owned(),
+        ))
+    }
+    /// The time remaining before `key` expires, or `None` if the key does not
+    /// exist or does not expire.
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let _ = key;
+        Err(Error::Other(
+            "this key-value store does not support expiring keys".to_owned(),
+        ))
+    }
     async fn delete(&self, key: &str) -> Result<(), Error>;
     async fn exists(&self, key: &str) -> Result<bool, Error>;
     async fn get_keys(&self) -> Result<Vec<String>, Error>;
+    /// List the keys starting with `prefix`, beginning at the position given by `cursor`.
+    ///
+    /// `cursor` is `None` for the first page, and otherwise the cursor of the previous page.
+    /// `limit` is a hint: backends may return fewer (or, if the backend's native pagination
+    /// cannot be bounded exactly, more) keys.
+    ///
+    /// The default implementation lists every key with `get_keys` and pages through them in
+    /// order, using the last key of each page as the cursor.
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let mut keys = self.get_keys().await?;
+        keys.retain(|key| {
+            prefix.is_none_or(|prefix| key.starts_with(prefix))
+                && cursor.is_none_or(|cursor| key.as_str() > cursor)
+        });
+        keys.sort_unstable();
+        let cursor = if keys.len() > limit as usize {
+            keys.truncate(limit as usize);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Ok(KeyPage { keys, cursor })
+    }
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error>;
     async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error>;
     async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error>;
     async fn increment(&self, key: String, delta: i64) -> Result<i64, Error>;
     async fn new_compare_and_swap(&self, bucket_rep: u32, key: &str)
         -> Result<Arc<dyn Cas>, Error>;
+    /// Atomically apply the transaction's writes if all of its conditions hold.
+    ///
+    /// Returns `false`, without applying any writes, if a condition did not hold. Stores which
+    /// implement this should also report [`Capabilities::transactions`].
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let _ = transaction;
+        Err(Error::Other(
+            "this key-value store does not support transactions".to_owned(),
+        ))
+    }
+    /// Watch for changes to keys starting with `prefix`.
+    ///
+    /// Stores which implement this should also report [`Capabilities::watch`].
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        let _ = prefix;
+        Err(Error::Other(
+            "this key-value store does not support watching keys".to_owned(),
+        ))
+    }
+}
+
+/// A stream of changes to keys in a store, as returned by [`Store::watch`].
+pub type KeyChanges = BoxStream<'static, KeyChange>;
+
+/// A change to a key in a store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChange {
+    pub key: String,
+    pub kind: KeyChangeKind,
+}
+
+/// The kind of a [`KeyChange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyChangeKind {
+    /// The key was set to a new value.
+    Set,
+    /// The key was deleted or expired.
+    Delete,
+}
+
+/// The optional features supported by a [`Store`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The store implements [`Store::set_with_ttl`] and [`Store::get_ttl`].
+    pub expiry: bool,
+    /// The store implements [`Store::transact`].
+    pub transactions: bool,
+    /// The store implements [`Store::watch`].
+    pub watch: bool,
+}
+
+/// A set of writes to apply to a store atomically, provided its conditions hold.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// The values keys are expected to have, or `None` for keys expected not to exist.
+    pub conditions: Vec<(String, Option<Vec<u8>>)>,
+    /// The writes to apply, in order.
+    pub writes: Vec<Write>,
+}
+
+/// A write in a [`Transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Write {
+    Set(String, Vec<u8>),
+    Delete(String),
+}
+
+impl Write {
+    pub fn key(&self) -> &str {
+        match self {
+            Write::Set(key, _) | Write::Delete(key) => key,
+        }
+    }
+}
+
+/// A transaction being built by a guest, along with the store it will be committed to.
+struct PendingTransaction {
+    store: Arc<dyn Store>,
+    transaction: Transaction,
+}
+
+/// A page of keys returned by [`Store::list_keys`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyPage {
+    /// The keys in this page.
+    pub keys: Vec<String>,
+    /// The cursor from which to list the next page, or `None` if this is the last page.
+    pub cursor: Option<String>,
 }
 
 pub struct KeyValueDispatch {
@@ -49,6 +197,9 @@ pub struct KeyValueDispatch {
     manager: Arc<dyn StoreManager>,
     stores: Table<Arc<dyn Store>>,
     compare_and_swaps: Table<Arc<dyn Cas>>,
+    transactions: Table<PendingTransaction>,
+    /// The quotas enforced on stores, by label.
+    quotas: HashMap<String, Arc<StoreQuota>>,
 }
 
 impl KeyValueDispatch {
@@ -66,9 +217,17 @@ impl KeyValueDispatch {
             manager,
             stores: Table::new(capacity),
             compare_and_swaps: Table::new(capacity),
+            transactions: Table::new(capacity),
+            quotas: HashMap::new(),
         }
     }
 
+    /// Enforces `quotas` on the stores with the given labels.
+    pub(crate) fn with_quotas(mut self, quotas: HashMap<String, Arc<StoreQuota>>) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
     pub fn get_store<T: 'static>(&self, store: Resource<T>) -> anyhow::Result<&Arc<dyn Store>> {
         let res = self.stores.get(store.rep()).context("invalid store");
         if let Err(err) = &res {
@@ -83,10 +242,39 @@ impl KeyValueDispatch {
             .context("invalid compare and swap")
     }
 
+    fn get_transaction(
+        &mut self,
+        transaction: Resource<v3::Transaction>,
+    ) -> Result<&mut Transaction> {
+        self.transactions
+            .get_mut(transaction.rep())
+            .map(|pending| &mut pending.transaction)
+            .context("invalid transaction")
+    }
+
     pub fn allowed_stores(&self) -> &HashSet<String> {
         &self.allowed_stores
     }
 
+    async fn open_store(&mut self, name: &str) -> Result<u32, Error> {
+        if !self.allowed_stores.contains(name) {
+            return Err(Error::AccessDenied);
+        }
+        let store = self.manager.get(name).await?;
+        let store = self.wrap_store(name, store);
+        store.after_open().await?;
+        self.stores.push(store).map_err(|()| Error::StoreTableFull)
+    }
+
+    /// Wraps an opened store to enforce its quota (if any) and report metrics on its operations.
+    fn wrap_store(&self, name: &str, store: Arc<dyn Store>) -> Arc<dyn Store> {
+        let store = match self.quotas.get(name) {
+            Some(quota) => Arc::new(QuotaStore::new(store, quota.clone())),
+            None => store,
+        };
+        Arc::new(MeteredStore::new(store, name))
+    }
+
     pub fn get_store_wasi<T: 'static>(
         &self,
         store: Resource<T>,
@@ -113,20 +301,7 @@ impl key_value::Host for KeyValueDispatch {}
 impl key_value::HostStore for KeyValueDispatch {
     #[instrument(name = "spin_key_value.open", skip(self), err, fields(otel.kind = "client", kv.backend=self.manager.summary(&name).unwrap_or("unknown".to_string())))]
     async fn open(&mut self, name: String) -> Result<Result<Resource<key_value::Store>, Error>> {
-        Ok(async {
-            if self.allowed_stores.contains(&name) {
-                let store = self.manager.get(&name).await?;
-                store.after_open().await?;
-                let store_idx = self
-                    .stores
-                    .push(store)
-                    .map_err(|()| Error::StoreTableFull)?;
-                Ok(Resource::new_own(store_idx))
-            } else {
-                Err(Error::AccessDenied)
-            }
-        }
-        .await)
+        Ok(self.open_store(&name).await.map(Resource::new_own))
     }
 
     #[instrument(name = "spin_key_value.get", skip_all, fields(otel.kind = "client"))]
@@ -185,6 +360,215 @@ impl key_value::HostStore for KeyValueDispatch {
     }
 }
 
+impl v3::Host for KeyValueDispatch {}
+
+impl v3::HostStore for KeyValueDispatch {
+    #[instrument(name = "spin_key_value.open", skip(self), err, fields(otel.kind = "client", kv.backend=self.manager.summary(&name).unwrap_or("unknown".to_string())))]
+    async fn open(&mut self, name: String) -> Result<Result<Resource<v3::Store>, v3::Error>> {
+        Ok(self
+            .open_store(&name)
+            .await
+            .map(Resource::new_own)
+            .map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.get", skip_all, fields(otel.kind = "client"))]
+    async fn get(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.get(&key).await.map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.set", skip_all, fields(otel.kind = "client"))]
+    async fn set(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<Result<(), v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.set(&key, &value).await.map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.set_with_ttl", skip_all, fields(otel.kind = "client", kv.ttl_ms = ttl_ms))]
+    async fn set_with_ttl(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        value: Vec<u8>,
+        ttl_ms: u64,
+    ) -> Result<Result<(), v3::Error>> {
+        let store = self.get_store(store)?;
+        if ttl_ms == 0 {
+            return Ok(Err(v3::Error::Other(
+                "ttl must be greater than zero".to_owned(),
+            )));
+        }
+        Ok(store
+            .set_with_ttl(&key, &value, Duration::from_millis(ttl_ms))
+            .await
+            .map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.get_ttl", skip_all, fields(otel.kind = "client"))]
+    async fn get_ttl(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<Option<u64>, v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store
+            .get_ttl(&key)
+            .await
+            .map(|ttl| ttl.map(|ttl| ttl.as_millis().try_into().unwrap_or(u64::MAX)))
+            .map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.delete", skip_all, fields(otel.kind = "client"))]
+    async fn delete(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<(), v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.delete(&key).await.map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.exists", skip_all, fields(otel.kind = "client"))]
+    async fn exists(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<bool, v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.exists(&key).await.map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.get_keys", skip_all, fields(otel.kind = "client"))]
+    async fn get_keys(
+        &mut self,
+        store: Resource<v3::Store>,
+    ) -> Result<Result<Vec<String>, v3::Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.get_keys().await.map_err(to_v3_error))
+    }
+
+    #[instrument(name = "spin_key_value.list_keys", skip_all, fields(otel.kind = "client"))]
+    async fn list_keys(
+        &mut self,
+        store: Resource<v3::Store>,
+        prefix: Option<String>,
+        cursor: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<Result<v3::KeyPage, v3::Error>> {
+        let store = self.get_store(store)?;
+        let limit = limit
+            .unwrap_or(MAX_LIST_KEYS_LIMIT)
+            .clamp(1, MAX_LIST_KEYS_LIMIT);
+        Ok(store
+            .list_keys(prefix.as_deref(), cursor.as_deref(), limit)
+            .await
+            .map(|KeyPage { keys, cursor }| v3::KeyPage { keys, cursor })
+            .map_err(to_v3_error))
+    }
+
+    async fn capabilities(&mut self, store: Resource<v3::Store>) -> Result<v3::Capabilities> {
+        let capabilities = self.get_store(store)?.capabilities();
+        let mut flags = v3::Capabilities::empty();
+        if capabilities.expiry {
+            flags |= v3::Capabilities::EXPIRY;
+        }
+        if capabilities.transactions {
+            flags |= v3::Capabilities::TRANSACTIONS;
+        }
+        if capabilities.watch {
+            flags |= v3::Capabilities::WATCH;
+        }
+        Ok(flags)
+    }
+
+    async fn drop(&mut self, store: Resource<v3::Store>) -> Result<()> {
+        self.stores.remove(store.rep());
+        Ok(())
+    }
+}
+
+impl v3::HostTransaction for KeyValueDispatch {
+    async fn new(&mut self, store: Resource<v3::Store>) -> Result<Resource<v3::Transaction>> {
+        let store = self.get_store(store)?.clone();
+        let rep = self
+            .transactions
+            .push(PendingTransaction {
+                store,
+                transaction: Transaction::default(),
+            })
+            .map_err(|()| anyhow::anyhow!("too many transactions opened"))?;
+        Ok(Resource::new_own(rep))
+    }
+
+    async fn expect(
+        &mut self,
+        transaction: Resource<v3::Transaction>,
+        key: String,
+        value: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let transaction = self.get_transaction(transaction)?;
+        transaction.conditions.push((key, value));
+        Ok(())
+    }
+
+    async fn set(
+        &mut self,
+        transaction: Resource<v3::Transaction>,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<()> {
+        let transaction = self.get_transaction(transaction)?;
+        transaction.writes.push(Write::Set(key, value));
+        Ok(())
+    }
+
+    async fn delete(&mut self, transaction: Resource<v3::Transaction>, key: String) -> Result<()> {
+        let transaction = self.get_transaction(transaction)?;
+        transaction.writes.push(Write::Delete(key));
+        Ok(())
+    }
+
+    #[instrument(name = "spin_key_value.commit", skip_all, fields(otel.kind = "client"))]
+    async fn commit(
+        &mut self,
+        transaction: Resource<v3::Transaction>,
+    ) -> Result<Result<bool, v3::Error>> {
+        let pending = self
+            .transactions
+            .get_mut(transaction.rep())
+            .context("invalid transaction")?;
+        let transaction = std::mem::take(&mut pending.transaction);
+        let store = pending.store.clone();
+        if transaction.writes.is_empty() && transaction.conditions.is_empty() {
+            return Ok(Ok(true));
+        }
+        Ok(store.transact(transaction).await.map_err(to_v3_error))
+    }
+
+    async fn drop(&mut self, transaction: Resource<v3::Transaction>) -> Result<()> {
+        self.transactions.remove(transaction.rep());
+        Ok(())
+    }
+}
+
+fn to_v3_error(err: Error) -> v3::Error {
+    match track_error_on_span(err) {
+        Error::StoreTableFull => v3::Error::StoreTableFull,
+        Error::NoSuchStore => v3::Error::NoSuchStore,
+        Error::AccessDenied => v3::Error::AccessDenied,
+        Error::Other(msg) => v3::Error::Other(msg),
+    }
+}
+
 /// Make sure that infrastructure related errors are tracked in the current span.
 fn track_error_on_span(err: Error) -> Error {
     let blame = match err {
@@ -212,6 +596,7 @@ impl wasi_keyvalue::store::Host for KeyValueDispatch {
     ) -> Result<Resource<wasi_keyvalue::store::Bucket>, wasi_keyvalue::store::Error> {
         if self.allowed_stores.contains(&identifier) {
             let store = self.manager.get(&identifier).await.map_err(to_wasi_err)?;
+            let store = self.wrap_store(&identifier, store);
             store.after_open().await.map_err(to_wasi_err)?;
             let store_idx = self
                 .stores
@@ -280,16 +665,12 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         cursor: Option<String>,
     ) -> Result<wasi_keyvalue::store::KeyResponse, wasi_keyvalue::store::Error> {
-        match cursor {
-            Some(_) => Err(wasi_keyvalue::store::Error::Other(
-                "list_keys: cursor not supported".to_owned(),
-            )),
-            None => {
-                let store = self.get_store_wasi(self_)?;
-                let keys = store.get_keys().await.map_err(to_wasi_err)?;
-                Ok(wasi_keyvalue::store::KeyResponse { keys, cursor: None })
-            }
-        }
+        let store = self.get_store_wasi(self_)?;
+        let KeyPage { keys, cursor } = store
+            .list_keys(None, cursor.as_deref(), MAX_LIST_KEYS_LIMIT)
+            .await
+            .map_err(to_wasi_err)?;
+        Ok(wasi_keyvalue::store::KeyResponse { keys, cursor })
     }
 
     async fn drop(&mut self, rep: Resource<Bucket>) -> anyhow::Result<()> {
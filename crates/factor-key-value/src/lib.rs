@@ -1,4 +1,6 @@
 mod host;
+mod metrics;
+mod quota;
 pub mod runtime_config;
 mod util;
 
@@ -16,7 +18,12 @@ use spin_locked_app::MetadataKey;
 
 /// Metadata key for key-value stores.
 pub const KEY_VALUE_STORES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("key_value_stores");
-pub use host::{log_cas_error, log_error, Error, KeyValueDispatch, Store, StoreManager};
+pub use host::{
+    log_cas_error, log_error, Capabilities, Error, KeyChange, KeyChangeKind, KeyChanges, KeyPage,
+    KeyValueDispatch, Store, StoreManager, Transaction, Write,
+};
+pub use quota::Quota;
+use quota::StoreQuota;
 pub use runtime_config::RuntimeConfig;
 use spin_core::async_trait;
 pub use util::DelegatingStoreManager;
@@ -42,6 +49,9 @@ impl Factor for KeyValueFactor {
     fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
         ctx.link_bindings(spin_world::v1::key_value::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(spin_world::v2::key_value::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(
+            spin_world::spin::key_value::key_value::add_to_linker::<_, FactorData<Self>>,
+        )?;
         ctx.link_bindings(spin_world::wasi::keyvalue::store::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(spin_world::wasi::keyvalue::batch::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(
@@ -55,6 +65,13 @@ impl Factor for KeyValueFactor {
         mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
         let store_managers = ctx.take_runtime_config().unwrap_or_default();
+        let quotas = store_managers
+            .quotas()
+            .map(|(label, quota)| {
+                let store_quota = StoreQuota::new(label.to_owned(), *quota);
+                (label.to_owned(), Arc::new(store_quota))
+            })
+            .collect();
 
         let delegating_manager = DelegatingStoreManager::new(store_managers);
         let store_manager = Arc::new(delegating_manager);
@@ -82,6 +99,7 @@ impl Factor for KeyValueFactor {
         Ok(AppState {
             store_manager,
             component_allowed_stores,
+            quotas,
         })
     }
 
@@ -95,9 +113,16 @@ impl Factor for KeyValueFactor {
             .get(ctx.app_component().id())
             .expect("component should be in component_stores")
             .clone();
+        let quotas = app_state
+            .quotas
+            .iter()
+            .filter(|(label, _)| allowed_stores.contains(*label))
+            .map(|(label, quota)| (label.clone(), quota.clone()))
+            .collect();
         Ok(InstanceBuilder {
             store_manager: app_state.store_manager.clone(),
             allowed_stores,
+            quotas,
         })
     }
 }
@@ -116,6 +141,10 @@ pub struct AppState {
     /// This is a map from component ID to the set of store labels that the
     /// component is allowed to use.
     component_allowed_stores: HashMap<String, HashSet<String>>,
+    /// The quotas enforced on stores, by label.
+    ///
+    /// These are shared by every instance so that usage is tracked across them.
+    quotas: HashMap<String, Arc<StoreQuota>>,
 }
 
 impl AppState {
@@ -177,6 +206,8 @@ pub struct InstanceBuilder {
     store_manager: Arc<AppStoreManager>,
     /// The allowed stores for this component instance.
     allowed_stores: HashSet<String>,
+    /// The quotas enforced on the allowed stores, by label.
+    quotas: HashMap<String, Arc<StoreQuota>>,
 }
 
 impl FactorInstanceBuilder for InstanceBuilder {
@@ -186,11 +217,11 @@ impl FactorInstanceBuilder for InstanceBuilder {
         let Self {
             store_manager,
             allowed_stores,
+            quotas,
         } = self;
-        Ok(KeyValueDispatch::new_with_capacity(
-            allowed_stores,
-            store_manager,
-            u32::MAX,
-        ))
+        Ok(
+            KeyValueDispatch::new_with_capacity(allowed_stores, store_manager, u32::MAX)
+                .with_quotas(quotas),
+        )
     }
 }
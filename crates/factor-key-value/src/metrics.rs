@@ -0,0 +1,175 @@
+use crate::{Capabilities, Cas, Error, KeyChanges, KeyPage, Store, SwapError, Transaction};
+use spin_core::async_trait;
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A [`Store`] which reports the count, duration and errors of each operation on another store
+/// through metrics.
+pub(crate) struct MeteredStore {
+    inner: Arc<dyn Store>,
+    label: Arc<str>,
+}
+
+impl MeteredStore {
+    pub(crate) fn new(inner: Arc<dyn Store>, label: &str) -> Self {
+        Self {
+            inner,
+            label: label.into(),
+        }
+    }
+}
+
+async fn record<T, E>(
+    label: &str,
+    operation: &'static str,
+    op: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let result = op.await;
+    let duration = start.elapsed().as_secs_f64();
+    spin_telemetry::metrics::monotonic_counter!(
+        spin.key_value_operations = 1,
+        store = label,
+        operation = operation
+    );
+    spin_telemetry::metrics::histogram!(
+        spin.key_value_operation_duration = duration,
+        store = label,
+        operation = operation
+    );
+    if result.is_err() {
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.key_value_errors = 1,
+            store = label,
+            operation = operation
+        );
+    }
+    result
+}
+
+#[async_trait]
+impl Store for MeteredStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        record(&self.label, "get", self.inner.get(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        record(&self.label, "set", self.inner.set(key, value)).await
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        record(
+            &self.label,
+            "set_with_ttl",
+            self.inner.set_with_ttl(key, value, ttl),
+        )
+        .await
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        record(&self.label, "get_ttl", self.inner.get_ttl(key)).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        record(&self.label, "delete", self.inner.delete(key)).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        record(&self.label, "exists", self.inner.exists(key)).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        record(&self.label, "get_keys", self.inner.get_keys()).await
+    }
+
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        record(
+            &self.label,
+            "list_keys",
+            self.inner.list_keys(prefix, cursor, limit),
+        )
+        .await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        record(&self.label, "get_many", self.inner.get_many(keys)).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        record(&self.label, "set_many", self.inner.set_many(key_values)).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        record(&self.label, "delete_many", self.inner.delete_many(keys)).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        record(&self.label, "increment", self.inner.increment(key, delta)).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = record(
+            &self.label,
+            "new_compare_and_swap",
+            self.inner.new_compare_and_swap(bucket_rep, key),
+        )
+        .await?;
+        Ok(Arc::new(MeteredCas {
+            inner,
+            label: self.label.clone(),
+        }))
+    }
+
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        record(&self.label, "transact", self.inner.transact(transaction)).await
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        record(&self.label, "watch", self.inner.watch(prefix)).await
+    }
+}
+
+/// A [`Cas`] which reports its operations through metrics.
+struct MeteredCas {
+    inner: Arc<dyn Cas>,
+    label: Arc<str>,
+}
+
+#[async_trait]
+impl Cas for MeteredCas {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        record(&self.label, "cas_current", self.inner.current()).await
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        record(&self.label, "cas_swap", self.inner.swap(value)).await
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.inner.key().await
+    }
+}
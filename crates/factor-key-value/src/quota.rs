@@ -0,0 +1,462 @@
+use crate::{Cas, Error, KeyChanges, KeyPage, Store, SwapError, Transaction, Write};
+use serde::Deserialize;
+use spin_core::async_trait;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The number of keys read at a time when measuring a store's usage or the change a write makes
+/// to it. This is the most DynamoDB reads in a single batch.
+const READ_BATCH_SIZE: u32 = 100;
+
+/// Limits on the data components may write to a store.
+///
+/// Sizes are in bytes; the size of an entry is the length of its key plus the length of its value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    /// The maximum number of keys in the store.
+    pub max_keys: Option<u64>,
+    /// The maximum length of a single value.
+    pub max_value_bytes: Option<u64>,
+    /// The maximum total size of the entries in the store.
+    ///
+    /// Measuring a store's total size means reading every value in it, so this is only done when
+    /// the total size is limited.
+    pub max_total_bytes: Option<u64>,
+}
+
+impl Quota {
+    /// Whether enforcing the quota requires knowing how much data the store holds.
+    fn tracks_usage(&self) -> bool {
+        self.max_keys.is_some() || self.max_total_bytes.is_some()
+    }
+}
+
+/// The data held by a store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Usage {
+    keys: u64,
+    bytes: u64,
+}
+
+/// The quota of a store, along with the store's usage, shared by every instance of an app.
+///
+/// Usage is measured once, when the store is first opened, and then kept up to date by the writes
+/// made through this host. Writes from elsewhere (and expiring keys) aren't seen, so the usage is
+/// only an estimate of the store's contents.
+pub(crate) struct StoreQuota {
+    label: String,
+    quota: Quota,
+    /// The store's usage, or `None` if it hasn't been measured yet.
+    ///
+    /// Each write reserves its change in usage before it's made, and gives it back if it fails,
+    /// so the lock is never held across a request to the store. Concurrent writes to the same key
+    /// are each accounted for as if the other hadn't been made.
+    usage: Mutex<Option<Usage>>,
+    /// Held while measuring the store's usage, so that it's only measured once.
+    measuring: tokio::sync::Mutex<()>,
+}
+
+/// The value a write leaves a key with.
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    /// The key is set to a value of the given length.
+    Set(usize),
+    /// The key is deleted.
+    Delete,
+    /// The key is incremented.
+    ///
+    /// The size of the incremented value depends on how the backend stores it, so it's accounted
+    /// for as the size of the current value, or of an i64 for a new key.
+    Increment,
+}
+
+impl StoreQuota {
+    pub(crate) fn new(label: String, quota: Quota) -> Self {
+        Self {
+            label,
+            quota,
+            usage: Mutex::new(None),
+            measuring: Default::default(),
+        }
+    }
+
+    /// Measures the usage of `store`, unless that has already been done.
+    async fn measure_once(&self, store: &dyn Store) -> Result<(), Error> {
+        if !self.quota.tracks_usage() {
+            return Ok(());
+        }
+        let _measuring = self.measuring.lock().await;
+        if self.usage.lock().unwrap().is_none() {
+            let measured = self.measure(store).await?;
+            self.set_usage(&mut self.usage.lock().unwrap(), measured);
+        }
+        Ok(())
+    }
+
+    /// Applies `write`, which makes each of `changes`, if doing so would not exceed the quota.
+    ///
+    /// `applied` reports whether a successful `write` changed the store (e.g. a transaction whose
+    /// conditions didn't hold doesn't).
+    async fn apply<T, E>(
+        &self,
+        store: &dyn Store,
+        changes: Vec<(String, Change)>,
+        write: impl Future<Output = Result<T, E>>,
+        applied: impl FnOnce(&T) -> bool,
+    ) -> Result<Result<T, E>, Error> {
+        if let Some(max) = self.quota.max_value_bytes {
+            let lens = changes.iter().filter_map(|(_, change)| match change {
+                Change::Set(len) => Some(*len),
+                Change::Delete | Change::Increment => None,
+            });
+            if let Some(len) = lens.max() {
+                if len as u64 > max {
+                    return Err(self.exceeded(format!(
+                        "a value of {len} bytes exceeds the maximum of {max} bytes"
+                    )));
+                }
+            }
+        }
+        if !self.quota.tracks_usage() || changes.is_empty() {
+            return Ok(write.await);
+        }
+
+        // Only the last change to each key matters.
+        let changes = changes.into_iter().collect::<HashMap<_, _>>();
+        // Only does anything if the store was written to without being opened
+        self.measure_once(store).await?;
+        let delta = self.delta(store, changes).await?;
+        self.reserve(delta)?;
+
+        let result = write.await;
+        match &result {
+            Ok(value) if applied(value) => {}
+            // A failed write is assumed not to have been applied, as it usually won't have been.
+            _ => self.release(delta),
+        }
+        Ok(result)
+    }
+
+    /// Adds `delta` to the store's usage, unless that would exceed the quota.
+    fn reserve(&self, delta: UsageDelta) -> Result<(), Error> {
+        let mut usage = self.usage.lock().unwrap();
+        let current = usage.unwrap_or_default();
+        let updated = delta.apply(current);
+        self.check(current, updated)
+            .map_err(|reason| self.exceeded(reason))?;
+        self.set_usage(&mut usage, updated);
+        Ok(())
+    }
+
+    /// Takes back `delta`, reserved by a write which wasn't applied, from the store's usage.
+    fn release(&self, delta: UsageDelta) {
+        let mut usage = self.usage.lock().unwrap();
+        let updated = delta.reverse().apply(usage.unwrap_or_default());
+        self.set_usage(&mut usage, updated);
+    }
+
+    /// Checks that `updated` usage is within the quota, unless it is no greater than `current`
+    /// usage (so that stores over their quota can still be shrunk).
+    fn check(&self, current: Usage, updated: Usage) -> Result<(), String> {
+        if let Some(max) = self.quota.max_keys {
+            if updated.keys > max && updated.keys > current.keys {
+                return Err(format!("the store may hold at most {max} keys"));
+            }
+        }
+        if let Some(max) = self.quota.max_total_bytes {
+            if updated.bytes > max && updated.bytes > current.bytes {
+                return Err(format!("the store may hold at most {max} bytes"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The change in usage from applying `changes`, found by reading the keys' current values.
+    async fn delta(
+        &self,
+        store: &dyn Store,
+        changes: HashMap<String, Change>,
+    ) -> Result<UsageDelta, Error> {
+        let keys = changes.keys().cloned().collect::<Vec<_>>();
+        let mut old_lens = HashMap::new();
+        for keys in keys.chunks(READ_BATCH_SIZE as usize) {
+            for (key, value) in store.get_many(keys.to_vec()).await? {
+                if let Some(value) = value {
+                    old_lens.insert(key, value.len());
+                }
+            }
+        }
+        let mut delta = UsageDelta::default();
+        for (key, change) in changes {
+            let old_len = old_lens.get(&key).copied();
+            let new_len = match change {
+                Change::Set(len) => Some(len),
+                Change::Delete => None,
+                Change::Increment => Some(old_len.unwrap_or(size_of::<i64>())),
+            };
+            let old = old_len.map(|len| entry_size(&key, len));
+            let new = new_len.map(|len| entry_size(&key, len));
+            delta.keys += i64::from(new.is_some()) - i64::from(old.is_some());
+            delta.bytes += new.unwrap_or(0) as i64 - old.unwrap_or(0) as i64;
+        }
+        Ok(delta)
+    }
+
+    /// Measures the usage of `store` by listing its keys and, if its total size is limited,
+    /// reading every entry.
+    async fn measure(&self, store: &dyn Store) -> Result<Usage, Error> {
+        let mut usage = Usage::default();
+        let mut cursor = None;
+        loop {
+            let page = store
+                .list_keys(None, cursor.as_deref(), READ_BATCH_SIZE)
+                .await?;
+            if self.quota.max_total_bytes.is_none() {
+                usage.keys += page.keys.len() as u64;
+            } else if !page.keys.is_empty() {
+                for (key, value) in store.get_many(page.keys).await? {
+                    if let Some(value) = value {
+                        usage.keys += 1;
+                        usage.bytes += entry_size(&key, value.len());
+                    }
+                }
+            }
+            cursor = page.cursor;
+            if cursor.is_none() {
+                return Ok(usage);
+            }
+        }
+    }
+
+    /// Replaces the tracked usage, reporting the change through metrics.
+    ///
+    /// Usage which hasn't been measured yet is reported as zero. The total size is only reported
+    /// if it's limited, as it isn't measured otherwise.
+    fn set_usage(&self, usage: &mut Option<Usage>, new: Usage) {
+        let old = usage.unwrap_or_default();
+        let keys = new.keys as i64 - old.keys as i64;
+        if keys != 0 {
+            spin_telemetry::metrics::counter!(spin.key_value_keys = keys, store = self.label);
+        }
+        let bytes = new.bytes as i64 - old.bytes as i64;
+        if bytes != 0 && self.quota.max_total_bytes.is_some() {
+            spin_telemetry::metrics::counter!(spin.key_value_bytes = bytes, store = self.label);
+        }
+        *usage = Some(new);
+    }
+
+    fn exceeded(&self, reason: String) -> Error {
+        tracing::warn!(
+            "error.type" = "key_value_quota_exceeded",
+            store = self.label,
+            reason,
+            "key-value store quota exceeded",
+        );
+        Error::Other(format!(
+            "quota exceeded for key-value store {:?}: {reason}",
+            self.label
+        ))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct UsageDelta {
+    keys: i64,
+    bytes: i64,
+}
+
+impl UsageDelta {
+    fn reverse(self) -> Self {
+        Self {
+            keys: -self.keys,
+            bytes: -self.bytes,
+        }
+    }
+
+    fn apply(self, usage: Usage) -> Usage {
+        Usage {
+            keys: usage.keys.saturating_add_signed(self.keys),
+            bytes: usage.bytes.saturating_add_signed(self.bytes),
+        }
+    }
+}
+
+fn entry_size(key: &str, value_len: usize) -> u64 {
+    (key.len() + value_len) as u64
+}
+
+/// A [`Store`] which enforces a [`StoreQuota`] on writes before passing them to another store.
+pub(crate) struct QuotaStore {
+    inner: Arc<dyn Store>,
+    quota: Arc<StoreQuota>,
+}
+
+impl QuotaStore {
+    pub(crate) fn new(inner: Arc<dyn Store>, quota: Arc<StoreQuota>) -> Self {
+        Self { inner, quota }
+    }
+
+    async fn apply<T>(
+        &self,
+        changes: Vec<(String, Change)>,
+        write: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        self.quota
+            .apply(&*self.inner, changes, write, |_| true)
+            .await?
+    }
+}
+
+#[async_trait]
+impl Store for QuotaStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await?;
+        self.quota.measure_once(&*self.inner).await
+    }
+
+    fn capabilities(&self) -> crate::Capabilities {
+        self.inner.capabilities()
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let changes = vec![(key.to_owned(), Change::Set(value.len()))];
+        self.apply(changes, self.inner.set(key, value)).await
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let changes = vec![(key.to_owned(), Change::Set(value.len()))];
+        self.apply(changes, self.inner.set_with_ttl(key, value, ttl))
+            .await
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        self.inner.get_ttl(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let changes = vec![(key.to_owned(), Change::Delete)];
+        self.apply(changes, self.inner.delete(key)).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        self.inner.list_keys(prefix, cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.inner.get_many(keys).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let changes = key_values
+            .iter()
+            .map(|(key, value)| (key.clone(), Change::Set(value.len())))
+            .collect();
+        self.apply(changes, self.inner.set_many(key_values)).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let changes = keys
+            .iter()
+            .map(|key| (key.clone(), Change::Delete))
+            .collect();
+        self.apply(changes, self.inner.delete_many(keys)).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let changes = vec![(key.clone(), Change::Increment)];
+        self.apply(changes, self.inner.increment(key, delta)).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = self.inner.new_compare_and_swap(bucket_rep, key).await?;
+        Ok(Arc::new(QuotaCas {
+            inner,
+            key: key.to_owned(),
+            store: self.inner.clone(),
+            quota: self.quota.clone(),
+        }))
+    }
+
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let changes = transaction
+            .writes
+            .iter()
+            .map(|write| match write {
+                Write::Set(key, value) => (key.clone(), Change::Set(value.len())),
+                Write::Delete(key) => (key.clone(), Change::Delete),
+            })
+            .collect();
+        self.quota
+            .apply(
+                &*self.inner,
+                changes,
+                self.inner.transact(transaction),
+                |committed| *committed,
+            )
+            .await?
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        self.inner.watch(prefix).await
+    }
+}
+
+/// A [`Cas`] which enforces a [`StoreQuota`] on swaps.
+struct QuotaCas {
+    inner: Arc<dyn Cas>,
+    key: String,
+    store: Arc<dyn Store>,
+    quota: Arc<StoreQuota>,
+}
+
+#[async_trait]
+impl Cas for QuotaCas {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.current().await
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let changes = vec![(self.key.clone(), Change::Set(value.len()))];
+        self.quota
+            .apply(&*self.store, changes, self.inner.swap(value), |_| true)
+            .await
+            .map_err(|err| match err {
+                Error::Other(msg) => SwapError::Other(msg),
+                err => SwapError::Other(format!("{err:?}")),
+            })?
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.inner.key().await
+    }
+}
@@ -2,13 +2,15 @@ pub mod spin;
 
 use std::{collections::HashMap, sync::Arc};
 
-use crate::StoreManager;
+use crate::{Quota, StoreManager};
 
 /// Runtime configuration for all key value stores.
 #[derive(Default, Clone)]
 pub struct RuntimeConfig {
     /// Map of store names to store managers.
     store_managers: HashMap<String, Arc<dyn StoreManager>>,
+    /// Map of store names to the quotas enforced on them.
+    quotas: HashMap<String, Quota>,
 }
 
 impl RuntimeConfig {
@@ -24,6 +26,18 @@ impl RuntimeConfig {
         self.store_managers.contains_key(label)
     }
 
+    /// Sets the quota enforced on the store with the given label.
+    pub fn set_quota(&mut self, label: String, quota: Quota) {
+        self.quotas.insert(label, quota);
+    }
+
+    /// Returns the quotas enforced on stores, by label.
+    pub fn quotas(&self) -> impl Iterator<Item = (&str, &Quota)> {
+        self.quotas
+            .iter()
+            .map(|(label, quota)| (label.as_str(), quota))
+    }
+
     /// Returns the store manager for the store with the given label.
     pub fn get_store_manager(&self, label: &str) -> Option<Arc<dyn StoreManager>> {
         self.store_managers.get(label).cloned()
@@ -1,6 +1,6 @@
 //! Runtime configuration implementation used by Spin CLI.
 
-use crate::{RuntimeConfig, StoreManager};
+use crate::{Quota, RuntimeConfig, StoreManager};
 use anyhow::Context as _;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -122,14 +122,17 @@ impl RuntimeConfigResolver {
         let Some(table) = table.and_then(|t| t.get("key_value_store")) else {
             return Ok(None);
         };
-        let table: HashMap<String, StoreConfig> = table.clone().try_into()?;
+        let table: HashMap<String, LabelledStoreConfig> = table.clone().try_into()?;
 
         let mut runtime_config = RuntimeConfig::default();
-        for (label, config) in table {
+        for (label, LabelledStoreConfig { quota, config }) in table {
             let store_manager = self.store_manager_from_config(config).with_context(|| {
                 format!("could not configure key-value store with label '{label}'")
             })?;
             runtime_config.add_store_manager(label.clone(), store_manager);
+            if let Some(quota) = quota {
+                runtime_config.set_quota(label, quota);
+            }
         }
 
         Ok(Some(runtime_config))
@@ -139,7 +142,7 @@ impl RuntimeConfigResolver {
     ///
     /// Errors if there is no [`MakeKeyValueStore`] registered for the store config's type
     /// or if the store manager cannot be created from the config.
-    fn store_manager_from_config(
+    pub fn store_manager_from_config(
         &self,
         config: StoreConfig,
     ) -> anyhow::Result<Arc<dyn StoreManager>> {
@@ -151,6 +154,16 @@ impl RuntimeConfigResolver {
     }
 }
 
+/// The configuration of a labelled store in the `key_value_store` table.
+#[derive(Deserialize)]
+struct LabelledStoreConfig {
+    /// The quota enforced on the store's data, if any.
+    #[serde(default)]
+    quota: Option<Quota>,
+    #[serde(flatten)]
+    config: StoreConfig,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct StoreConfig {
     #[serde(rename = "type")]
@@ -1,8 +1,12 @@
 use anyhow::bail;
 use spin_core::async_trait;
-use spin_factor_key_value::{Cas, KeyValueFactor, RuntimeConfig, Store, StoreManager};
+use spin_core::wasmtime::component::Resource;
+use spin_factor_key_value::{
+    Cas, KeyValueDispatch, KeyValueFactor, Quota, RuntimeConfig, Store, StoreManager,
+};
 use spin_factors::RuntimeFactors;
 use spin_factors_test::{toml, TestEnvironment};
+use spin_key_value_memory::KeyValueMemory;
 use spin_world::v2::key_value::{Error, HostStore};
 use std::{collections::HashSet, sync::Arc};
 
@@ -98,6 +102,63 @@ async fn errors_when_store_is_not_allowed() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn enforces_store_quotas() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), Arc::new(KeyValueMemory::new()));
+    runtime_config.set_quota(
+        "default".into(),
+        Quota {
+            max_keys: Some(2),
+            max_value_bytes: Some(4),
+            max_total_bytes: None,
+        },
+    );
+    let factors = TestFactors {
+        key_value: KeyValueFactor::new(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+    let kv = &mut state.key_value;
+
+    let store = kv.open("default".to_owned()).await?.unwrap().rep();
+    assert!(set(kv, store, "a", "1").await?.is_ok());
+    assert!(set(kv, store, "b", "2").await?.is_ok());
+    // Too many keys
+    assert!(set(kv, store, "c", "3").await?.is_err());
+    // Overwriting a key doesn't add one
+    assert!(set(kv, store, "a", "11").await?.is_ok());
+    // Value too large
+    assert!(set(kv, store, "a", "11111").await?.is_err());
+
+    kv.delete(Resource::new_borrow(store), "b".to_owned())
+        .await?
+        .unwrap();
+    assert!(set(kv, store, "c", "3").await?.is_ok());
+    Ok(())
+}
+
+async fn set(
+    kv: &mut KeyValueDispatch,
+    store: u32,
+    key: &str,
+    value: &str,
+) -> anyhow::Result<Result<(), Error>> {
+    kv.set(
+        Resource::new_borrow(store),
+        key.to_owned(),
+        value.as_bytes().to_vec(),
+    )
+    .await
+}
+
 fn mock_store_manager() -> Arc<dyn StoreManager> {
     Arc::new(MockStoreManager)
 }
@@ -1,10 +1,12 @@
 mod host;
+pub mod migrations;
 pub mod runtime_config;
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use host::InstanceState;
+use migrations::{resolve_migrations, Migration, SQLITE_MIGRATIONS_KEY};
 
 use async_trait::async_trait;
 use spin_factors::{anyhow, Factor, FactorData};
@@ -43,10 +45,10 @@ impl Factor for SqliteFactor {
         &self,
         mut ctx: spin_factors::ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        let connection_creators = ctx
-            .take_runtime_config()
-            .unwrap_or_default()
-            .connection_creators;
+        let RuntimeConfig {
+            connection_creators,
+            migrations: configured_migrations,
+        } = ctx.take_runtime_config().unwrap_or_default();
 
         let allowed_databases = ctx
             .app()
@@ -69,7 +71,24 @@ impl Factor for SqliteFactor {
             connection_creators.contains_key(label)
         })?;
 
-        Ok(AppState::new(allowed_databases, connection_creators))
+        let mut declared_migrations = Vec::new();
+        for component in ctx.app().components() {
+            if let Some(migrations) = component.get_metadata(SQLITE_MIGRATIONS_KEY)? {
+                let databases = component
+                    .get_metadata(ALLOWED_DATABASES_KEY)?
+                    .unwrap_or_default();
+                declared_migrations.push((component.id().to_owned(), databases, migrations));
+            }
+        }
+        let migrations = resolve_migrations(declared_migrations, configured_migrations)?;
+        for label in migrations.keys() {
+            anyhow::ensure!(
+                connection_creators.contains_key(label),
+                "Migrations are configured for SQLite database '{label}' which is not defined"
+            );
+        }
+
+        Ok(AppState::new(allowed_databases, connection_creators).with_migrations(migrations))
     }
 
     fn prepare<T: spin_factors::RuntimeFactors>(
@@ -131,6 +150,8 @@ pub struct AppState {
     allowed_databases: HashMap<String, Arc<HashSet<String>>>,
     /// A mapping from database label to a connection creator.
     connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
+    /// A mapping from database label to the migrations to apply to it.
+    migrations: HashMap<String, Vec<Migration>>,
 }
 
 impl AppState {
@@ -142,9 +163,23 @@ impl AppState {
         Self {
             allowed_databases,
             connection_creators,
+            migrations: HashMap::new(),
         }
     }
 
+    /// Sets the migrations to apply to each database, by label.
+    pub fn with_migrations(mut self, migrations: HashMap<String, Vec<Migration>>) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Returns the migrations to apply to each database, by label.
+    pub fn migrations(&self) -> impl Iterator<Item = (&str, &[Migration])> {
+        self.migrations
+            .iter()
+            .map(|(label, migrations)| (label.as_str(), migrations.as_slice()))
+    }
+
     /// Get a connection for a given database label.
     ///
     /// Returns `None` if there is no connection creator for the given label.
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spin_factors::anyhow::{self, bail, Context as _};
+use spin_locked_app::MetadataKey;
+use spin_world::spin::sqlite::sqlite as v3;
+
+use crate::Connection;
+
+/// Metadata key for the migrations to apply to each of a component's databases.
+pub const SQLITE_MIGRATIONS_KEY: MetadataKey<Vec<Migration>> =
+    MetadataKey::new("sqlite_migrations");
+
+/// The table in which applied migrations are recorded.
+const MIGRATIONS_TABLE: &str = "_spin_migrations";
+
+/// A single schema migration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Migration {
+    /// The name of the migration, which determines the order in which migrations are applied.
+    pub name: String,
+    /// The SQL statements making up the migration.
+    pub sql: String,
+}
+
+impl Migration {
+    /// The hex SHA-256 digest of the migration's SQL.
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.sql.as_bytes()))
+    }
+}
+
+/// Reads the migrations in a directory: the `.sql` files it contains, in file name order.
+pub fn read_migrations_dir(dir: &Path) -> anyhow::Result<Vec<Migration>> {
+    let migrations = spin_common::migrations::read_migrations_dir(dir)?
+        .into_iter()
+        .map(|(name, sql)| Migration { name, sql })
+        .collect();
+    Ok(migrations)
+}
+
+/// Combines the migrations declared by components, given as `(component id, databases,
+/// migrations)`, with those configured for individual databases, which take precedence.
+///
+/// Components declaring different migrations for the same database are an error.
+pub fn resolve_migrations(
+    declared: impl IntoIterator<Item = (String, Vec<String>, Vec<Migration>)>,
+    configured: HashMap<String, Vec<Migration>>,
+) -> anyhow::Result<HashMap<String, Vec<Migration>>> {
+    let mut resolved: HashMap<String, (String, Vec<Migration>)> = HashMap::new();
+    for (component_id, databases, migrations) in declared {
+        for label in databases {
+            if configured.contains_key(&label) {
+                continue;
+            }
+            match resolved.get(&label) {
+                Some((other, existing)) if *existing != migrations => bail!(
+                    "components {other} and {component_id} declare different migrations for SQLite database '{label}'"
+                ),
+                Some(_) => {}
+                None => {
+                    resolved.insert(label, (component_id.clone(), migrations.clone()));
+                }
+            }
+        }
+    }
+    Ok(resolved
+        .into_iter()
+        .map(|(label, (_, migrations))| (label, migrations))
+        .chain(configured)
+        .collect())
+}
+
+/// The state of a migration in a database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigrationStatus {
+    /// The migration has not been applied.
+    Pending,
+    /// The migration was applied at the given time.
+    Applied { applied_at: String },
+    /// The migration was applied but its SQL has changed since.
+    Modified { applied_at: String },
+    /// The migration was applied but is no longer in the list of migrations.
+    Missing { applied_at: String },
+}
+
+/// A migration recorded in the migrations table.
+struct AppliedMigration {
+    checksum: String,
+    applied_at: String,
+}
+
+/// Returns the status of each migration, followed by any applied migrations which are no longer
+/// in `migrations`.
+pub async fn migration_status(
+    connection: &dyn Connection,
+    migrations: &[Migration],
+) -> anyhow::Result<Vec<(String, MigrationStatus)>> {
+    ensure_migrations_table(connection).await?;
+    let mut applied = applied_migrations(connection).await?;
+    let mut statuses = migrations
+        .iter()
+        .map(|migration| {
+            let status = match applied.remove(&migration.name) {
+                None => MigrationStatus::Pending,
+                Some(a) if a.checksum == migration.checksum() => MigrationStatus::Applied {
+                    applied_at: a.applied_at,
+                },
+                Some(a) => MigrationStatus::Modified {
+                    applied_at: a.applied_at,
+                },
+            };
+            (migration.name.clone(), status)
+        })
+        .collect::<Vec<_>>();
+    let mut missing = applied
+        .into_iter()
+        .map(|(name, a)| {
+            let status = MigrationStatus::Missing {
+                applied_at: a.applied_at,
+            };
+            (name, status)
+        })
+        .collect::<Vec<_>>();
+    missing.sort_by(|a, b| a.0.cmp(&b.0));
+    statuses.extend(missing);
+    Ok(statuses)
+}
+
+/// Applies the migrations which have not yet been applied to the database, in order, returning
+/// the names of those applied.
+///
+/// Each migration runs in its own transaction together with its entry in the migrations table,
+/// so migrations must not contain transaction statements of their own. Fails without applying
+/// anything if a migration which has already been applied has since been changed.
+pub async fn apply_migrations(
+    connection: &dyn Connection,
+    migrations: &[Migration],
+) -> anyhow::Result<Vec<String>> {
+    ensure_migrations_table(connection).await?;
+    let applied = applied_migrations(connection).await?;
+    for migration in migrations {
+        if let Some(a) = applied.get(&migration.name) {
+            if a.checksum != migration.checksum() {
+                bail!(
+                    "migration '{}' has changed since it was applied at {}",
+                    migration.name,
+                    a.applied_at
+                );
+            }
+        }
+    }
+
+    let mut newly_applied = Vec::new();
+    for migration in migrations {
+        if applied.contains_key(&migration.name) {
+            continue;
+        }
+        let checksum = migration.checksum();
+        let batch = format!(
+            "BEGIN IMMEDIATE;\nINSERT INTO {MIGRATIONS_TABLE} (name, checksum) VALUES ({}, {});\n{}\n;\nCOMMIT;",
+            quote(&migration.name),
+            quote(&checksum),
+            migration.sql
+        );
+        if let Err(e) = connection.execute_batch(&batch).await {
+            let _ = connection.execute_batch("ROLLBACK;").await;
+            // Another process sharing the database may have applied the migration first.
+            let concurrent = applied_migrations(connection).await?;
+            match concurrent.get(&migration.name) {
+                Some(a) if a.checksum == checksum => continue,
+                _ => {
+                    return Err(e)
+                        .with_context(|| format!("failed to apply migration '{}'", migration.name))
+                }
+            }
+        }
+        tracing::info!("Applied SQLite migration '{}'", migration.name);
+        newly_applied.push(migration.name.clone());
+    }
+    Ok(newly_applied)
+}
+
+async fn ensure_migrations_table(connection: &dyn Connection) -> anyhow::Result<()> {
+    connection
+        .execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE} (
+                name TEXT PRIMARY KEY NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );"
+        ))
+        .await
+        .context("failed to create migrations table")
+}
+
+async fn applied_migrations(
+    connection: &dyn Connection,
+) -> anyhow::Result<HashMap<String, AppliedMigration>> {
+    let result = connection
+        .query(
+            &format!("SELECT name, checksum, applied_at FROM {MIGRATIONS_TABLE}"),
+            Vec::new(),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to read migrations table: {e:?}"))?;
+    result
+        .rows
+        .into_iter()
+        .map(|row| match row.values.as_slice() {
+            [v3::Value::Text(name), v3::Value::Text(checksum), v3::Value::Text(applied_at)] => {
+                Ok((
+                    name.clone(),
+                    AppliedMigration {
+                        checksum: checksum.clone(),
+                        applied_at: applied_at.clone(),
+                    },
+                ))
+            }
+            _ => bail!("unexpected row in migrations table: {:?}", row.values),
+        })
+        .collect()
+}
+
+/// Quotes a string as an SQL string literal.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use super::*;
+
+    /// A fake connection which records executed batches and answers queries of the
+    /// migrations table from the batches that committed.
+    #[derive(Default)]
+    struct MockConnection {
+        batches: Mutex<Vec<String>>,
+        rows: Mutex<Vec<(String, String)>>,
+        fail_on: Option<String>,
+    }
+
+    #[async_trait]
+    impl Connection for MockConnection {
+        async fn query(
+            &self,
+            _query: &str,
+            _parameters: Vec<v3::Value>,
+        ) -> Result<v3::QueryResult, v3::Error> {
+            let rows = self
+                .rows
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, checksum)| v3::RowResult {
+                    values: vec![
+                        v3::Value::Text(name.clone()),
+                        v3::Value::Text(checksum.clone()),
+                        v3::Value::Text("2024-01-01 00:00:00".into()),
+                    ],
+                })
+                .collect();
+            Ok(v3::QueryResult {
+                columns: vec!["name".into(), "checksum".into(), "applied_at".into()],
+                rows,
+            })
+        }
+
+        async fn execute_batch(&self, statements: &str) -> anyhow::Result<()> {
+            self.batches.lock().unwrap().push(statements.to_owned());
+            if self
+                .fail_on
+                .as_deref()
+                .is_some_and(|sql| statements.contains(sql))
+            {
+                bail!("syntax error");
+            }
+            if let Some(insert) = statements
+                .lines()
+                .find(|line| line.starts_with("INSERT INTO _spin_migrations"))
+            {
+                let values = insert.split("VALUES (").nth(1).unwrap();
+                let mut parts = values.trim_end_matches(");").split(", ");
+                let name = parts.next().unwrap().trim_matches('\'').to_owned();
+                let checksum = parts.next().unwrap().trim_matches('\'').to_owned();
+                self.rows.lock().unwrap().push((name, checksum));
+            }
+            Ok(())
+        }
+
+        async fn changes(&self) -> Result<u64, v3::Error> {
+            Ok(0)
+        }
+
+        async fn last_insert_rowid(&self) -> Result<i64, v3::Error> {
+            Ok(0)
+        }
+    }
+
+    fn migration(name: &str, sql: &str) -> Migration {
+        Migration {
+            name: name.into(),
+            sql: sql.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_pending_migrations_once() {
+        let connection = MockConnection::default();
+        let migrations = vec![
+            migration("001_users.sql", "CREATE TABLE users (id INTEGER);"),
+            migration("002_posts.sql", "CREATE TABLE posts (id INTEGER);"),
+        ];
+
+        let applied = apply_migrations(&connection, &migrations).await.unwrap();
+        assert_eq!(applied, ["001_users.sql", "002_posts.sql"]);
+
+        let applied = apply_migrations(&connection, &migrations).await.unwrap();
+        assert!(applied.is_empty());
+
+        let statuses = migration_status(&connection, &migrations).await.unwrap();
+        assert!(statuses
+            .iter()
+            .all(|(_, status)| matches!(status, MigrationStatus::Applied { .. })));
+    }
+
+    #[tokio::test]
+    async fn rejects_modified_migrations() {
+        let connection = MockConnection::default();
+        apply_migrations(&connection, &[migration("001.sql", "SELECT 1;")])
+            .await
+            .unwrap();
+
+        let changed = [
+            migration("001.sql", "SELECT 2;"),
+            migration("002.sql", "SELECT 3;"),
+        ];
+        let err = apply_migrations(&connection, &changed).await.unwrap_err();
+        assert!(err.to_string().contains("has changed"), "{err}");
+
+        let statuses = migration_status(&connection, &changed).await.unwrap();
+        assert!(matches!(statuses[0].1, MigrationStatus::Modified { .. }));
+        assert_eq!(statuses[1].1, MigrationStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn reports_failed_and_missing_migrations() {
+        let connection = MockConnection {
+            fail_on: Some("BROKEN".into()),
+            ..Default::default()
+        };
+        apply_migrations(&connection, &[migration("001.sql", "SELECT 1;")])
+            .await
+            .unwrap();
+
+        let err = apply_migrations(&connection, &[migration("002.sql", "BROKEN")])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("002.sql"), "{err}");
+        assert_eq!(
+            connection.batches.lock().unwrap().last().unwrap(),
+            "ROLLBACK;"
+        );
+
+        let statuses = migration_status(&connection, &[]).await.unwrap();
+        assert!(matches!(statuses[0].1, MigrationStatus::Missing { .. }));
+    }
+
+    #[test]
+    fn reads_sql_files_in_name_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("002_b.sql"), "SELECT 2;").unwrap();
+        std::fs::write(dir.path().join("001_a.sql"), "SELECT 1;").unwrap();
+        std::fs::write(dir.path().join("README.md"), "notes").unwrap();
+
+        let migrations = read_migrations_dir(dir.path()).unwrap();
+        assert_eq!(
+            migrations,
+            [
+                migration("001_a.sql", "SELECT 1;"),
+                migration("002_b.sql", "SELECT 2;")
+            ]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::Arc};
 
-use crate::ConnectionCreator;
+use crate::{migrations::Migration, ConnectionCreator};
 
 /// A runtime configuration for SQLite databases.
 ///
@@ -8,4 +8,7 @@ use crate::ConnectionCreator;
 #[derive(Default)]
 pub struct RuntimeConfig {
     pub connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
+    /// Migrations to apply to databases, by label. These take precedence over
+    /// migrations declared by components for the same database.
+    pub migrations: HashMap<String, Vec<Migration>>,
 }
@@ -1,9 +1,10 @@
 use std::{
     collections::{HashMap, HashSet},
+    path::Path,
     sync::Arc,
 };
 
-use spin_factor_sqlite::{RuntimeConfig, SqliteFactor};
+use spin_factor_sqlite::{migrations::Migration, RuntimeConfig, SqliteFactor};
 use spin_factors::{
     anyhow::{self, bail, Context as _},
     RuntimeFactors,
@@ -71,6 +72,7 @@ async fn it_works_when_database_is_configured() -> anyhow::Result<()> {
     let runtime_config = TestFactorsRuntimeConfig {
         sqlite: Some(RuntimeConfig {
             connection_creators,
+            ..Default::default()
         }),
     };
     let env = TestEnvironment::new(factors)
@@ -95,6 +97,101 @@ async fn it_works_when_database_is_configured() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn resolves_migrations_from_components_and_runtime_config() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("002_posts.sql"), "CREATE TABLE posts (id);")?;
+    std::fs::write(dir.path().join("001_users.sql"), "CREATE TABLE users (id);")?;
+
+    let factors = TestFactors {
+        sqlite: SqliteFactor::new(),
+    };
+    let mut connection_creators = HashMap::new();
+    connection_creators.insert("foo".to_owned(), Arc::new(MockConnectionCreator) as _);
+    connection_creators.insert("bar".to_owned(), Arc::new(MockConnectionCreator) as _);
+    let configured = vec![Migration {
+        name: "001_configured.sql".into(),
+        sql: "CREATE TABLE configured (id);".into(),
+    }];
+    let runtime_config = TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+            migrations: [("bar".to_owned(), configured.clone())].into(),
+        }),
+    };
+    let mut env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["foo", "bar"]
+        })
+        .runtime_config(runtime_config)?;
+    set_migrations_dir(&mut env, "test-component", dir.path());
+
+    let app = env.build_configured_app().await?;
+    let state = app.app_state::<SqliteFactor>()?;
+    let migrations = state
+        .migrations()
+        .map(|(label, migrations)| (label.to_owned(), migrations.to_vec()))
+        .collect::<HashMap<_, _>>();
+
+    let names = migrations["foo"]
+        .iter()
+        .map(|m| m.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["001_users.sql", "002_posts.sql"]);
+    assert_eq!(migrations["bar"], configured);
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_components_declare_different_migrations() -> anyhow::Result<()> {
+    let first = tempfile::tempdir()?;
+    std::fs::write(first.path().join("001.sql"), "CREATE TABLE a (id);")?;
+    let second = tempfile::tempdir()?;
+    std::fs::write(second.path().join("001.sql"), "CREATE TABLE b (id);")?;
+
+    let factors = TestFactors {
+        sqlite: SqliteFactor::new(),
+    };
+    let mut connection_creators = HashMap::new();
+    connection_creators.insert("foo".to_owned(), Arc::new(MockConnectionCreator) as _);
+    let runtime_config = TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+            ..Default::default()
+        }),
+    };
+    let mut env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.first]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["foo"]
+            [component.second]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["foo"]
+        })
+        .runtime_config(runtime_config)?;
+    set_migrations_dir(&mut env, "first", first.path());
+    set_migrations_dir(&mut env, "second", second.path());
+
+    let Err(err) = env.build_configured_app().await else {
+        bail!("Expected build_configured_app to error but it did not");
+    };
+    assert!(err.to_string().contains("declare different migrations"));
+    Ok(())
+}
+
+/// Sets a component's `sqlite_migrations` in the test manifest.
+fn set_migrations_dir(env: &mut TestEnvironment<TestFactors>, component: &str, dir: &Path) {
+    env.manifest
+        .get_mut("component")
+        .and_then(|components| components.get_mut(component))
+        .and_then(|component| component.as_table_mut())
+        .expect("component should be in manifest")
+        .insert("sqlite_migrations".into(), dir.to_str().unwrap().into());
+}
+
 /// A connection creator that returns a mock connection.
 struct MockConnectionCreator;
 
@@ -182,6 +182,21 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
                 })
             }
 
+            fn reset_instance_state(
+                &self,
+                state: &mut Self::InstanceState,
+                builders: Self::InstanceBuilders,
+            ) -> #Result<()> {
+                #(
+                    #Factor::reset_instance_state(
+                        &self.#factor_names,
+                        &mut state.#factor_names,
+                        builders.#factor_names.unwrap(),
+                    ).map_err(#Error::factor_reset_error::<#factor_types>)?;
+                )*
+                Ok(())
+            }
+
             fn app_state<F: #Factor>(app_state: &Self::AppState) -> Option<&F::AppState> {
                 #(
                     if let Some(state) = &app_state.#factor_names {
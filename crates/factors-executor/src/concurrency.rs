@@ -0,0 +1,223 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use spin_app::locked::ComponentLimits;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The error returned by [`FactorsExecutorApp::prepare`] when a component is
+/// at its concurrency limit and the invocation could not be queued, or timed
+/// out waiting in the queue.
+///
+/// [`FactorsExecutorApp::prepare`]: crate::FactorsExecutorApp::prepare
+#[derive(Debug)]
+pub struct ConcurrencyLimitExceeded {
+    component_id: String,
+    reason: &'static str,
+}
+
+impl std::fmt::Display for ConcurrencyLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "component {:?} is at its concurrency limit: {}",
+            self.component_id, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ConcurrencyLimitExceeded {}
+
+/// The concurrency settings of a component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ConcurrencySettings {
+    max_concurrency: usize,
+    max_queued: Option<usize>,
+    queue_timeout: Option<Duration>,
+}
+
+impl ConcurrencySettings {
+    /// Returns the settings from the given limits, or `None` if concurrency
+    /// is unlimited.
+    pub fn from_limits(limits: &ComponentLimits) -> Option<Self> {
+        Some(Self {
+            max_concurrency: limits.concurrency? as usize,
+            max_queued: limits.queue.map(|queue| queue as usize),
+            queue_timeout: limits.queue_timeout_ms.map(Duration::from_millis),
+        })
+    }
+}
+
+/// Limits the number of concurrent instances of a component.
+pub(crate) struct ConcurrencyLimiter {
+    component_id: String,
+    settings: ConcurrencySettings,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(component_id: String, settings: ConcurrencySettings) -> Self {
+        Self {
+            component_id,
+            semaphore: Arc::new(Semaphore::new(settings.max_concurrency)),
+            settings,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn settings(&self) -> ConcurrencySettings {
+        self.settings
+    }
+
+    /// The number of instances currently running.
+    #[cfg(test)]
+    pub fn active(&self) -> usize {
+        self.settings.max_concurrency - self.semaphore.available_permits()
+    }
+
+    /// The number of invocations currently waiting for a free instance.
+    #[cfg(test)]
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Waits for a free instance slot, which is held until the returned
+    /// permit is dropped.
+    pub async fn acquire(&self) -> Result<ConcurrencyPermit, ConcurrencyLimitExceeded> {
+        let permit = match self.semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => self.acquire_queued().await?,
+        };
+        spin_telemetry::metrics::counter!(
+            spin.component_active_instances = 1,
+            component_id = self.component_id
+        );
+        Ok(ConcurrencyPermit {
+            component_id: self.component_id.clone(),
+            _permit: permit,
+        })
+    }
+
+    async fn acquire_queued(&self) -> Result<OwnedSemaphorePermit, ConcurrencyLimitExceeded> {
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        spin_telemetry::metrics::counter!(
+            spin.component_queued_invocations = 1,
+            component_id = self.component_id
+        );
+        let _dequeue = Dequeue(self);
+        if self
+            .settings
+            .max_queued
+            .is_some_and(|max_queued| queued >= max_queued)
+        {
+            return Err(self.exceeded("the queue is full"));
+        }
+
+        let acquire = self.semaphore.clone().acquire_owned();
+        let permit = match self.settings.queue_timeout {
+            Some(queue_timeout) => tokio::time::timeout(queue_timeout, acquire)
+                .await
+                .map_err(|_| self.exceeded("timed out waiting in the queue"))?,
+            None => acquire.await,
+        };
+        // The semaphore is never closed
+        Ok(permit.expect("semaphore closed"))
+    }
+
+    fn exceeded(&self, reason: &'static str) -> ConcurrencyLimitExceeded {
+        tracing::warn!(
+            "error.type" = "concurrency_limit_exceeded",
+            component_id = self.component_id,
+            reason,
+            "component concurrency limit exceeded",
+        );
+        ConcurrencyLimitExceeded {
+            component_id: self.component_id.clone(),
+            reason,
+        }
+    }
+}
+
+// Decrements the queued count when a queued acquire finishes or is cancelled.
+struct Dequeue<'a>(&'a ConcurrencyLimiter);
+
+impl Drop for Dequeue<'_> {
+    fn drop(&mut self) {
+        self.0.queued.fetch_sub(1, Ordering::Relaxed);
+        spin_telemetry::metrics::counter!(
+            spin.component_queued_invocations = -1,
+            component_id = self.0.component_id
+        );
+    }
+}
+
+/// Holds one of a component's concurrency slots until dropped.
+pub(crate) struct ConcurrencyPermit {
+    component_id: String,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        spin_telemetry::metrics::counter!(
+            spin.component_active_instances = -1,
+            component_id = self.component_id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_queued: Option<usize>, queue_timeout: Option<Duration>) -> ConcurrencyLimiter {
+        ConcurrencyLimiter::new(
+            "test".into(),
+            ConcurrencySettings {
+                max_concurrency: 1,
+                max_queued,
+                queue_timeout,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn queued_acquire_waits_for_permit() {
+        let limiter = Arc::new(limiter(None, None));
+        let permit = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.active(), 1);
+
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(drop) }
+        });
+        while limiter.queued() == 0 {
+            tokio::task::yield_now().await;
+        }
+        drop(permit);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn full_queue_rejects() {
+        let limiter = limiter(Some(0), None);
+        let _permit = limiter.acquire().await.unwrap();
+        limiter.acquire().await.map(drop).unwrap_err();
+        assert_eq!(limiter.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn queue_timeout_rejects() {
+        let limiter = limiter(None, Some(Duration::from_millis(10)));
+        let _permit = limiter.acquire().await.unwrap();
+        limiter.acquire().await.map(drop).unwrap_err();
+        assert_eq!(limiter.queued(), 0);
+    }
+}
@@ -1,13 +1,21 @@
+mod concurrency;
+mod reuse;
+
 use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
-use spin_app::{App, AppComponent};
+use concurrency::{ConcurrencyLimiter, ConcurrencyPermit, ConcurrencySettings};
+use reuse::InstancePool;
+use spin_app::{App, AppComponent, COMPONENT_INSTANCE_REUSE_KEY, COMPONENT_LIMITS_KEY};
 use spin_core::{async_trait, Component};
 use spin_factors::{
     AsInstanceState, ConfiguredApp, Factor, HasInstanceBuilder, RuntimeFactors,
     RuntimeFactorsInstanceState,
 };
 
+pub use concurrency::ConcurrencyLimitExceeded;
+pub use reuse::InstanceRecycler;
+
 /// A FactorsExecutor manages execution of a Spin app.
 ///
 /// It is generic over the executor's [`RuntimeFactors`]. Additionally, it
@@ -53,6 +61,17 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
         app: App,
         runtime_config: T::RuntimeConfig,
         component_loader: &impl ComponentLoader<T, U>,
+    ) -> anyhow::Result<FactorsExecutorApp<T, U>> {
+        self.load_app_reusing(app, runtime_config, component_loader, None)
+            .await
+    }
+
+    async fn load_app_reusing(
+        self: Arc<Self>,
+        app: App,
+        runtime_config: T::RuntimeConfig,
+        component_loader: &impl ComponentLoader<T, U>,
+        previous: Option<&FactorsExecutorApp<T, U>>,
     ) -> anyhow::Result<FactorsExecutorApp<T, U>> {
         let configured_app = self
             .factors
@@ -60,23 +79,69 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
             .context("failed to configure app")?;
 
         for hooks in &self.hooks {
+            if previous.is_some() && !hooks.configure_on_reload() {
+                continue;
+            }
             hooks.configure_app(&configured_app).await?;
         }
+        component_loader.configure_app(&configured_app).await?;
 
         let components = configured_app.app().components();
         let mut component_instance_pres = HashMap::with_capacity(components.len());
+        let mut component_fingerprints = HashMap::new();
+        let mut concurrency_limiters = HashMap::new();
+        let mut instance_pools = HashMap::new();
 
         for component in components {
-            let instance_pre = component_loader
-                .load_instance_pre(&self.core_engine, &component)
-                .await?;
-            component_instance_pres.insert(component.id().to_string(), instance_pre);
+            let id = component.id().to_string();
+            let fingerprint = component_loader.component_fingerprint(&component).await?;
+            let reusable = previous.and_then(|previous| {
+                let previous_fingerprint = previous.component_fingerprints.get(&id)?;
+                (Some(previous_fingerprint) == fingerprint.as_ref())
+                    .then(|| previous.component_instance_pres.get(&id))
+                    .flatten()
+            });
+            let instance_pre = match reusable {
+                Some(instance_pre) => instance_pre.clone(),
+                None => {
+                    component_loader
+                        .load_instance_pre(&self.core_engine, &component)
+                        .await?
+                }
+            };
+            if let Some(fingerprint) = fingerprint {
+                component_fingerprints.insert(id.clone(), fingerprint);
+            }
+            let limits = component
+                .get_metadata(COMPONENT_LIMITS_KEY)
+                .with_context(|| format!("invalid limits for component {id:?}"))?
+                .unwrap_or_default();
+            if let Some(settings) = ConcurrencySettings::from_limits(&limits) {
+                // Keep the existing limiter across reloads so that in-flight
+                // instances of the previous app still count toward the limit
+                let limiter = previous
+                    .and_then(|previous| previous.concurrency_limiters.get(&id))
+                    .filter(|limiter| limiter.settings() == settings)
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(ConcurrencyLimiter::new(id.clone(), settings)));
+                concurrency_limiters.insert(id.clone(), limiter);
+            }
+            let instance_reuse = component
+                .get_metadata(COMPONENT_INSTANCE_REUSE_KEY)
+                .with_context(|| format!("invalid instance_reuse for component {id:?}"))?;
+            if let Some(max_invocations) = instance_reuse.filter(|&n| n > 1) {
+                instance_pools.insert(id.clone(), Arc::new(InstancePool::new(max_invocations)));
+            }
+            component_instance_pres.insert(id, instance_pre);
         }
 
         Ok(FactorsExecutorApp {
             executor: self.clone(),
-            configured_app,
-            component_instance_pres,
+            configured_app: Arc::new(configured_app),
+            component_instance_pres: Arc::new(component_instance_pres),
+            component_fingerprints: Arc::new(component_fingerprints),
+            concurrency_limiters: Arc::new(concurrency_limiters),
+            instance_pools: Arc::new(instance_pools),
         })
     }
 }
@@ -92,6 +157,13 @@ where
         Ok(())
     }
 
+    /// Whether [`ExecutorHooks::configure_app`] also runs when the app is
+    /// reloaded. Hooks with one-shot startup effects, such as seeding data,
+    /// should return false.
+    fn configure_on_reload(&self) -> bool {
+        true
+    }
+
     /// Prepare instance hooks run immediately before [`FactorsExecutorApp::prepare`] returns.
     fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<T, U>) -> anyhow::Result<()> {
         let _ = builder;
@@ -102,6 +174,13 @@ where
 /// A ComponentLoader is responsible for loading Wasmtime [`Component`]s.
 #[async_trait]
 pub trait ComponentLoader<T: RuntimeFactors, U>: Sync {
+    /// Called with each configured app before any of its components are
+    /// loaded.
+    async fn configure_app(&self, configured_app: &ConfiguredApp<T>) -> anyhow::Result<()> {
+        let _ = configured_app;
+        Ok(())
+    }
+
     /// Loads a [`Component`] for the given [`AppComponent`].
     async fn load_component(
         &self,
@@ -118,6 +197,19 @@ pub trait ComponentLoader<T: RuntimeFactors, U>: Sync {
         let component = self.load_component(engine.as_ref(), component).await?;
         engine.instantiate_pre(&component)
     }
+
+    /// Returns a fingerprint of everything that goes into compiling the given
+    /// [`AppComponent`], or `None` if it cannot be determined.
+    ///
+    /// When reloading an app, components whose fingerprint is unchanged reuse
+    /// their previously compiled [`InstancePre`](spin_core::InstancePre).
+    async fn component_fingerprint(
+        &self,
+        component: &AppComponent,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let _ = component;
+        Ok(None)
+    }
 }
 
 type InstancePre<T, U> =
@@ -127,11 +219,33 @@ type InstancePre<T, U> =
 ///
 /// It is generic over the executor's [`RuntimeFactors`] and any ad-hoc additional
 /// per-instance state needed by the caller.
+///
+/// Cloning is cheap; clones share the same executor, configured app and
+/// compiled components.
 pub struct FactorsExecutorApp<T: RuntimeFactors, U: 'static> {
     executor: Arc<FactorsExecutor<T, U>>,
-    configured_app: ConfiguredApp<T>,
+    configured_app: Arc<ConfiguredApp<T>>,
     // Maps component IDs -> InstancePres
-    component_instance_pres: HashMap<String, InstancePre<T, U>>,
+    component_instance_pres: Arc<HashMap<String, InstancePre<T, U>>>,
+    // Maps component IDs -> fingerprints from [`ComponentLoader::component_fingerprint`]
+    component_fingerprints: Arc<HashMap<String, Vec<u8>>>,
+    // Maps component IDs -> limiters for components with a concurrency limit
+    concurrency_limiters: Arc<HashMap<String, Arc<ConcurrencyLimiter>>>,
+    // Maps component IDs -> idle instances for components that opt in to reuse
+    instance_pools: Arc<HashMap<String, Arc<InstancePool<T, U>>>>,
+}
+
+impl<T: RuntimeFactors, U: 'static> Clone for FactorsExecutorApp<T, U> {
+    fn clone(&self) -> Self {
+        Self {
+            executor: self.executor.clone(),
+            configured_app: self.configured_app.clone(),
+            component_instance_pres: self.component_instance_pres.clone(),
+            component_fingerprints: self.component_fingerprints.clone(),
+            concurrency_limiters: self.concurrency_limiters.clone(),
+            instance_pools: self.instance_pools.clone(),
+        }
+    }
 }
 
 impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
@@ -139,6 +253,24 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
         &self.executor.core_engine
     }
 
+    /// Loads a new version of this app with the same executor.
+    ///
+    /// Components whose [`ComponentLoader::component_fingerprint`] is
+    /// unchanged reuse their compiled components from this app; all others
+    /// are recompiled. This app is unaffected and can continue to be used,
+    /// e.g. by requests which are still in flight.
+    pub async fn reload(
+        &self,
+        app: App,
+        runtime_config: T::RuntimeConfig,
+        component_loader: &impl ComponentLoader<T, U>,
+    ) -> anyhow::Result<Self> {
+        self.executor
+            .clone()
+            .load_app_reusing(app, runtime_config, component_loader, Some(self))
+            .await
+    }
+
     pub fn configured_app(&self) -> &ConfiguredApp<T> {
         &self.configured_app
     }
@@ -158,13 +290,26 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
     }
 
     /// Returns an instance builder for the given component ID.
-    pub fn prepare(&self, component_id: &str) -> anyhow::Result<FactorsInstanceBuilder<'_, T, U>> {
+    ///
+    /// If the component has a concurrency limit, this waits for a free
+    /// instance slot, which is held until the instance's store is dropped.
+    /// Fails with [`ConcurrencyLimitExceeded`] if the slot cannot be
+    /// obtained within the component's queue limits.
+    pub async fn prepare(
+        &self,
+        component_id: &str,
+    ) -> anyhow::Result<FactorsInstanceBuilder<'_, T, U>> {
         let app_component = self
             .configured_app
             .app()
             .get_component(component_id)
             .with_context(|| format!("no such component {component_id:?}"))?;
 
+        let concurrency_permit = match self.concurrency_limiters.get(component_id) {
+            Some(limiter) => Some(limiter.acquire().await?),
+            None => None,
+        };
+
         let instance_pre = self.component_instance_pres.get(component_id).unwrap();
 
         let factor_builders = self
@@ -180,6 +325,8 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
             instance_pre,
             app_component,
             factors: &self.executor.factors,
+            concurrency_permit,
+            instance_pool: self.instance_pools.get(component_id).cloned(),
         };
 
         for hooks in &self.executor.hooks {
@@ -200,9 +347,32 @@ pub struct FactorsInstanceBuilder<'a, F: RuntimeFactors, U: 'static> {
     factor_builders: F::InstanceBuilders,
     instance_pre: &'a InstancePre<F, U>,
     factors: &'a F,
+    concurrency_permit: Option<ConcurrencyPermit>,
+    instance_pool: Option<Arc<InstancePool<F, U>>>,
 }
 
-impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
+impl<'a, T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'a, T, U> {
+    /// Returns an [`InstanceRecycler`] which can return the instance to its
+    /// component's pool after a successful invocation.
+    ///
+    /// A reused instance's factor state is rebuilt from the factor builders
+    /// of its next invocation, but resources the guest obtained in earlier
+    /// invocations are kept. In particular, its stdio streams are those of
+    /// its first invocation, so triggers that give each invocation its own
+    /// stdio must not recycle instances.
+    pub fn recycler(&self) -> InstanceRecycler<T, U> {
+        InstanceRecycler {
+            pool: self.instance_pool.clone(),
+        }
+    }
+
+    /// Prevents this instance from being reused and from being served by an
+    /// idle instance, e.g. because the store builder configures per-instance
+    /// state that can't be applied to a reused store.
+    pub fn disable_reuse(&mut self) {
+        self.instance_pool = None;
+    }
+
     /// Returns the app component for the instance.
     pub fn app_component(&self) -> &AppComponent<'_> {
         &self.app_component
@@ -236,6 +406,11 @@ impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
 
 impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
     /// Instantiates the instance with the given executor instance state
+    ///
+    /// If the component opted in to instance reuse and an idle instance was
+    /// returned with [`InstanceRecycler::recycle`], that instance is returned
+    /// instead, with its factor state reset from the factor builders and its
+    /// store reconfigured by the store builder.
     pub async fn instantiate(
         self,
         executor_instance_state: U,
@@ -243,10 +418,25 @@ impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
         spin_core::Instance,
         spin_core::Store<InstanceState<T::InstanceState, U>>,
     )> {
+        if let Some((instance, mut store)) =
+            self.instance_pool.as_ref().and_then(|pool| pool.take())
+        {
+            let state = store.data_mut();
+            self.factors
+                .reset_instance_state(&mut state.factors, self.factor_builders)
+                .context("failed to reset reused instance")?;
+            state.executor = executor_instance_state;
+            state.concurrency_permit = self.concurrency_permit;
+            state.invocations += 1;
+            self.store_builder.reuse(&mut store)?;
+            return Ok((instance, store));
+        }
         let instance_state = InstanceState {
             core: Default::default(),
             factors: self.factors.build_instance_state(self.factor_builders)?,
             executor: executor_instance_state,
+            concurrency_permit: self.concurrency_permit,
+            invocations: 1,
         };
         let mut store = self.store_builder.build(instance_state)?;
         let instance = self.instance_pre.instantiate_async(&mut store).await?;
@@ -262,6 +452,10 @@ pub struct InstanceState<T, U> {
     core: spin_core::State,
     factors: T,
     executor: U,
+    // Releases the component's concurrency slot when the store is dropped
+    concurrency_permit: Option<ConcurrencyPermit>,
+    // The number of invocations served, for instance reuse
+    invocations: u32,
 }
 
 impl<T, U> InstanceState<T, U> {
@@ -310,6 +504,8 @@ impl<T: RuntimeFactorsInstanceState, U> AsInstanceState<T> for InstanceState<T,
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
     use spin_factors::RuntimeFactors;
     use spin_factors_test::TestEnvironment;
@@ -337,7 +533,7 @@ mod tests {
             .load_app(app, Default::default(), &DummyComponentLoader)
             .await?;
 
-        let mut instance_builder = factors_app.prepare("empty")?;
+        let mut instance_builder = factors_app.prepare("empty").await?;
 
         assert_eq!(instance_builder.app_component().id(), "empty");
 
@@ -352,6 +548,185 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn instances_are_reused_up_to_limit() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let mut env = TestEnvironment::new(factors);
+        env.manifest["component"]["empty"]
+            .as_table_mut()
+            .unwrap()
+            .insert("instance_reuse".into(), 2.into());
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        let factors_app = executor
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await?;
+
+        let invoke = || async {
+            let instance_builder = factors_app.prepare("empty").await?;
+            let recycler = instance_builder.recycler();
+            assert!(recycler.is_enabled());
+            let (instance, store) = instance_builder.instantiate(()).await?;
+            let invocations = store.data().invocations;
+            recycler.recycle(instance, store);
+            anyhow::Ok(invocations)
+        };
+        assert_eq!(invoke().await?, 1);
+        assert_eq!(invoke().await?, 2);
+        // The instance was discarded after its second invocation
+        assert_eq!(invoke().await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_instances_are_capped() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let mut env = TestEnvironment::new(factors);
+        env.manifest["component"]["empty"]
+            .as_table_mut()
+            .unwrap()
+            .insert("instance_reuse".into(), 2.into());
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        let factors_app = executor
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await?;
+
+        // Simulate a burst of concurrent invocations
+        let mut instances = vec![];
+        for _ in 0..reuse::MAX_IDLE_INSTANCES + 4 {
+            let instance_builder = factors_app.prepare("empty").await?;
+            let recycler = instance_builder.recycler();
+            let (instance, store) = instance_builder.instantiate(()).await?;
+            instances.push((recycler, instance, store));
+        }
+        for (recycler, instance, store) in instances {
+            recycler.recycle(instance, store);
+        }
+        assert_eq!(
+            factors_app.instance_pools["empty"].idle(),
+            reuse::MAX_IDLE_INSTANCES
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_reuses_unchanged_components() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+
+        let loader = CountingComponentLoader::default();
+        let factors_app = executor
+            .load_app(
+                App::new("test-app", locked.clone()),
+                Default::default(),
+                &loader,
+            )
+            .await?;
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 1);
+
+        let reloaded = factors_app
+            .reload(
+                App::new("test-app", locked.clone()),
+                Default::default(),
+                &loader,
+            )
+            .await?;
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 1);
+
+        // A changed fingerprint forces recompilation
+        loader.fingerprint.fetch_add(1, Ordering::SeqCst);
+        let reloaded = reloaded
+            .reload(App::new("test-app", locked), Default::default(), &loader)
+            .await?;
+        assert_eq!(loader.loads.load(Ordering::SeqCst), 2);
+
+        // The previous app remains usable
+        factors_app.prepare("empty").await?.instantiate(()).await?;
+        reloaded.prepare("empty").await?.instantiate(()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reload_skips_startup_only_hooks() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        let every_configure = Arc::new(CountingHooks::default());
+        let startup_only = Arc::new(CountingHooks {
+            on_reload: false,
+            ..Default::default()
+        });
+        executor.add_hooks(every_configure.clone());
+        executor.add_hooks(startup_only.clone());
+        let executor = Arc::new(executor);
+
+        let factors_app = executor
+            .load_app(
+                App::new("test-app", locked.clone()),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+        factors_app
+            .reload(
+                App::new("test-app", locked),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+        assert_eq!(every_configure.configures.load(Ordering::SeqCst), 2);
+        assert_eq!(startup_only.configures.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    struct CountingHooks {
+        configures: AtomicUsize,
+        on_reload: bool,
+    }
+
+    impl Default for CountingHooks {
+        fn default() -> Self {
+            Self {
+                configures: Default::default(),
+                on_reload: true,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ExecutorHooks<TestFactors, ()> for Arc<CountingHooks> {
+        async fn configure_app(&self, _app: &ConfiguredApp<TestFactors>) -> anyhow::Result<()> {
+            self.configures.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn configure_on_reload(&self) -> bool {
+            self.on_reload
+        }
+    }
+
     struct DummyComponentLoader;
 
     #[async_trait]
@@ -364,4 +739,30 @@ mod tests {
             Component::new(engine, "(component)")
         }
     }
+
+    #[derive(Default)]
+    struct CountingComponentLoader {
+        loads: AtomicUsize,
+        fingerprint: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ComponentLoader<TestFactors, ()> for CountingComponentLoader {
+        async fn load_component(
+            &self,
+            engine: &spin_core::wasmtime::Engine,
+            _component: &AppComponent,
+        ) -> anyhow::Result<Component> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            Component::new(engine, "(component)")
+        }
+
+        async fn component_fingerprint(
+            &self,
+            _component: &AppComponent,
+        ) -> anyhow::Result<Option<Vec<u8>>> {
+            let fingerprint = self.fingerprint.load(Ordering::SeqCst);
+            Ok(Some(fingerprint.to_le_bytes().to_vec()))
+        }
+    }
 }
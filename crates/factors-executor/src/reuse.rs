@@ -0,0 +1,88 @@
+use std::sync::{Arc, Mutex};
+
+use spin_factors::RuntimeFactors;
+
+use crate::InstanceState;
+
+type PooledInstance<T, U> = (
+    spin_core::Instance,
+    spin_core::Store<InstanceState<<T as RuntimeFactors>::InstanceState, U>>,
+);
+
+/// The maximum number of idle instances kept for each component. Instances
+/// returned while the pool is full are dropped, so that a burst of concurrent
+/// invocations doesn't leave as many instances holding memory indefinitely.
+pub(crate) const MAX_IDLE_INSTANCES: usize = 16;
+
+/// Idle instances of a component that opted in to instance reuse.
+pub(crate) struct InstancePool<T: RuntimeFactors, U: 'static> {
+    max_invocations: u32,
+    idle: Mutex<Vec<PooledInstance<T, U>>>,
+}
+
+impl<T: RuntimeFactors, U: 'static> InstancePool<T, U> {
+    pub fn new(max_invocations: u32) -> Self {
+        Self {
+            max_invocations,
+            idle: Default::default(),
+        }
+    }
+
+    /// Takes an idle instance from the pool, if there is one.
+    pub fn take(&self) -> Option<PooledInstance<T, U>> {
+        self.idle.lock().unwrap().pop()
+    }
+
+    /// Returns the number of idle instances.
+    #[cfg(test)]
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// Returns instances to their component's pool after an invocation so that
+/// they may serve later invocations.
+///
+/// Obtained from [`FactorsInstanceBuilder::recycler`](crate::FactorsInstanceBuilder::recycler).
+///
+/// The recycler doesn't borrow the instance builder, so it may be moved into
+/// a task that outlives the builder, e.g. one that completes an invocation
+/// after its response has been sent.
+pub struct InstanceRecycler<T: RuntimeFactors, U: 'static> {
+    pub(crate) pool: Option<Arc<InstancePool<T, U>>>,
+}
+
+impl<T: RuntimeFactors, U: Send + 'static> InstanceRecycler<T, U> {
+    /// Returns true if instances of this component may be reused.
+    pub fn is_enabled(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// Returns the instance to the pool for reuse.
+    ///
+    /// This must only be called after an invocation completed successfully;
+    /// an instance that trapped must be dropped instead. The instance is
+    /// dropped if the component did not opt in to reuse, if it has served
+    /// its maximum number of invocations, or if the pool is full.
+    ///
+    /// The instance's state is reset when it is next taken from the pool.
+    pub fn recycle(
+        self,
+        instance: spin_core::Instance,
+        mut store: spin_core::Store<InstanceState<T::InstanceState, U>>,
+    ) {
+        let Some(pool) = self.pool else {
+            return;
+        };
+        let state = store.data_mut();
+        if state.invocations >= pool.max_invocations {
+            return;
+        }
+        // Idle instances don't count toward the component's concurrency limit
+        state.concurrency_permit = None;
+        let mut idle = pool.idle.lock().unwrap();
+        if idle.len() < MAX_IDLE_INSTANCES {
+            idle.push((instance, store));
+        }
+    }
+}
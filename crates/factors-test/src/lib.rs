@@ -2,7 +2,7 @@ use spin_app::locked::LockedApp;
 use spin_factors::{
     anyhow::{self, Context},
     wasmtime::{component::Linker, Config, Engine},
-    App, RuntimeFactors,
+    App, ConfiguredApp, RuntimeFactors,
 };
 use spin_loader::FilesMountStrategy;
 
@@ -92,6 +92,17 @@ impl<T: RuntimeFactors> TestEnvironment<T> {
         Ok(self.factors.build_instance_state(builders)?)
     }
 
+    /// Run through the [`Factor`]s' `configure_app` lifecycle to build a
+    /// [`ConfiguredApp`] for the manifest.
+    pub async fn build_configured_app(self) -> anyhow::Result<ConfiguredApp<T>> {
+        let locked_app = self
+            .build_locked_app()
+            .await
+            .context("failed to build locked app")?;
+        let app = App::new("test-app", locked_app);
+        Ok(self.factors.configure_app(app, self.runtime_config)?)
+    }
+
     pub async fn build_locked_app(&self) -> anyhow::Result<LockedApp> {
         build_locked_app(&self.manifest).await
     }
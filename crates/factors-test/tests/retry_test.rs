@@ -0,0 +1,382 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use spin_core::async_trait;
+use spin_factor_key_value::{
+    Cas, Error, KeyValueFactor, RuntimeConfig, Store, StoreManager, SwapError,
+};
+use spin_factors::{anyhow, RuntimeFactors};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_trigger::retry::{
+    DeadLetter, DeadLetterConfig, DeadLetterSink, Invocation, RetryConfig, RetryPolicy,
+};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    key_value: KeyValueFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self {
+            key_value: Some(value),
+        }
+    }
+}
+
+const INVOCATION: Invocation = Invocation {
+    trigger_type: "test",
+    component_id: "test-component",
+    payload: b"hello",
+};
+
+fn fast_retries(max_attempts: u32) -> RetryConfig {
+    RetryConfig {
+        max_attempts,
+        initial_backoff_ms: 1,
+        max_backoff_ms: 1,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn retries_until_success() -> anyhow::Result<()> {
+    let policy = RetryPolicy::new(&fast_retries(3))?;
+    let calls = AtomicU32::new(0);
+    let result = policy
+        .run(INVOCATION, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => anyhow::bail!("not yet"),
+                n => Ok(n),
+            }
+        })
+        .await?;
+    assert_eq!(result, 2);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn exhausted_retries_are_dead_lettered() -> anyhow::Result<()> {
+    let sink = RecordingSink::default();
+    let letters = sink.letters.clone();
+    let policy = RetryPolicy::new(&fast_retries(2))?.with_dead_letter_sink(sink);
+    let calls = AtomicU32::new(0);
+
+    let err = policy
+        .run(INVOCATION, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(anyhow::anyhow!("always fails"))
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "always fails");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let letters = letters.lock().unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].component_id, "test-component");
+    assert_eq!(letters[0].attempts, 2);
+    assert_eq!(letters[0].error, "always fails");
+    assert_eq!(letters[0].decode_payload()?, b"hello");
+    Ok(())
+}
+
+#[test]
+fn backoff_grows_exponentially_up_to_max() -> anyhow::Result<()> {
+    let policy = RetryPolicy::new(&RetryConfig {
+        max_attempts: 10,
+        initial_backoff_ms: 100,
+        max_backoff_ms: 1_000,
+        backoff_multiplier: 3.0,
+        ..Default::default()
+    })?;
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(300));
+    assert_eq!(policy.backoff(3), Duration::from_millis(900));
+    assert_eq!(policy.backoff(4), Duration::from_millis(1_000));
+    assert_eq!(policy.backoff(1_000), Duration::from_millis(1_000));
+    Ok(())
+}
+
+#[test]
+fn huge_backoff_is_capped() -> anyhow::Result<()> {
+    let policy = RetryPolicy::new(&RetryConfig {
+        max_attempts: 100,
+        initial_backoff_ms: u64::MAX,
+        max_backoff_ms: 1_000,
+        backoff_multiplier: 10.0,
+        ..Default::default()
+    })?;
+    assert_eq!(policy.backoff(1), Duration::from_millis(1_000));
+    assert_eq!(policy.backoff(50), Duration::from_millis(1_000));
+    Ok(())
+}
+
+#[test]
+fn invalid_config_is_rejected() {
+    assert!(RetryPolicy::new(&fast_retries(0)).is_err());
+    assert!(RetryPolicy::new(&RetryConfig {
+        jitter: 1.5,
+        ..Default::default()
+    })
+    .is_err());
+}
+
+#[test]
+fn config_deserializes_from_trigger_config() -> anyhow::Result<()> {
+    let config: RetryConfig = serde_json::from_value(serde_json::json!({
+        "max_attempts": 4,
+        "jitter": 0.25,
+        "dead_letter": { "type": "key_value", "store": "failures" },
+    }))?;
+    assert_eq!(config.max_attempts, 4);
+    assert_eq!(config.jitter, 0.25);
+    assert_eq!(
+        config.dead_letter,
+        Some(DeadLetterConfig::KeyValue {
+            store: "failures".into(),
+            key_prefix: None,
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn dead_letters_go_to_file() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("dead-letters.jsonl");
+    let config = RetryConfig {
+        dead_letter: Some(DeadLetterConfig::File { path: path.clone() }),
+        ..fast_retries(1)
+    };
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    });
+    let configured_app = env.build_configured_app().await?;
+    let policy = RetryPolicy::from_config(&config, &configured_app).await?;
+
+    for _ in 0..2 {
+        let _ = policy
+            .run(INVOCATION, || async {
+                Err::<(), _>(anyhow::anyhow!("nope"))
+            })
+            .await;
+    }
+
+    let contents = std::fs::read_to_string(&path)?;
+    let letters = contents
+        .lines()
+        .map(serde_json::from_str::<DeadLetter>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(letters.len(), 2);
+    assert!(letters.iter().all(|l| l.error == "nope"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn dead_letters_go_to_key_value_store() -> anyhow::Result<()> {
+    let store = Arc::new(MemoryStore::default());
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager(
+        "failures".into(),
+        Arc::new(SingleStoreManager(store.clone())),
+    );
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["failures"]
+    })
+    .runtime_config(runtime_config)?;
+    let configured_app = env.build_configured_app().await?;
+
+    let config = RetryConfig {
+        dead_letter: Some(DeadLetterConfig::KeyValue {
+            store: "failures".into(),
+            key_prefix: Some("dlq/".into()),
+        }),
+        ..fast_retries(1)
+    };
+    let policy = RetryPolicy::from_config(&config, &configured_app).await?;
+    let _ = policy
+        .run(INVOCATION, || async {
+            Err::<(), _>(anyhow::anyhow!("nope"))
+        })
+        .await;
+
+    let keys = store.get_keys().await?;
+    assert_eq!(keys.len(), 1);
+    let key = &keys[0];
+    assert!(key.starts_with("dlq/test-component/"), "{key}");
+    let value = store.get(key).await?.expect("dead letter should be stored");
+    let letter: DeadLetter = serde_json::from_slice(&value)?;
+    assert_eq!(letter.trigger_type, "test");
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_dead_letter_store_is_an_error() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    });
+    let configured_app = env.build_configured_app().await?;
+    let config = RetryConfig {
+        dead_letter: Some(DeadLetterConfig::KeyValue {
+            store: "nope".into(),
+            key_prefix: None,
+        }),
+        ..Default::default()
+    };
+    let Err(err) = RetryPolicy::from_config(&config, &configured_app).await else {
+        anyhow::bail!("expected unknown store to be rejected");
+    };
+    assert!(err.to_string().contains("nope"), "{err}");
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordingSink {
+    letters: Arc<Mutex<Vec<DeadLetter>>>,
+}
+
+#[async_trait]
+impl DeadLetterSink for RecordingSink {
+    async fn send(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        self.letters.lock().unwrap().push(letter.clone());
+        Ok(())
+    }
+}
+
+struct SingleStoreManager(Arc<MemoryStore>);
+
+#[async_trait]
+impl StoreManager for SingleStoreManager {
+    async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
+        Ok(self.0.clone())
+    }
+
+    fn is_defined(&self, _store_name: &str) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    values: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.values.lock().unwrap().contains_key(key))
+    }
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        Ok(self.values.lock().unwrap().keys().cloned().collect())
+    }
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let values = self.values.lock().unwrap();
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = values.get(&key).cloned();
+                (key, value)
+            })
+            .collect())
+    }
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.values.lock().unwrap().extend(key_values);
+        Ok(())
+    }
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let mut values = self.values.lock().unwrap();
+        for key in keys {
+            values.remove(&key);
+        }
+        Ok(())
+    }
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let mut values = self.values.lock().unwrap();
+        let current = match values.get(&key) {
+            Some(value) => i64::from_le_bytes(
+                value
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| Error::Other(format!("{key:?} is not a counter")))?,
+            ),
+            None => 0,
+        };
+        let new_value = current + delta;
+        values.insert(key, new_value.to_le_bytes().to_vec());
+        Ok(new_value)
+    }
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        Ok(Arc::new(MemoryCas {
+            values: self.values.clone(),
+            key: key.to_owned(),
+            bucket_rep,
+            current: Mutex::new(None),
+        }))
+    }
+}
+
+/// A compare-and-swap which swaps only if the value is unchanged since `current` was read.
+struct MemoryCas {
+    values: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    key: String,
+    bucket_rep: u32,
+    current: Mutex<Option<Option<Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Cas for MemoryCas {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.values.lock().unwrap().get(&self.key).cloned();
+        *self.current.lock().unwrap() = Some(value.clone());
+        Ok(value)
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let mut values = self.values.lock().unwrap();
+        let expected = self.current.lock().unwrap().clone();
+        if expected.is_some_and(|expected| expected != values.get(&self.key).cloned()) {
+            return Err(SwapError::CasFailed("value has changed".to_owned()));
+        }
+        values.insert(self.key.clone(), value);
+        Ok(())
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.bucket_rep
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
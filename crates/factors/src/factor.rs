@@ -64,6 +64,22 @@ pub trait Factor: Any + Sized {
         &self,
         ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder>;
+
+    /// Resets the instance state of an instance being reused for another
+    /// invocation, given the instance builder prepared for that invocation.
+    ///
+    /// Components may opt in to serving several sequential invocations from
+    /// one instance. By default the state is rebuilt from `builder`, so that
+    /// nothing set up for or accumulated during earlier invocations carries
+    /// over. Resources in the shared [`ResourceTable`] are kept.
+    fn reset_instance_state(
+        &self,
+        state: &mut FactorInstanceState<Self>,
+        builder: Self::InstanceBuilder,
+    ) -> anyhow::Result<()> {
+        *state = builder.build()?;
+        Ok(())
+    }
 }
 
 /// The instance state of the given [`Factor`] `F`.
@@ -51,6 +51,11 @@ pub enum Error {
         factor: &'static str,
         source: anyhow::Error,
     },
+    #[error("{factor}::reset_instance_state failed: {source}")]
+    FactorResetError {
+        factor: &'static str,
+        source: anyhow::Error,
+    },
     #[error("no such factor: {0}")]
     NoSuchFactor(&'static str),
     #[error("{factor} requested already-consumed key {key:?}")]
@@ -95,6 +100,12 @@ impl Error {
         Self::FactorPrepareError { factor, source }
     }
 
+    #[doc(hidden)]
+    pub fn factor_reset_error<T: Factor>(source: anyhow::Error) -> Self {
+        let factor = std::any::type_name::<T>();
+        Self::FactorResetError { factor, source }
+    }
+
     #[doc(hidden)]
     pub fn factor_build_error<T: Factor>(source: anyhow::Error) -> Self {
         let factor = std::any::type_name::<T>();
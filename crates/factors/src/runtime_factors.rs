@@ -69,6 +69,16 @@ pub trait RuntimeFactors: Send + Sync + Sized + 'static {
         builders: Self::InstanceBuilders,
     ) -> crate::Result<Self::InstanceState>;
 
+    /// Reset the instance state for the factors so that the instance can be
+    /// reused for another invocation, given the builders prepared for it.
+    ///
+    /// Each factor's `reset_instance_state` is called in turn.
+    fn reset_instance_state(
+        &self,
+        state: &mut Self::InstanceState,
+        builders: Self::InstanceBuilders,
+    ) -> crate::Result<()>;
+
     /// Get the app state related to a particular factor.
     fn app_state<F: Factor>(app_state: &Self::AppState) -> Option<&F::AppState>;
 
@@ -1,7 +1,8 @@
 use core::str;
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, BTreeSet, HashMap},
     sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Result;
@@ -11,17 +12,20 @@ use aws_sdk_dynamodb::{
     config::{ProvideCredentials, SharedCredentialsProvider},
     operation::{
         batch_get_item::BatchGetItemOutput, batch_write_item::BatchWriteItemOutput,
-        get_item::GetItemOutput,
+        get_item::GetItemOutput, scan::ScanOutput, transact_write_items::TransactWriteItemsError,
     },
     primitives::Blob,
     types::{
-        AttributeValue, DeleteRequest, KeysAndAttributes, PutRequest, TransactWriteItem, Update,
-        WriteRequest,
+        AttributeValue, ConditionCheck, Delete, DeleteRequest, KeysAndAttributes, Put, PutRequest,
+        TransactWriteItem, Update, WriteRequest,
     },
     Client,
 };
 use spin_core::async_trait;
-use spin_factor_key_value::{log_error, Cas, Error, Store, StoreManager, SwapError};
+use spin_factor_key_value::{
+    log_error, Capabilities, Cas, Error, KeyPage, Store, StoreManager, SwapError, Transaction,
+    Write,
+};
 
 pub struct KeyValueAwsDynamo {
     /// AWS region
@@ -172,9 +176,144 @@ const PK: &str = "PK";
 const VAL: &str = "VAL";
 /// Version key in DynamoDB items used for atomic operations
 const VER: &str = "VER";
+/// Expiry key in DynamoDB items storing the expiry time in seconds since the Unix epoch
+///
+/// Configure this as the table's TTL attribute to have DynamoDB delete expired items. As
+/// DynamoDB deletes expired items lazily, reads also filter out items which have expired.
+const EXP: &str = "EXP";
+
+/// The current time as a duration since the Unix epoch
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// The expiry time of an item as a duration since the Unix epoch, if it has one
+fn expiry(item: &HashMap<String, AttributeValue>) -> Option<Duration> {
+    match item.get(EXP) {
+        Some(AttributeValue::N(exp)) => exp.parse().ok().map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+fn is_expired(item: &HashMap<String, AttributeValue>) -> bool {
+    expiry(item).is_some_and(|exp| exp <= now())
+}
+
+/// The maximum number of items in a single `TransactWriteItems` request
+const MAX_TRANSACTION_ITEMS: usize = 100;
+
+/// The maximum number of keys in a single `BatchGetItem` request
+const MAX_BATCH_GET_ITEMS: usize = 100;
+
+/// A condition expression, along with the attribute names and values it refers to
+struct Condition {
+    expression: String,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl Condition {
+    /// A condition that an item has the `expected` value, or doesn't exist if `None`
+    ///
+    /// Expired items are treated as missing.
+    fn value(expected: Option<Vec<u8>>) -> Self {
+        let names = HashMap::from([
+            ("#VAL".to_owned(), VAL.to_owned()),
+            ("#EXP".to_owned(), EXP.to_owned()),
+        ]);
+        let mut values = HashMap::from([(
+            ":now".to_owned(),
+            AttributeValue::N(now().as_secs().to_string()),
+        )]);
+        let expression = match expected {
+            Some(expected) => {
+                values.insert(
+                    ":expected".to_owned(),
+                    AttributeValue::B(Blob::new(expected)),
+                );
+                "#VAL = :expected AND (attribute_not_exists(#EXP) OR #EXP > :now)"
+            }
+            None => "attribute_not_exists(#VAL) OR #EXP <= :now",
+        };
+        Self {
+            expression: expression.to_owned(),
+            names,
+            values,
+        }
+    }
+}
+
+impl AwsDynamoStore {
+    /// Reads a batch of keys, which must not be more than [`MAX_BATCH_GET_ITEMS`], appending the
+    /// values found to `results`.
+    async fn batch_get(
+        &self,
+        keys: &[String],
+        results: &mut Vec<(String, Option<Vec<u8>>)>,
+    ) -> Result<(), Error> {
+        let mut keys_and_attributes_builder = KeysAndAttributes::builder()
+            .projection_expression(format!("{PK},{VAL},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
+            .consistent_read(self.consistent_read);
+        for key in keys {
+            keys_and_attributes_builder = keys_and_attributes_builder.keys(HashMap::from_iter([(
+                PK.to_owned(),
+                AttributeValue::S(key.clone()),
+            )]))
+        }
+        let mut request_items = Some(HashMap::from_iter([(
+            self.table.to_string(),
+            keys_and_attributes_builder.build().map_err(log_error)?,
+        )]));
+
+        while request_items.is_some() {
+            let BatchGetItemOutput {
+                responses,
+                unprocessed_keys,
+                ..
+            } = self
+                .client
+                .batch_get_item()
+                .set_request_items(request_items)
+                .send()
+                .await
+                .map_err(log_error)?;
+
+            if let Some(items) =
+                responses.and_then(|mut responses| responses.remove(self.table.as_str()))
+            {
+                for mut item in items.into_iter().filter(|item| !is_expired(item)) {
+                    match (item.remove(PK), item.remove(VAL)) {
+                        (Some(AttributeValue::S(pk)), Some(AttributeValue::B(val))) => {
+                            results.push((pk, Some(val.into_inner())));
+                        }
+                        (Some(AttributeValue::S(pk)), None) => {
+                            results.push((pk, None));
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            request_items = unprocessed_keys.filter(|unprocessed| !unprocessed.is_empty());
+        }
+
+        Ok(())
+    }
+}
 
 #[async_trait]
 impl Store for AwsDynamoStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            expiry: true,
+            transactions: true,
+            watch: false,
+        }
+    }
+
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
         let response = self
             .client
@@ -185,18 +324,22 @@ impl Store for AwsDynamoStore {
                 PK,
                 aws_sdk_dynamodb::types::AttributeValue::S(key.to_string()),
             )
-            .projection_expression(VAL)
+            .projection_expression(format!("{VAL},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
             .send()
             .await
             .map_err(log_error)?;
 
-        let item = response.item.and_then(|mut item| {
-            if let Some(AttributeValue::B(val)) = item.remove(VAL) {
-                Some(val.into_inner())
-            } else {
-                None
-            }
-        });
+        let item = response
+            .item
+            .filter(|item| !is_expired(item))
+            .and_then(|mut item| {
+                if let Some(AttributeValue::B(val)) = item.remove(VAL) {
+                    Some(val.into_inner())
+                } else {
+                    None
+                }
+            });
 
         Ok(item)
     }
@@ -213,6 +356,40 @@ impl Store for AwsDynamoStore {
         Ok(())
     }
 
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        // DynamoDB expiry has a granularity of seconds, so round up
+        let expires_at = (now() + ttl).as_millis().div_ceil(1000);
+        self.client
+            .put_item()
+            .table_name(self.table.as_str())
+            .item(PK, AttributeValue::S(key.to_string()))
+            .item(VAL, AttributeValue::B(Blob::new(value)))
+            .item(EXP, AttributeValue::N(expires_at.to_string()))
+            .send()
+            .await
+            .map_err(log_error)?;
+        Ok(())
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let GetItemOutput { item, .. } = self
+            .client
+            .get_item()
+            .consistent_read(self.consistent_read)
+            .table_name(self.table.as_str())
+            .key(PK, AttributeValue::S(key.to_string()))
+            .projection_expression(format!("{PK},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
+            .send()
+            .await
+            .map_err(log_error)?;
+
+        Ok(item
+            .and_then(|item| expiry(&item))
+            .map(|exp| exp.saturating_sub(now()))
+            .filter(|remaining| !remaining.is_zero()))
+    }
+
     async fn delete(&self, key: &str) -> Result<(), Error> {
         self.client
             .delete_item()
@@ -234,12 +411,15 @@ impl Store for AwsDynamoStore {
                 PK,
                 aws_sdk_dynamodb::types::AttributeValue::S(key.to_string()),
             )
-            .projection_expression(PK)
+            .projection_expression(format!("{PK},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
             .send()
             .await
             .map_err(log_error)?;
 
-        Ok(item.map(|item| item.contains_key(PK)).unwrap_or(false))
+        Ok(item
+            .map(|item| item.contains_key(PK) && !is_expired(&item))
+            .unwrap_or(false))
     }
 
     async fn get_keys(&self) -> Result<Vec<String>, Error> {
@@ -249,14 +429,15 @@ impl Store for AwsDynamoStore {
             .client
             .scan()
             .table_name(self.table.as_str())
-            .projection_expression(PK)
+            .projection_expression(format!("{PK},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
             .into_paginator()
             .send();
 
         while let Some(output) = scan_paginator.next().await {
             let scan_output = output.map_err(log_error)?;
             if let Some(items) = scan_output.items {
-                for mut item in items {
+                for mut item in items.into_iter().filter(|item| !is_expired(item)) {
                     if let Some(AttributeValue::S(pk)) = item.remove(PK) {
                         primary_keys.push(pk);
                     }
@@ -267,54 +448,62 @@ impl Store for AwsDynamoStore {
         Ok(primary_keys)
     }
 
-    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
-        let mut results = Vec::with_capacity(keys.len());
-        let mut keys_and_attributes_builder = KeysAndAttributes::builder()
-            .projection_expression(format!("{PK},{VAL}"))
-            .consistent_read(self.consistent_read);
-        for key in keys {
-            keys_and_attributes_builder = keys_and_attributes_builder.keys(HashMap::from_iter([(
-                PK.to_owned(),
-                AttributeValue::S(key),
-            )]))
+    /// Lists keys with a single page of a `Scan`, using the primary key of the last evaluated
+    /// item as the cursor.
+    ///
+    /// DynamoDB applies `limit` before filtering by prefix, so pages may contain fewer keys.
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let mut scan = self
+            .client
+            .scan()
+            .consistent_read(self.consistent_read)
+            .table_name(self.table.as_str())
+            .projection_expression("#PK,#EXP")
+            .expression_attribute_names("#PK", PK)
+            .expression_attribute_names("#EXP", EXP)
+            .limit(limit.try_into().unwrap_or(i32::MAX));
+        if let Some(prefix) = prefix {
+            scan = scan
+                .filter_expression("begins_with(#PK, :prefix)")
+                .expression_attribute_values(":prefix", AttributeValue::S(prefix.to_owned()));
+        }
+        if let Some(cursor) = cursor {
+            scan = scan.exclusive_start_key(PK, AttributeValue::S(cursor.to_owned()));
         }
-        let mut request_items = Some(HashMap::from_iter([(
-            self.table.to_string(),
-            keys_and_attributes_builder.build().map_err(log_error)?,
-        )]));
-
-        while request_items.is_some() {
-            let BatchGetItemOutput {
-                responses,
-                unprocessed_keys,
-                ..
-            } = self
-                .client
-                .batch_get_item()
-                .set_request_items(request_items)
-                .send()
-                .await
-                .map_err(log_error)?;
 
-            if let Some(items) =
-                responses.and_then(|mut responses| responses.remove(self.table.as_str()))
-            {
-                for mut item in items {
-                    match (item.remove(PK), item.remove(VAL)) {
-                        (Some(AttributeValue::S(pk)), Some(AttributeValue::B(val))) => {
-                            results.push((pk, Some(val.into_inner())));
-                        }
-                        (Some(AttributeValue::S(pk)), None) => {
-                            results.push((pk, None));
-                        }
-                        _ => (),
-                    }
-                }
-            }
+        let ScanOutput {
+            items,
+            last_evaluated_key,
+            ..
+        } = scan.send().await.map_err(log_error)?;
+
+        let keys = items
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|item| !is_expired(item))
+            .filter_map(|mut item| match item.remove(PK) {
+                Some(AttributeValue::S(pk)) => Some(pk),
+                _ => None,
+            })
+            .collect();
+        let cursor = last_evaluated_key.and_then(|mut key| match key.remove(PK) {
+            Some(AttributeValue::S(pk)) => Some(pk),
+            _ => None,
+        });
+        Ok(KeyPage { keys, cursor })
+    }
 
-            request_items = unprocessed_keys.filter(|unprocessed| !unprocessed.is_empty());
+    /// `get_many` reads the keys in batches of at most [`MAX_BATCH_GET_ITEMS`].
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let mut results = Vec::with_capacity(keys.len());
+        for keys in keys.chunks(MAX_BATCH_GET_ITEMS) {
+            self.batch_get(keys, &mut results).await?;
         }
-
         Ok(results)
     }
 
@@ -394,12 +583,14 @@ impl Store for AwsDynamoStore {
             .consistent_read(true)
             .table_name(self.table.as_str())
             .key(PK, AttributeValue::S(key.clone()))
-            .projection_expression(VAL)
+            .projection_expression(format!("{VAL},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
             .send()
             .await
             .map_err(log_error)?;
 
-        let old_val = match item {
+        // An expired item counts from zero, like a missing one
+        let old_val = match item.filter(|item| !is_expired(item)) {
             Some(mut current_item) => match current_item.remove(VAL) {
                 // We're expecting i64, so technically we could transmute but seems risky...
                 Some(AttributeValue::B(val)) => Some(
@@ -415,26 +606,28 @@ impl Store for AwsDynamoStore {
 
         let new_val = old_val.unwrap_or(0) + delta;
 
-        let mut update = Update::builder()
+        let Condition {
+            expression,
+            names,
+            mut values,
+        } = Condition::value(old_val.map(|old_val| old_val.to_string().into_bytes()));
+        values.insert(
+            ":new_val".to_owned(),
+            AttributeValue::B(Blob::new(new_val.to_string().as_bytes())),
+        );
+        // A live key keeps its expiry, as with the other backends, but one
+        // that had expired starts afresh without it
+        let update_expression = match old_val {
+            Some(_) => "SET #VAL = :new_val",
+            None => "SET #VAL = :new_val REMOVE #EXP",
+        };
+        let update = Update::builder()
             .table_name(self.table.as_str())
             .key(PK, AttributeValue::S(key))
-            .update_expression("SET #VAL = :new_val")
-            .expression_attribute_names("#VAL", VAL)
-            .expression_attribute_values(
-                ":new_val",
-                AttributeValue::B(Blob::new(new_val.to_string().as_bytes())),
-            );
-
-        if let Some(old_val) = old_val {
-            update = update
-                .condition_expression("#VAL = :old_val")
-                .expression_attribute_values(
-                    ":old_val",
-                    AttributeValue::B(Blob::new(old_val.to_string().as_bytes())),
-                )
-        } else {
-            update = update.condition_expression("attribute_not_exists (#VAL)")
-        }
+            .update_expression(update_expression)
+            .condition_expression(expression)
+            .set_expression_attribute_names(Some(names))
+            .set_expression_attribute_values(Some(values));
 
         self.client
             .transact_write_items()
@@ -463,6 +656,100 @@ impl Store for AwsDynamoStore {
             bucket_rep,
         }))
     }
+
+    /// `transact` applies the transaction with `TransactWriteItems`, in which each key may only
+    /// appear once: the last write to a key is applied, conditioned on the key's expected value.
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let mut conditions = HashMap::new();
+        for (key, expected) in transaction.conditions {
+            match conditions.entry(key) {
+                Entry::Vacant(entry) => {
+                    entry.insert(expected);
+                }
+                // A key can't have two different values
+                Entry::Occupied(entry) if *entry.get() != expected => return Ok(false),
+                Entry::Occupied(_) => {}
+            }
+        }
+        let mut writes = HashMap::new();
+        for write in transaction.writes {
+            writes.insert(write.key().to_owned(), write);
+        }
+
+        let keys: BTreeSet<String> = conditions.keys().chain(writes.keys()).cloned().collect();
+        if keys.len() > MAX_TRANSACTION_ITEMS {
+            return Err(Error::Other(format!(
+                "DynamoDB transactions are limited to {MAX_TRANSACTION_ITEMS} keys"
+            )));
+        }
+
+        let mut items = Vec::with_capacity(keys.len());
+        for key in keys {
+            let (expression, names, values) = match conditions.remove(&key).map(Condition::value) {
+                Some(Condition {
+                    expression,
+                    names,
+                    values,
+                }) => (Some(expression), Some(names), Some(values)),
+                None => (None, None, None),
+            };
+            let item = match writes.remove(&key) {
+                Some(Write::Set(_, value)) => TransactWriteItem::builder().put(
+                    Put::builder()
+                        .table_name(self.table.as_str())
+                        .item(PK, AttributeValue::S(key))
+                        .item(VAL, AttributeValue::B(Blob::new(value)))
+                        .set_condition_expression(expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(log_error)?,
+                ),
+                Some(Write::Delete(_)) => TransactWriteItem::builder().delete(
+                    Delete::builder()
+                        .table_name(self.table.as_str())
+                        .key(PK, AttributeValue::S(key))
+                        .set_condition_expression(expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(log_error)?,
+                ),
+                None => TransactWriteItem::builder().condition_check(
+                    ConditionCheck::builder()
+                        .table_name(self.table.as_str())
+                        .key(PK, AttributeValue::S(key))
+                        .set_condition_expression(expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(log_error)?,
+                ),
+            };
+            items.push(item.build());
+        }
+
+        match self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(err) => match err.as_service_error() {
+                Some(TransactWriteItemsError::TransactionCanceledException(canceled))
+                    if canceled
+                        .cancellation_reasons()
+                        .iter()
+                        .any(|reason| reason.code() == Some("ConditionalCheckFailed")) =>
+                {
+                    Ok(false)
+                }
+                _ => Err(log_error(err)),
+            },
+        }
+    }
 }
 
 #[async_trait]
@@ -474,12 +761,14 @@ impl Cas for CompareAndSwap {
             .consistent_read(true)
             .table_name(self.table.as_str())
             .key(PK, AttributeValue::S(self.key.clone()))
-            .projection_expression(format!("{VAL},{VER}"))
+            .projection_expression(format!("{VAL},{VER},#EXP"))
+            .expression_attribute_names("#EXP", EXP)
             .send()
             .await
             .map_err(log_error)?;
 
-        match item {
+        // An expired item is swapped like a missing one
+        match item.filter(|item| !is_expired(item)) {
             Some(mut current_item) => match (current_item.remove(VAL), current_item.remove(VER)) {
                 (Some(AttributeValue::B(val)), Some(AttributeValue::N(ver))) => {
                     self.state
@@ -511,13 +800,16 @@ impl Cas for CompareAndSwap {
 
     /// `swap` updates the value for the key -- if possible, using the version saved in the `current` function for
     /// optimistic concurrency or the previous item value
+    ///
+    /// Like `set`, the swapped value has no expiry.
     async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
         let mut update = Update::builder()
             .table_name(self.table.as_str())
             .key(PK, AttributeValue::S(self.key.clone()))
-            .update_expression("SET #VAL = :val ADD #VER :increment")
+            .update_expression("SET #VAL = :val ADD #VER :increment REMOVE #EXP")
             .expression_attribute_names("#VAL", VAL)
             .expression_attribute_names("#VER", VER)
+            .expression_attribute_names("#EXP", EXP)
             .expression_attribute_values(":val", AttributeValue::B(Blob::new(value)))
             .expression_attribute_values(":increment", AttributeValue::N("1".to_owned()));
 
@@ -534,7 +826,12 @@ impl Cas for CompareAndSwap {
                     .expression_attribute_values(":old_val", AttributeValue::B(old_val));
             }
             CasState::Unset => {
-                update = update.condition_expression("attribute_not_exists (#VAL)");
+                update = update
+                    .condition_expression("attribute_not_exists (#VAL) OR #EXP <= :now")
+                    .expression_attribute_values(
+                        ":now",
+                        AttributeValue::N(now().as_secs().to_string()),
+                    );
             }
             CasState::Unknown => (),
         };
@@ -2,14 +2,20 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use azure_data_cosmos::{
     prelude::{
-        AuthorizationToken, CollectionClient, CosmosClient, CosmosClientBuilder, Operation, Query,
+        AuthorizationToken, CollectionClient, CosmosClient, CosmosClientBuilder, Operation, Param,
+        Query,
     },
     CosmosEntity,
 };
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use spin_factor_key_value::{log_cas_error, log_error, Cas, Error, Store, StoreManager, SwapError};
-use std::sync::{Arc, Mutex};
+use spin_factor_key_value::{
+    log_cas_error, log_error, Capabilities, Cas, Error, KeyPage, Store, StoreManager, SwapError,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 pub struct KeyValueAzureCosmos {
     client: CollectionClient,
@@ -148,32 +154,51 @@ struct AzureCosmosStore {
 
 #[async_trait]
 impl Store for AzureCosmosStore {
+    /// Transactional batches in Cosmos DB are limited to a single partition, so transactions
+    /// aren't supported.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            expiry: true,
+            transactions: false,
+            watch: false,
+        }
+    }
+
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
         let pair = self.get_entity::<Pair>(key).await?;
         Ok(pair.map(|p| p.value))
     }
 
     async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
-        let illegal_chars = ['/', '\\', '?', '#'];
+        self.upsert(key, value, None).await
+    }
 
-        if key.contains(|c| illegal_chars.contains(&c)) {
-            return Err(Error::Other(format!(
-                "Key contains an illegal character. Keys must not include any of: {}",
-                illegal_chars.iter().collect::<String>()
-            )));
-        }
+    /// Sets a value which Cosmos DB expires after `ttl`, rounded up to whole seconds.
+    ///
+    /// Per-item expiry requires time to live to be enabled on the container
+    /// (e.g. with a default time to live of -1).
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let ttl = ttl
+            .as_millis()
+            .div_ceil(1000)
+            .try_into()
+            .map_err(log_error)?;
+        self.upsert(key, value, Some(ttl)).await
+    }
 
-        let pair = Pair {
-            id: key.to_string(),
-            value: value.to_vec(),
-            store_id: self.store_id.clone(),
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let Some(expiry) = self.get_entity::<Expiry>(key).await? else {
+            return Ok(None);
         };
-        self.client
-            .create_document(pair)
-            .is_upsert(true)
-            .await
-            .map_err(log_error)?;
-        Ok(())
+        // Cosmos DB uses a `ttl` of -1 for items which never expire
+        let Some(ttl) = expiry.ttl.and_then(|ttl| u64::try_from(ttl).ok()) else {
+            return Ok(None);
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let expires_at = Duration::from_secs(expiry.ts.saturating_add(ttl));
+        Ok(Some(expires_at.saturating_sub(now)).filter(|remaining| !remaining.is_zero()))
     }
 
     async fn delete(&self, key: &str) -> Result<(), Error> {
@@ -192,7 +217,7 @@ impl Store for AzureCosmosStore {
     async fn exists(&self, key: &str) -> Result<bool, Error> {
         let mut stream = self
             .client
-            .query_documents(Query::new(self.get_id_query(key)))
+            .query_documents(self.get_id_query(key))
             .query_cross_partition(true)
             .max_item_count(1)
             .into_stream::<Key>();
@@ -208,8 +233,36 @@ impl Store for AzureCosmosStore {
         self.get_keys().await
     }
 
+    /// Lists keys a page of query results at a time, using the query's continuation token as
+    /// the cursor.
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let mut query = self
+            .client
+            .query_documents(self.get_keys_query(prefix))
+            .query_cross_partition(true)
+            .max_item_count(i32::try_from(limit).unwrap_or(i32::MAX));
+        if let Some(cursor) = cursor {
+            query = query.continuation(cursor.to_owned());
+        }
+
+        let mut stream = query.into_stream::<Key>();
+        let Some(page) = stream.next().await else {
+            return Ok(KeyPage::default());
+        };
+        let page = page.map_err(log_error)?;
+        Ok(KeyPage {
+            keys: page.results.into_iter().map(|(key, _)| key.id).collect(),
+            cursor: page.continuation_token.map(|token| token.as_string()),
+        })
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
-        let stmt = Query::new(self.get_in_query(keys));
+        let stmt = self.get_in_query(keys);
         let query = self
             .client
             .query_documents(stmt)
@@ -321,14 +374,13 @@ struct CompareAndSwap {
 }
 
 impl CompareAndSwap {
-    fn get_query(&self) -> String {
-        let mut query = format!("SELECT * FROM c WHERE c.id='{}'", self.key);
-        self.append_store_id(&mut query, true);
-        query
-    }
-
-    fn append_store_id(&self, query: &mut String, condition_already_exists: bool) {
-        append_store_id_condition(query, self.store_id.as_deref(), condition_already_exists);
+    fn get_query(&self) -> Query {
+        build_query(
+            "SELECT * FROM c WHERE c.id = @key",
+            vec![Param::new("@key".to_owned(), self.key.as_str())],
+            self.store_id.as_deref(),
+            true,
+        )
     }
 }
 
@@ -339,7 +391,7 @@ impl Cas for CompareAndSwap {
     async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
         let mut stream = self
             .client
-            .query_documents(Query::new(self.get_query()))
+            .query_documents(self.get_query())
             .query_cross_partition(true)
             .max_item_count(1)
             .into_stream::<Pair>();
@@ -374,6 +426,7 @@ impl Cas for CompareAndSwap {
             id: self.key.clone(),
             value,
             store_id: self.store_id.clone(),
+            ttl: None,
         };
 
         let doc_client = self
@@ -413,13 +466,37 @@ impl Cas for CompareAndSwap {
 }
 
 impl AzureCosmosStore {
+    async fn upsert(&self, key: &str, value: &[u8], ttl: Option<i64>) -> Result<(), Error> {
+        let illegal_chars = ['/', '\\', '?', '#'];
+
+        if key.contains(|c| illegal_chars.contains(&c)) {
+            return Err(Error::Other(format!(
+                "Key contains an illegal character. Keys must not include any of: {}",
+                illegal_chars.iter().collect::<String>()
+            )));
+        }
+
+        let pair = Pair {
+            id: key.to_string(),
+            value: value.to_vec(),
+            store_id: self.store_id.clone(),
+            ttl,
+        };
+        self.client
+            .create_document(pair)
+            .is_upsert(true)
+            .await
+            .map_err(log_error)?;
+        Ok(())
+    }
+
     async fn get_entity<F>(&self, key: &str) -> Result<Option<F>, Error>
     where
         F: CosmosEntity + Send + Sync + serde::de::DeserializeOwned + Clone,
     {
         let query = self
             .client
-            .query_documents(Query::new(self.get_query(key)))
+            .query_documents(self.get_query(key))
             .query_cross_partition(true)
             .max_item_count(1);
 
@@ -438,7 +515,7 @@ impl AzureCosmosStore {
     async fn get_keys(&self) -> Result<Vec<String>, Error> {
         let query = self
             .client
-            .query_documents(Query::new(self.get_keys_query()))
+            .query_documents(self.get_keys_query(None))
             .query_cross_partition(true);
         let mut res = Vec::new();
 
@@ -451,57 +528,74 @@ impl AzureCosmosStore {
         Ok(res)
     }
 
-    fn get_query(&self, key: &str) -> String {
-        let mut query = format!("SELECT * FROM c WHERE c.id='{key}'");
-        self.append_store_id(&mut query, true);
-        query
+    fn get_query(&self, key: &str) -> Query {
+        self.build_query(
+            "SELECT * FROM c WHERE c.id = @key",
+            vec![Param::new("@key".to_owned(), key)],
+            true,
+        )
     }
 
-    fn get_id_query(&self, key: &str) -> String {
-        let mut query = format!("SELECT c.id, c.store_id FROM c WHERE c.id='{key}'");
-        self.append_store_id(&mut query, true);
-        query
+    fn get_id_query(&self, key: &str) -> Query {
+        self.build_query(
+            "SELECT c.id, c.store_id FROM c WHERE c.id = @key",
+            vec![Param::new("@key".to_owned(), key)],
+            true,
+        )
     }
 
-    fn get_keys_query(&self) -> String {
-        let mut query = "SELECT c.id, c.store_id FROM c".to_owned();
-        self.append_store_id(&mut query, false);
-        query
+    fn get_keys_query(&self, prefix: Option<&str>) -> Query {
+        match prefix {
+            Some(prefix) => self.build_query(
+                "SELECT c.id, c.store_id FROM c WHERE STARTSWITH(c.id, @prefix)",
+                vec![Param::new("@prefix".to_owned(), prefix)],
+                true,
+            ),
+            None => self.build_query("SELECT c.id, c.store_id FROM c", vec![], false),
+        }
     }
 
-    fn get_in_query(&self, keys: Vec<String>) -> String {
-        let in_clause: String = keys
-            .into_iter()
-            .map(|k| format!("'{k}'"))
-            .collect::<Vec<String>>()
-            .join(", ");
-
-        let mut query = format!("SELECT * FROM c WHERE c.id IN ({in_clause})");
-        self.append_store_id(&mut query, true);
-        query
+    fn get_in_query(&self, keys: Vec<String>) -> Query {
+        self.build_query(
+            "SELECT * FROM c WHERE ARRAY_CONTAINS(@keys, c.id)",
+            vec![Param::new("@keys".to_owned(), keys)],
+            true,
+        )
     }
 
-    fn append_store_id(&self, query: &mut String, condition_already_exists: bool) {
-        append_store_id_condition(query, self.store_id.as_deref(), condition_already_exists);
+    fn build_query(
+        &self,
+        query: &str,
+        params: Vec<Param>,
+        condition_already_exists: bool,
+    ) -> Query {
+        build_query(
+            query,
+            params,
+            self.store_id.as_deref(),
+            condition_already_exists,
+        )
     }
 }
 
-/// Appends an option store id condition to the query.
-fn append_store_id_condition(
-    query: &mut String,
+/// Builds a query with the given parameters, adding a condition on the store id, if any.
+fn build_query(
+    query: &str,
+    mut params: Vec<Param>,
     store_id: Option<&str>,
     condition_already_exists: bool,
-) {
-    if let Some(s) = store_id {
+) -> Query {
+    let mut query = query.to_owned();
+    if let Some(store_id) = store_id {
         if condition_already_exists {
             query.push_str(" AND");
         } else {
             query.push_str(" WHERE");
         }
-        query.push_str(" c.store_id='");
-        query.push_str(s);
-        query.push('\'')
+        query.push_str(" c.store_id = @store_id");
+        params.push(Param::new("@store_id".to_owned(), store_id));
     }
+    Query::with_params(query, params)
 }
 
 // Pair structure for key value operations
@@ -511,6 +605,9 @@ pub struct Pair {
     pub value: Vec<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub store_id: Option<String>,
+    /// The item's time to live in seconds, if it expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i64>,
 }
 
 impl CosmosEntity for Pair {
@@ -553,3 +650,24 @@ impl CosmosEntity for Key {
         self.store_id.clone().unwrap_or_else(|| self.id.clone())
     }
 }
+
+// Expiry structure for time to live operations
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Expiry {
+    pub id: String,
+    #[serde(default)]
+    pub ttl: Option<i64>,
+    /// The time the item was last modified, in seconds since the Unix epoch
+    #[serde(rename = "_ts")]
+    pub ts: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store_id: Option<String>,
+}
+
+impl CosmosEntity for Expiry {
+    type Entity = String;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.store_id.clone().unwrap_or_else(|| self.id.clone())
+    }
+}
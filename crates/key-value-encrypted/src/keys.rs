@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use aes_gcm::{
+    aead::{
+        generic_array::{typenum::Unsigned, GenericArray},
+        Aead, AeadCore, KeyInit, OsRng, Payload,
+    },
+    Aes256Gcm,
+};
+use anyhow::{bail, ensure, Context as _};
+use base64::Engine as _;
+use chacha20poly1305::XChaCha20Poly1305;
+use serde::Deserialize;
+use spin_expressions::{Key, Provider};
+
+/// The version of the format in which encrypted values are stored.
+const ENVELOPE_VERSION: u8 = 1;
+
+/// The length in bytes of the keys used by every [`Algorithm`].
+const KEY_LEN: usize = 32;
+
+/// An authenticated encryption algorithm with which values may be encrypted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            Self::Aes256Gcm => 1,
+            Self::XChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Aes256Gcm),
+            2 => Some(Self::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    /// Returns the nonce followed by the ciphertext.
+    fn seal(self, key: &[u8; KEY_LEN], plaintext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Aes256Gcm => seal::<Aes256Gcm>(key, plaintext, aad),
+            Self::XChaCha20Poly1305 => seal::<XChaCha20Poly1305>(key, plaintext, aad),
+        }
+    }
+
+    /// Decrypts the output of [`Self::seal`], returning `None` if it isn't authentic.
+    fn open(self, key: &[u8; KEY_LEN], sealed: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Aes256Gcm => open::<Aes256Gcm>(key, sealed, aad),
+            Self::XChaCha20Poly1305 => open::<XChaCha20Poly1305>(key, sealed, aad),
+        }
+    }
+}
+
+fn seal<C: Aead + AeadCore + KeyInit>(
+    key: &[u8; KEY_LEN],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Option<Vec<u8>> {
+    let cipher = C::new_from_slice(key).ok()?;
+    let nonce = C::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .ok()?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Some(sealed)
+}
+
+fn open<C: Aead + AeadCore + KeyInit>(
+    key: &[u8; KEY_LEN],
+    sealed: &[u8],
+    aad: &[u8],
+) -> Option<Vec<u8>> {
+    let nonce_len = C::NonceSize::USIZE;
+    if sealed.len() < nonce_len {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(nonce_len);
+    let cipher = C::new_from_slice(key).ok()?;
+    cipher
+        .decrypt(
+            GenericArray::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .ok()
+}
+
+/// Configuration of the keys with which a store's values are encrypted.
+pub struct KeyRingConfig {
+    /// The algorithm with which new values are encrypted.
+    pub algorithm: Algorithm,
+    /// The ID of the key with which new values are encrypted.
+    pub current_key: String,
+    /// The name of the variable containing each key, by key ID.
+    pub keys: HashMap<String, String>,
+}
+
+impl KeyRingConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.keys.contains_key(&self.current_key),
+            "current_key {:?} is not one of the configured keys",
+            self.current_key
+        );
+        for (id, variable) in &self.keys {
+            ensure!(
+                !id.is_empty() && id.len() <= u8::MAX as usize,
+                "key ID {id:?} must be between 1 and {} bytes long",
+                u8::MAX
+            );
+            Key::new(variable).with_context(|| format!("invalid variable name for key {id:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The keys with which a store's values are encrypted and decrypted.
+pub struct KeyRing {
+    algorithm: Algorithm,
+    current_key: String,
+    keys: HashMap<String, [u8; KEY_LEN]>,
+}
+
+impl KeyRing {
+    /// Reads the configured keys from the first of `providers` to have a value for each
+    /// variable. Keys must be base64-encoded and 32 bytes long.
+    pub async fn load(
+        config: &KeyRingConfig,
+        providers: &[Box<dyn Provider>],
+    ) -> anyhow::Result<Self> {
+        let mut keys = HashMap::with_capacity(config.keys.len());
+        for (id, variable) in &config.keys {
+            let encoded = get_variable(providers, variable)
+                .await?
+                .with_context(|| format!("no value for variable {variable:?} (key {id:?})"))?;
+            let key = base64::engine::general_purpose::STANDARD
+                .decode(encoded.trim())
+                .ok()
+                .and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+                .with_context(|| {
+                    format!(
+                        "variable {variable:?} (key {id:?}) must be {KEY_LEN} base64-encoded bytes"
+                    )
+                })?;
+            keys.insert(id.clone(), key);
+        }
+        Ok(Self {
+            algorithm: config.algorithm,
+            current_key: config.current_key.clone(),
+            keys,
+        })
+    }
+
+    /// Encrypts the `value` of `key` with the current key.
+    ///
+    /// The value is bound to `key`, so can't be decrypted as the value of any other key.
+    pub fn encrypt(&self, key: &str, value: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let id = &self.current_key;
+        let sealed = self
+            .algorithm
+            .seal(&self.keys[id], value, key.as_bytes())
+            .context("encryption failed")?;
+        let mut envelope = Vec::with_capacity(3 + id.len() + sealed.len());
+        envelope.push(ENVELOPE_VERSION);
+        envelope.push(self.algorithm.id());
+        envelope.push(id.len() as u8);
+        envelope.extend(id.as_bytes());
+        envelope.extend(sealed);
+        Ok(envelope)
+    }
+
+    /// Decrypts the `envelope` stored as the value of `key`, with whichever key encrypted it.
+    pub fn decrypt(&self, key: &str, envelope: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let [version, algorithm, id_len, rest @ ..] = envelope else {
+            bail!("value is not encrypted");
+        };
+        ensure!(
+            *version == ENVELOPE_VERSION,
+            "unsupported encrypted value version {version}"
+        );
+        let algorithm =
+            Algorithm::from_id(*algorithm).context("value encrypted with unknown algorithm")?;
+        let (id, sealed) = rest
+            .split_at_checked(*id_len as usize)
+            .context("value is not encrypted")?;
+        let id = String::from_utf8_lossy(id);
+        let secret = self
+            .keys
+            .get(id.as_ref())
+            .with_context(|| format!("value encrypted with unknown key {id:?}"))?;
+        algorithm
+            .open(secret, sealed, key.as_bytes())
+            .with_context(|| format!("value could not be decrypted with key {id:?}"))
+    }
+}
+
+async fn get_variable(
+    providers: &[Box<dyn Provider>],
+    name: &str,
+) -> anyhow::Result<Option<String>> {
+    let key = Key::new(name)?;
+    for provider in providers {
+        if let Some(value) = provider.get(&key).await? {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,89 @@
+mod keys;
+mod store;
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_expressions::Provider;
+use spin_factor_key_value::runtime_config::spin::{
+    MakeKeyValueStore, RuntimeConfigResolver, StoreConfig,
+};
+
+pub use keys::Algorithm;
+use keys::KeyRingConfig;
+pub use store::KeyValueEncrypted;
+
+/// A key-value store that encrypts the values of another store.
+///
+/// Encryption keys are read from variables providers, so may be kept in a
+/// secret store such as Vault. Each key has an ID which is stored alongside
+/// the values it encrypted, so keys can be rotated by adding a new key,
+/// making it current, and retiring the old key once every value it encrypted
+/// has been rewritten.
+pub struct EncryptedKeyValueStore {
+    /// Resolves the configuration of the backing store.
+    resolver: RuntimeConfigResolver,
+    /// The providers from which encryption keys are read.
+    providers: Arc<[Box<dyn Provider>]>,
+}
+
+impl EncryptedKeyValueStore {
+    /// Creates a new `EncryptedKeyValueStore` whose backing stores may be of
+    /// any type registered with `resolver`, and whose keys are read from the
+    /// first of `providers` to provide them.
+    pub fn new(
+        resolver: RuntimeConfigResolver,
+        providers: impl IntoIterator<Item = Box<dyn Provider>>,
+    ) -> Self {
+        Self {
+            resolver,
+            providers: providers.into_iter().collect(),
+        }
+    }
+}
+
+/// Runtime configuration for the encrypted key-value store.
+#[derive(Deserialize)]
+pub struct EncryptedKeyValueRuntimeConfig {
+    /// The algorithm with which new values are encrypted. Defaults to AES-256-GCM.
+    #[serde(default)]
+    algorithm: Algorithm,
+    /// The ID of the key with which new values are encrypted.
+    current_key: String,
+    /// The name of the variable containing each key, by key ID.
+    ///
+    /// Each variable must contain 32 base64-encoded bytes.
+    keys: HashMap<String, String>,
+    /// The configuration of the backing store, including its `type`.
+    backing: StoreConfig,
+}
+
+impl MakeKeyValueStore for EncryptedKeyValueStore {
+    const RUNTIME_CONFIG_TYPE: &'static str = "encrypted";
+
+    type RuntimeConfig = EncryptedKeyValueRuntimeConfig;
+
+    type StoreManager = KeyValueEncrypted;
+
+    fn make_store(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::StoreManager> {
+        let config = KeyRingConfig {
+            algorithm: runtime_config.algorithm,
+            current_key: runtime_config.current_key,
+            keys: runtime_config.keys,
+        };
+        config.validate()?;
+        let backing = self
+            .resolver
+            .store_manager_from_config(runtime_config.backing)
+            .context("could not make the backing store of an encrypted key-value store")?;
+        Ok(KeyValueEncrypted::new(
+            backing,
+            config,
+            self.providers.clone(),
+        ))
+    }
+}
@@ -0,0 +1,351 @@
+use std::sync::Arc;
+
+use spin_core::async_trait;
+use spin_expressions::Provider;
+use spin_factor_key_value::{
+    log_cas_error, log_error, Capabilities, Cas, Error, KeyChanges, KeyPage, Store, StoreManager,
+    SwapError,
+};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+use crate::keys::{KeyRing, KeyRingConfig};
+
+/// A [`StoreManager`] which encrypts the values of the stores of another manager.
+///
+/// Keys are read from the variables providers when a store is first opened.
+pub struct KeyValueEncrypted {
+    backing: Arc<dyn StoreManager>,
+    config: KeyRingConfig,
+    providers: Arc<[Box<dyn Provider>]>,
+    key_ring: OnceCell<Arc<KeyRing>>,
+}
+
+impl KeyValueEncrypted {
+    pub fn new(
+        backing: Arc<dyn StoreManager>,
+        config: KeyRingConfig,
+        providers: Arc<[Box<dyn Provider>]>,
+    ) -> Self {
+        Self {
+            backing,
+            config,
+            providers,
+            key_ring: OnceCell::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValueEncrypted {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let key_ring = self
+            .key_ring
+            .get_or_try_init(|| async {
+                KeyRing::load(&self.config, &self.providers)
+                    .await
+                    .map(Arc::new)
+            })
+            .await
+            .map_err(log_error)?
+            .clone();
+        let backing = self.backing.get(name).await?;
+        Ok(Arc::new(EncryptedStore { backing, key_ring }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.backing.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        let backing = self
+            .backing
+            .summary(store_name)
+            .unwrap_or_else(|| "another store".into());
+        Some(format!("{backing}, with encrypted values"))
+    }
+}
+
+struct EncryptedStore {
+    backing: Arc<dyn Store>,
+    key_ring: Arc<KeyRing>,
+}
+
+impl EncryptedStore {
+    fn encrypt(&self, key: &str, value: &[u8]) -> Result<Vec<u8>, Error> {
+        self.key_ring.encrypt(key, value).map_err(log_error)
+    }
+
+    fn decrypt(&self, key: &str, value: Option<Vec<u8>>) -> Result<Option<Vec<u8>>, Error> {
+        value
+            .map(|value| self.key_ring.decrypt(key, &value))
+            .transpose()
+            .map_err(|err| {
+                log_error(err.context(format!("could not decrypt the value of key {key:?}")))
+            })
+    }
+}
+
+/// `EncryptedStore` encrypts values, but not keys, before writing them to its backing store.
+///
+/// Operations which depend on the backing store interpreting values are not supported:
+/// `increment` returns an error, as do transactions (whose expected values can't be compared
+/// with encrypted values). Compare-and-swap is supported, as the backing store only compares
+/// the encrypted value it read with the one it's replacing.
+#[async_trait]
+impl Store for EncryptedStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.backing.after_open().await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            transactions: false,
+            ..self.backing.capabilities()
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.backing.get(key).await?;
+        self.decrypt(key, value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let value = self.encrypt(key, value)?;
+        self.backing.set(key, &value).await
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let value = self.encrypt(key, value)?;
+        self.backing.set_with_ttl(key, &value, ttl).await
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        self.backing.get_ttl(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.backing.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.backing.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.backing.get_keys().await
+    }
+
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        self.backing.list_keys(prefix, cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.backing
+            .get_many(keys)
+            .await?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.decrypt(&key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let key_values = key_values
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.encrypt(&key, &value)?;
+                Ok((key, value))
+            })
+            .collect::<Result<_, Error>>()?;
+        self.backing.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.backing.delete_many(keys).await
+    }
+
+    async fn increment(&self, _key: String, _delta: i64) -> Result<i64, Error> {
+        Err(Error::Other(
+            "increment is not supported by key-value stores with encrypted values".to_owned(),
+        ))
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = self.backing.new_compare_and_swap(bucket_rep, key).await?;
+        Ok(Arc::new(CompareAndSwap {
+            inner,
+            key: key.to_owned(),
+            key_ring: self.key_ring.clone(),
+        }))
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        self.backing.watch(prefix).await
+    }
+}
+
+/// A compare-and-swap on the backing store, which decrypts the current value and encrypts the
+/// new one.
+struct CompareAndSwap {
+    inner: Arc<dyn Cas>,
+    key: String,
+    key_ring: Arc<KeyRing>,
+}
+
+#[async_trait]
+impl Cas for CompareAndSwap {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.inner
+            .current()
+            .await?
+            .map(|value| self.key_ring.decrypt(&self.key, &value))
+            .transpose()
+            .map_err(log_error)
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let value = self
+            .key_ring
+            .encrypt(&self.key, &value)
+            .map_err(log_cas_error)?;
+        self.inner.swap(value).await
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keys::Algorithm;
+    use base64::Engine as _;
+    use spin_expressions::Key;
+    use spin_key_value_memory::KeyValueMemory;
+    use std::collections::HashMap;
+
+    #[derive(Debug)]
+    struct Variables(HashMap<String, String>);
+
+    #[async_trait]
+    impl Provider for Variables {
+        async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+            Ok(self.0.get(key.as_str()).cloned())
+        }
+    }
+
+    fn providers() -> Arc<[Box<dyn Provider>]> {
+        let encode = |byte| base64::engine::general_purpose::STANDARD.encode([byte; 32]);
+        let variables = HashMap::from([
+            ("key_one".to_owned(), encode(1)),
+            ("key_two".to_owned(), encode(2)),
+        ]);
+        Arc::from([Box::new(Variables(variables)) as Box<dyn Provider>])
+    }
+
+    fn config(algorithm: Algorithm, current_key: &str, keys: &[&str]) -> KeyRingConfig {
+        KeyRingConfig {
+            algorithm,
+            current_key: current_key.to_owned(),
+            keys: keys
+                .iter()
+                .map(|id| (id.to_string(), format!("key_{id}")))
+                .collect(),
+        }
+    }
+
+    async fn encrypted(
+        backing: &Arc<KeyValueMemory>,
+        config: KeyRingConfig,
+    ) -> Result<Arc<dyn Store>, Error> {
+        KeyValueEncrypted::new(backing.clone(), config, providers())
+            .get("default")
+            .await
+    }
+
+    #[tokio::test]
+    async fn encrypts_values() -> Result<(), Error> {
+        for algorithm in [Algorithm::Aes256Gcm, Algorithm::XChaCha20Poly1305] {
+            let backing = Arc::new(KeyValueMemory::new());
+            let store = encrypted(&backing, config(algorithm, "one", &["one"])).await?;
+            store.set("a", b"secret").await?;
+            store
+                .set_many(vec![("b".to_owned(), b"other".to_vec())])
+                .await?;
+            assert_eq!(store.get("a").await?, Some(b"secret".to_vec()));
+            assert_eq!(
+                store.get_many(vec!["b".to_owned(), "c".to_owned()]).await?,
+                [
+                    ("b".to_owned(), Some(b"other".to_vec())),
+                    ("c".to_owned(), None)
+                ]
+            );
+
+            let at_rest = backing.get("default").await?.get("a").await?.unwrap();
+            assert!(!at_rest.windows(6).any(|w| w == b"secret"));
+
+            // Values are bound to their keys
+            backing.get("default").await?.set("b", &at_rest).await?;
+            assert!(store.get("b").await.is_err());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rotates_keys() -> Result<(), Error> {
+        let backing = Arc::new(KeyValueMemory::new());
+        let store = encrypted(&backing, config(Algorithm::Aes256Gcm, "one", &["one"])).await?;
+        store.set("old", b"1").await?;
+
+        // Values encrypted with the old key can be read until they're rewritten
+        let rotated = config(Algorithm::XChaCha20Poly1305, "two", &["one", "two"]);
+        let store = encrypted(&backing, rotated).await?;
+        assert_eq!(store.get("old").await?, Some(b"1".to_vec()));
+        store.set("new", b"2").await?;
+
+        let retired = encrypted(&backing, config(Algorithm::Aes256Gcm, "two", &["two"])).await?;
+        assert_eq!(retired.get("new").await?, Some(b"2".to_vec()));
+        assert!(retired.get("old").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_and_swap_works_but_increment_does_not() -> Result<(), Error> {
+        let backing = Arc::new(KeyValueMemory::new());
+        let store = encrypted(&backing, config(Algorithm::Aes256Gcm, "one", &["one"])).await?;
+        store.set("a", b"1").await?;
+
+        let cas = store.new_compare_and_swap(0, "a").await?;
+        assert_eq!(cas.current().await?, Some(b"1".to_vec()));
+        cas.swap(b"2".to_vec()).await.unwrap();
+        assert_eq!(store.get("a").await?, Some(b"2".to_vec()));
+
+        assert!(store.increment("n".to_owned(), 1).await.is_err());
+        assert!(!store.capabilities().transactions);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_keys_are_reported() {
+        let backing = Arc::new(KeyValueMemory::new());
+        let result = encrypted(&backing, config(Algorithm::Aes256Gcm, "three", &["three"])).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use futures::StreamExt;
+use spin_core::async_trait;
+use spin_factor_key_value::{
+    log_error, Capabilities, Cas, Error, KeyChange, KeyChangeKind, KeyChanges, KeyPage, Store,
+    StoreManager, SwapError, Transaction, Write,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// The number of changes buffered for each watcher, beyond which a slow watcher misses changes.
+const WATCH_CAPACITY: usize = 1024;
+
+/// A [`StoreManager`] for in-memory key-value stores.
+///
+/// Each label gets its own store, which lives as long as the manager.
+#[derive(Default)]
+pub struct KeyValueMemory {
+    stores: Mutex<HashMap<String, Arc<MemoryStore>>>,
+}
+
+impl KeyValueMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValueMemory {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let store = self
+            .stores
+            .lock()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(MemoryStore::new()))
+            .clone();
+        Ok(store)
+    }
+
+    fn is_defined(&self, _store_name: &str) -> bool {
+        true
+    }
+
+    fn summary(&self, _store_name: &str) -> Option<String> {
+        Some("a temporary in-memory store".into())
+    }
+}
+
+struct MemoryStore {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl MemoryStore {
+    fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries::new())),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap()
+    }
+}
+
+/// The contents of a [`MemoryStore`], along with the sender of changes to them.
+///
+/// Expired entries are removed, and reported to watchers as deleted, when they are next accessed.
+struct Entries {
+    map: BTreeMap<String, Entry>,
+    changes: broadcast::Sender<KeyChange>,
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+impl Entries {
+    fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            changes: broadcast::channel(WATCH_CAPACITY).0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&Entry> {
+        let now = Instant::now();
+        if self.map.get(key).is_some_and(|entry| entry.is_expired(now)) {
+            self.map.remove(key);
+            self.notify(key, KeyChangeKind::Delete);
+        }
+        self.map.get(key)
+    }
+
+    fn value(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.get(key).map(|entry| entry.value.clone())
+    }
+
+    fn set(&mut self, key: String, value: Vec<u8>, expires_at: Option<Instant>) {
+        self.notify(&key, KeyChangeKind::Set);
+        self.map.insert(key, Entry { value, expires_at });
+    }
+
+    fn delete(&mut self, key: &str) {
+        if self.get(key).is_some() {
+            self.map.remove(key);
+            self.notify(key, KeyChangeKind::Delete);
+        }
+    }
+
+    /// Removes all expired entries, so that the remaining entries may be iterated.
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .map
+            .iter()
+            .filter(|(_, entry)| entry.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            self.map.remove(&key);
+            self.notify(&key, KeyChangeKind::Delete);
+        }
+    }
+
+    fn notify(&self, key: &str, kind: KeyChangeKind) {
+        if self.changes.receiver_count() == 0 {
+            return;
+        }
+        let change = KeyChange {
+            key: key.to_owned(),
+            kind,
+        };
+        // Sending only fails if there are no receivers
+        _ = self.changes.send(change);
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            expiry: true,
+            transactions: true,
+            watch: true,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.entries().value(key))
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.entries().set(key.to_owned(), value.to_owned(), None);
+        Ok(())
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        // A TTL too long to represent will never be reached
+        let expires_at = Instant::now().checked_add(ttl);
+        self.entries()
+            .set(key.to_owned(), value.to_owned(), expires_at);
+        Ok(())
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let now = Instant::now();
+        Ok(self
+            .entries()
+            .get(key)
+            .and_then(|entry| entry.expires_at)
+            .map(|at| at.saturating_duration_since(now)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.entries().delete(key);
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.entries().get(key).is_some())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        let mut entries = self.entries();
+        entries.remove_expired();
+        Ok(entries.map.keys().cloned().collect())
+    }
+
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let prefix = prefix.unwrap_or_default();
+        let start = match cursor {
+            Some(cursor) if cursor >= prefix => Bound::Excluded(cursor),
+            _ => Bound::Included(prefix),
+        };
+        let mut entries = self.entries();
+        entries.remove_expired();
+        let mut keys = entries
+            .map
+            .range::<str, _>((start, Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            // Take an extra key to find out whether there's another page
+            .take(limit as usize + 1)
+            .cloned()
+            .collect::<Vec<_>>();
+        let cursor = if keys.len() > limit as usize {
+            keys.truncate(limit as usize);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Ok(KeyPage { keys, cursor })
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let mut entries = self.entries();
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = entries.value(&key);
+                (key, value)
+            })
+            .collect())
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let mut entries = self.entries();
+        for (key, value) in key_values {
+            entries.set(key, value, None);
+        }
+        Ok(())
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let mut entries = self.entries();
+        for key in keys {
+            entries.delete(&key);
+        }
+        Ok(())
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let mut entries = self.entries();
+        let (current, expires_at) = match entries.get(&key) {
+            Some(entry) => {
+                let bytes = entry.value.as_slice().try_into().map_err(|_| {
+                    Error::Other(format!("value of key {key:?} is not a 64-bit integer"))
+                })?;
+                (i64::from_le_bytes(bytes), entry.expires_at)
+            }
+            None => (0, None),
+        };
+        let new_value = current
+            .checked_add(delta)
+            .ok_or_else(|| Error::Other(format!("incrementing key {key:?} overflowed")))?;
+        // Incrementing keeps the expiry of a live key, as with the other backends
+        entries.set(key, new_value.to_le_bytes().to_vec(), expires_at);
+        Ok(new_value)
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        Ok(Arc::new(CompareAndSwap {
+            key: key.to_owned(),
+            bucket_rep,
+            entries: self.entries.clone(),
+            current: Mutex::new(None),
+        }))
+    }
+
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let mut entries = self.entries();
+        for (key, expected) in &transaction.conditions {
+            if entries.value(key) != *expected {
+                return Ok(false);
+            }
+        }
+        for write in transaction.writes {
+            match write {
+                Write::Set(key, value) => entries.set(key, value, None),
+                Write::Delete(key) => entries.delete(&key),
+            }
+        }
+        Ok(true)
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        let receiver = self.entries().changes.subscribe();
+        let changes = futures::stream::unfold(
+            (receiver, prefix.to_owned()),
+            |(mut receiver, prefix)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(change) if change.key.starts_with(&prefix) => {
+                            return Some((change, (receiver, prefix)));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(missed)) => {
+                            log_error(format!("watcher missed {missed} changes"));
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        );
+        Ok(changes.boxed())
+    }
+}
+
+/// A compare-and-swap on a [`MemoryStore`], which succeeds if the value is unchanged since it
+/// was read with [`Cas::current`].
+struct CompareAndSwap {
+    key: String,
+    bucket_rep: u32,
+    entries: Arc<Mutex<Entries>>,
+    current: Mutex<Option<Option<Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Cas for CompareAndSwap {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.entries.lock().unwrap().value(&self.key);
+        *self.current.lock().unwrap() = Some(value.clone());
+        Ok(value)
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(expected) = self.current.lock().unwrap().take() {
+            if entries.value(&self.key) != expected {
+                return Err(SwapError::CasFailed(format!(
+                    "key {:?} was modified",
+                    self.key
+                )));
+            }
+        }
+        entries.set(self.key.clone(), value, None);
+        Ok(())
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.bucket_rep
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn store() -> Arc<dyn Store> {
+        KeyValueMemory::new().get("default").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn stores_are_shared_per_label() -> Result<(), Error> {
+        let kv = KeyValueMemory::new();
+        kv.get("default").await?.set("a", b"1").await?;
+        assert_eq!(
+            kv.get("default").await?.get("a").await?,
+            Some(b"1".to_vec())
+        );
+        assert_eq!(kv.get("other").await?.get("a").await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expiry() -> Result<(), Error> {
+        let store = store().await;
+        store.set_with_ttl("short", b"1", Duration::ZERO).await?;
+        store
+            .set_with_ttl("long", b"1", Duration::from_secs(3600))
+            .await?;
+        assert!(!store.exists("short").await?);
+        assert!(store.get_ttl("long").await?.unwrap() > Duration::from_secs(3500));
+        assert_eq!(store.get_keys().await?, ["long"]);
+
+        // Incrementing keeps the expiry, while setting removes it
+        store.increment("long".to_owned(), 1).await?;
+        assert!(store.get_ttl("long").await?.is_some());
+        store.set("long", b"1").await?;
+        assert_eq!(store.get_ttl("long").await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_keys() -> Result<(), Error> {
+        let store = store().await;
+        for key in ["a1", "a2", "a3", "b1", "0"] {
+            store.set(key, b"value").await?;
+        }
+
+        let page = store.list_keys(Some("a"), None, 2).await?;
+        assert_eq!(page.keys, ["a1", "a2"]);
+        let page = store
+            .list_keys(Some("a"), page.cursor.as_deref(), 2)
+            .await?;
+        assert_eq!(page.keys, ["a3"]);
+        assert_eq!(page.cursor, None);
+
+        let page = store.list_keys(None, None, 10).await?;
+        assert_eq!(page.keys.len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_and_swap() -> Result<(), Error> {
+        let store = store().await;
+        store.set("key", b"1").await?;
+
+        let cas = store.new_compare_and_swap(0, "key").await?;
+        assert_eq!(cas.current().await?, Some(b"1".to_vec()));
+        cas.swap(b"2".to_vec()).await.unwrap();
+        assert_eq!(store.get("key").await?, Some(b"2".to_vec()));
+
+        let cas = store.new_compare_and_swap(0, "key").await?;
+        cas.current().await?;
+        store.set("key", b"3").await?;
+        assert!(matches!(
+            cas.swap(b"4".to_vec()).await,
+            Err(SwapError::CasFailed(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn transactions_and_watch() -> Result<(), Error> {
+        let store = store().await;
+        let mut changes = store.watch("a").await?;
+
+        let transaction = Transaction {
+            conditions: vec![("a1".to_owned(), None)],
+            writes: vec![
+                Write::Set("a1".to_owned(), b"1".to_vec()),
+                Write::Set("b1".to_owned(), b"1".to_vec()),
+            ],
+        };
+        assert!(store.transact(transaction.clone()).await?);
+        // The condition no longer holds
+        assert!(!store.transact(transaction).await?);
+        store.delete("a1").await?;
+
+        let change = changes.next().await.unwrap();
+        assert_eq!(
+            (change.key.as_str(), change.kind),
+            ("a1", KeyChangeKind::Set)
+        );
+        let change = changes.next().await.unwrap();
+        assert_eq!(
+            (change.key.as_str(), change.kind),
+            ("a1", KeyChangeKind::Delete)
+        );
+        Ok(())
+    }
+}
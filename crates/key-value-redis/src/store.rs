@@ -1,23 +1,42 @@
 use anyhow::{Context, Result};
-use redis::{aio::ConnectionManager, parse_redis_url, AsyncCommands, Client, RedisError};
+use futures::StreamExt;
+use redis::{
+    aio::{ConnectionManager, MultiplexedConnection},
+    parse_redis_url, AsyncCommands, Client, RedisError,
+};
 use spin_core::async_trait;
-use spin_factor_key_value::{log_error, Cas, Error, Store, StoreManager, SwapError};
-use std::sync::Arc;
+use spin_factor_key_value::{
+    log_error, Capabilities, Cas, Error, KeyChange, KeyChangeKind, KeyChanges, KeyPage, Store,
+    StoreManager, SwapError, Transaction, Write,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::sync::OnceCell;
 use url::Url;
 
+/// The number of idle transaction connections kept open for reuse.
+const MAX_IDLE_TRANSACTION_CONNECTIONS: usize = 8;
+
 pub struct KeyValueRedis {
     database_url: Url,
     connection: OnceCell<ConnectionManager>,
+    transaction_connections: Arc<TransactionConnections>,
 }
 
 impl KeyValueRedis {
     pub fn new(address: String) -> Result<Self> {
         let database_url = parse_redis_url(&address).context("Invalid Redis URL")?;
+        let client = Client::open(database_url.clone()).context("Invalid Redis URL")?;
 
         Ok(Self {
             database_url,
             connection: OnceCell::new(),
+            transaction_connections: Arc::new(TransactionConnections {
+                client,
+                idle: Mutex::default(),
+            }),
         })
     }
 }
@@ -38,6 +57,7 @@ impl StoreManager for KeyValueRedis {
         Ok(Arc::new(RedisStore {
             connection: connection.clone(),
             database_url: self.database_url.clone(),
+            transaction_connections: self.transaction_connections.clone(),
         }))
     }
 
@@ -54,6 +74,40 @@ impl StoreManager for KeyValueRedis {
 struct RedisStore {
     connection: ConnectionManager,
     database_url: Url,
+    transaction_connections: Arc<TransactionConnections>,
+}
+
+/// Connections for transactions, each of which needs a connection to itself
+/// from `WATCH` to `EXEC`.
+///
+/// Unlike a [`ConnectionManager`], these connections don't reconnect, so a
+/// dropped connection fails the transaction rather than silently losing its
+/// watches. Connections are only returned for reuse once a transaction has
+/// finished with them cleanly.
+struct TransactionConnections {
+    client: Client,
+    idle: Mutex<Vec<MultiplexedConnection>>,
+}
+
+impl TransactionConnections {
+    async fn take(&self) -> Result<MultiplexedConnection, Error> {
+        let idle = self.idle.lock().unwrap().pop();
+        match idle {
+            Some(connection) => Ok(connection),
+            None => self
+                .client
+                .get_multiplexed_async_connection()
+                .await
+                .map_err(log_error),
+        }
+    }
+
+    fn release(&self, connection: MultiplexedConnection) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < MAX_IDLE_TRANSACTION_CONNECTIONS {
+            idle.push(connection);
+        }
+    }
 }
 
 struct CompareAndSwap {
@@ -72,6 +126,14 @@ impl Store for RedisStore {
         Ok(())
     }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            expiry: true,
+            transactions: true,
+            watch: true,
+        }
+    }
+
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
         self.connection.clone().get(key).await.map_err(log_error)
     }
@@ -84,6 +146,21 @@ impl Store for RedisStore {
             .map_err(log_error)
     }
 
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let millis = ttl.as_millis().try_into().map_err(log_error)?;
+        self.connection
+            .clone()
+            .pset_ex(key, value, millis)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        // PTTL returns -2 for missing keys and -1 for keys without an expiry
+        let millis: i64 = self.connection.clone().pttl(key).await.map_err(log_error)?;
+        Ok(u64::try_from(millis).ok().map(Duration::from_millis))
+    }
+
     async fn delete(&self, key: &str) -> Result<(), Error> {
         self.connection.clone().del(key).await.map_err(log_error)
     }
@@ -96,8 +173,48 @@ impl Store for RedisStore {
         self.connection.clone().keys("*").await.map_err(log_error)
     }
 
+    /// Lists keys with `SCAN`, whose cursors are Redis's own.
+    ///
+    /// As `limit` is passed as the `COUNT` hint, pages may contain more or fewer keys.
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let cursor = match cursor {
+            Some(cursor) => cursor
+                .parse::<u64>()
+                .map_err(|_| Error::Other(format!("invalid cursor {cursor:?}")))?,
+            None => 0,
+        };
+        let pattern = format!("{}*", escape_glob(prefix.unwrap_or_default()));
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(limit)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(log_error)?;
+        // A returned cursor of 0 marks the end of the iteration
+        let cursor = (next != 0).then(|| next.to_string());
+        Ok(KeyPage { keys, cursor })
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
-        self.connection.clone().keys(keys).await.map_err(log_error)
+        // MGET requires at least one key
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let values: Vec<Option<Vec<u8>>> = self
+            .connection
+            .clone()
+            .mget(&keys)
+            .await
+            .map_err(log_error)?;
+        Ok(keys.into_iter().zip(values).collect())
     }
 
     async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
@@ -140,6 +257,109 @@ impl Store for RedisStore {
             bucket_rep,
         }))
     }
+
+    /// `transact` applies the writes with `MULTI`/`EXEC` on a connection of its own, `WATCH`ing
+    /// the keys with conditions so that `EXEC` aborts if any of them change after being checked.
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let mut cx = self.transaction_connections.take().await?;
+        // On error the connection is dropped, along with any watches left on it
+        let applied = transact_on(&mut cx, &transaction).await?;
+        self.transaction_connections.release(cx);
+        Ok(applied)
+    }
+
+    /// `watch` subscribes to Redis keyspace notifications, which must be enabled on the server
+    /// for generic and string commands and for expired and evicted keys (e.g.
+    /// `notify-keyspace-events KA`). Changes are only observed while the subscription is open.
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        let client = Client::open(self.database_url.clone()).map_err(log_error)?;
+        let channel_prefix = format!("__keyspace@{}__:", client.get_connection_info().redis.db);
+        let mut pubsub = client.get_async_pubsub().await.map_err(log_error)?;
+        pubsub
+            .psubscribe(format!("{channel_prefix}{}*", escape_glob(prefix)))
+            .await
+            .map_err(log_error)?;
+
+        let changes = pubsub.into_on_message().filter_map(move |msg| {
+            let key = msg
+                .get_channel_name()
+                .strip_prefix(&channel_prefix)
+                .map(ToOwned::to_owned);
+            let kind = msg
+                .get_payload::<String>()
+                .ok()
+                .and_then(|event| change_kind(&event));
+            futures::future::ready(key.zip(kind).map(|(key, kind)| KeyChange { key, kind }))
+        });
+        Ok(changes.boxed())
+    }
+}
+
+/// Applies `transaction` on `cx`, which must not be used by anything else meanwhile.
+///
+/// Leaves no keys watched on `cx` if it succeeds.
+async fn transact_on(
+    cx: &mut MultiplexedConnection,
+    transaction: &Transaction,
+) -> Result<bool, Error> {
+    if !transaction.conditions.is_empty() {
+        let keys: Vec<&str> = transaction
+            .conditions
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        redis::cmd("WATCH")
+            .arg(&keys)
+            .exec_async(cx)
+            .await
+            .map_err(log_error)?;
+        for (key, expected) in &transaction.conditions {
+            let current: Option<Vec<u8>> = cx.get(key).await.map_err(log_error)?;
+            if current != *expected {
+                redis::cmd("UNWATCH")
+                    .exec_async(cx)
+                    .await
+                    .map_err(log_error)?;
+                return Ok(false);
+            }
+        }
+    }
+
+    let mut pipeline = redis::pipe();
+    pipeline.atomic();
+    for write in &transaction.writes {
+        match write {
+            Write::Set(key, value) => pipeline.set(key, value).ignore(),
+            Write::Delete(key) => pipeline.del(key).ignore(),
+        };
+    }
+    // `EXEC` replies with nil if a watched key was modified
+    let applied: Option<()> = pipeline.query_async(cx).await.map_err(log_error)?;
+    Ok(applied.is_some())
+}
+
+/// Maps the event of a keyspace notification to the change it makes to the key, ignoring events
+/// which neither write nor remove it.
+fn change_kind(event: &str) -> Option<KeyChangeKind> {
+    match event {
+        "set" | "incrby" | "incrbyfloat" | "append" | "setrange" | "rename_to" => {
+            Some(KeyChangeKind::Set)
+        }
+        "del" | "unlink" | "expired" | "evicted" | "rename_from" => Some(KeyChangeKind::Delete),
+        _ => None,
+    }
+}
+
+/// Escapes the characters that are special in Redis glob-style patterns.
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 #[async_trait]
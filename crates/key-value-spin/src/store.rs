@@ -1,14 +1,30 @@
 use anyhow::Result;
+use futures::StreamExt;
 use rusqlite::{named_params, Connection};
 use spin_core::async_trait;
-use spin_factor_key_value::{log_cas_error, log_error, Cas, Error, Store, StoreManager, SwapError};
+use spin_factor_key_value::{
+    log_cas_error, log_error, Capabilities, Cas, Error, KeyChange, KeyChangeKind, KeyChanges,
+    KeyPage, Store, StoreManager, SwapError, Transaction, Write,
+};
 use std::rc::Rc;
 use std::{
     path::PathBuf,
     sync::OnceLock,
     sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task,
 };
-use tokio::task;
+
+/// How often keys which have expired are deleted from the database.
+///
+/// Expired keys are filtered out of reads, so this only bounds how long they take up space.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The number of changes buffered for each watcher, beyond which a slow watcher misses changes.
+const WATCH_CAPACITY: usize = 1024;
 
 #[derive(Clone, Debug)]
 pub enum DatabaseLocation {
@@ -19,6 +35,58 @@ pub enum DatabaseLocation {
 pub struct KeyValueSqlite {
     location: DatabaseLocation,
     connection: OnceLock<Arc<Mutex<Connection>>>,
+    changes: Changes,
+}
+
+/// Broadcasts changes made through a [`KeyValueSqlite`] to its watchers.
+///
+/// Only changes made by this process are observed.
+#[derive(Clone)]
+struct Changes(broadcast::Sender<(String, KeyChange)>);
+
+impl Changes {
+    fn new() -> Self {
+        Self(broadcast::channel(WATCH_CAPACITY).0)
+    }
+
+    fn notify(&self, store: &str, key: &str, kind: KeyChangeKind) {
+        if self.0.receiver_count() == 0 {
+            return;
+        }
+        let change = KeyChange {
+            key: key.to_owned(),
+            kind,
+        };
+        // Sending only fails if there are no receivers
+        _ = self.0.send((store.to_owned(), change));
+    }
+
+    /// The changes to keys in `store` starting with `prefix`.
+    fn watch(&self, store: String, prefix: String) -> KeyChanges {
+        let receiver = self.0.subscribe();
+        futures::stream::unfold(
+            (receiver, store, prefix),
+            |(mut receiver, store, prefix)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok((changed, change))
+                            if changed == store && change.key.starts_with(&prefix) =>
+                        {
+                            return Some((change, (receiver, store, prefix)));
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Lagged(missed)) => {
+                            log_error(format!(
+                                "watcher of store {store:?} missed {missed} changes"
+                            ));
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )
+        .boxed()
+    }
 }
 
 impl KeyValueSqlite {
@@ -31,6 +99,7 @@ impl KeyValueSqlite {
         Self {
             location,
             connection: OnceLock::new(),
+            changes: Changes::new(),
         }
     }
 
@@ -47,6 +116,7 @@ impl KeyValueSqlite {
                            store TEXT NOT NULL,
                            key   TEXT NOT NULL,
                            value BLOB NOT NULL,
+                           expires_at INTEGER,
 
                            PRIMARY KEY (store, key)
                         )",
@@ -54,13 +124,108 @@ impl KeyValueSqlite {
             )
             .map_err(log_error)?;
 
+        // Databases created before expiry was supported lack the `expires_at` column
+        let has_expiry: bool = connection
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('spin_key_value') WHERE name='expires_at'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(log_error)?;
+        if !has_expiry {
+            connection
+                .execute(
+                    "ALTER TABLE spin_key_value ADD COLUMN expires_at INTEGER",
+                    [],
+                )
+                .map_err(log_error)?;
+        }
+        connection
+            .execute(
+                "CREATE INDEX IF NOT EXISTS spin_key_value_expires_at
+                     ON spin_key_value (expires_at) WHERE expires_at IS NOT NULL",
+                [],
+            )
+            .map_err(log_error)?;
+
         // the array module is needed for `rarray` usage in queries.
         rusqlite::vtab::array::load_module(&connection).map_err(log_error)?;
 
-        Ok(Arc::new(Mutex::new(connection)))
+        let connection = Arc::new(Mutex::new(connection));
+        spawn_sweeper(&connection, self.changes.clone());
+        Ok(connection)
     }
 }
 
+/// Periodically deletes expired keys for as long as the connection is in use.
+fn spawn_sweeper(connection: &Arc<Mutex<Connection>>, changes: Changes) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let connection = Arc::downgrade(connection);
+    runtime.spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(connection) = connection.upgrade() else {
+                return;
+            };
+            let changes = changes.clone();
+            let swept = task::spawn_blocking(move || {
+                let connection = connection.lock().unwrap();
+                let mut statement = connection.prepare_cached(
+                    "DELETE FROM spin_key_value WHERE expires_at <= $1 RETURNING store, key",
+                )?;
+                let swept = statement
+                    .query_map([now_millis()], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                for (store, key) in swept {
+                    changes.notify(&store, &key, KeyChangeKind::Delete);
+                }
+                rusqlite::Result::Ok(())
+            })
+            .await;
+            match swept {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    log_error(err);
+                }
+                Err(err) => {
+                    log_error(err);
+                }
+            }
+        }
+    });
+}
+
+/// The current time in milliseconds since the Unix epoch, as stored in `expires_at`
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(i64::MAX)
+}
+
+/// The least string greater than every string starting with `prefix`, if any,
+/// so that keys with the prefix can be found with a range scan of the index.
+///
+/// Keys compare by their UTF-8 bytes, which orders them as their code points.
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut upper = prefix.trim_end_matches(char::MAX).to_owned();
+    let last = upper.pop()?;
+    let next = match last {
+        // Skip the surrogate code points, which aren't chars
+        '\u{D7FF}' => '\u{E000}',
+        c => char::from_u32(c as u32 + 1)?,
+    };
+    upper.push(next);
+    Some(upper)
+}
+
 #[async_trait]
 impl StoreManager for KeyValueSqlite {
     async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
@@ -77,6 +242,7 @@ impl StoreManager for KeyValueSqlite {
         Ok(Arc::new(SqliteStore {
             name: name.to_owned(),
             connection: connection.clone(),
+            changes: self.changes.clone(),
         }))
     }
 
@@ -95,18 +261,57 @@ impl StoreManager for KeyValueSqlite {
 struct SqliteStore {
     name: String,
     connection: Arc<Mutex<Connection>>,
+    changes: Changes,
+}
+
+impl SqliteStore {
+    /// Sets the value for `key`, replacing any expiry with `expires_at` (in
+    /// milliseconds since the Unix epoch).
+    fn set_expiring(&self, key: &str, value: &[u8], expires_at: Option<i64>) -> Result<(), Error> {
+        task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "INSERT INTO spin_key_value (store, key, value, expires_at) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=$4",
+                )
+                .map_err(log_error)?
+                .execute(rusqlite::params![&self.name, key, value, expires_at])
+                .map_err(log_error)
+        })?;
+        self.notify(key, KeyChangeKind::Set);
+        Ok(())
+    }
+
+    fn notify(&self, key: &str, kind: KeyChangeKind) {
+        self.changes.notify(&self.name, key, kind);
+    }
 }
 
 #[async_trait]
 impl Store for SqliteStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            expiry: true,
+            transactions: true,
+            watch: true,
+        }
+    }
+
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
         task::block_in_place(|| {
             self.connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT value FROM spin_key_value WHERE store=$1 AND key=$2")
+                .prepare_cached(
+                    "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2
+                     AND (expires_at IS NULL OR expires_at > $3)",
+                )
                 .map_err(log_error)?
-                .query_map([&self.name, key], |row| row.get(0))
+                .query_map(rusqlite::params![&self.name, key, now_millis()], |row| {
+                    row.get(0)
+                })
                 .map_err(log_error)?
                 .next()
                 .transpose()
@@ -115,23 +320,38 @@ impl Store for SqliteStore {
     }
 
     async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
-        task::block_in_place(|| {
+        self.set_expiring(key, value, None)
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let ttl = i64::try_from(ttl.as_millis()).map_err(log_error)?;
+        self.set_expiring(key, value, Some(now_millis().saturating_add(ttl)))
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let now = now_millis();
+        let expires_at: Option<Option<i64>> = task::block_in_place(|| {
             self.connection
                 .lock()
                 .unwrap()
                 .prepare_cached(
-                    "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
-                     ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                    "SELECT expires_at FROM spin_key_value WHERE store=$1 AND key=$2
+                     AND (expires_at IS NULL OR expires_at > $3)",
                 )
                 .map_err(log_error)?
-                .execute(rusqlite::params![&self.name, key, value])
+                .query_map(rusqlite::params![&self.name, key, now], |row| row.get(0))
+                .map_err(log_error)?
+                .next()
+                .transpose()
                 .map_err(log_error)
-                .map(drop)
-        })
+        })?;
+        Ok(expires_at
+            .flatten()
+            .map(|expires_at| Duration::from_millis(expires_at.saturating_sub(now) as u64)))
     }
 
     async fn delete(&self, key: &str) -> Result<(), Error> {
-        task::block_in_place(|| {
+        let deleted = task::block_in_place(|| {
             self.connection
                 .lock()
                 .unwrap()
@@ -139,8 +359,11 @@ impl Store for SqliteStore {
                 .map_err(log_error)?
                 .execute([&self.name, key])
                 .map_err(log_error)
-                .map(drop)
-        })
+        })?;
+        if deleted > 0 {
+            self.notify(key, KeyChangeKind::Delete);
+        }
+        Ok(())
     }
 
     async fn exists(&self, key: &str) -> Result<bool, Error> {
@@ -152,15 +375,64 @@ impl Store for SqliteStore {
             self.connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT key FROM spin_key_value WHERE store=$1")
+                .prepare_cached(
+                    "SELECT key FROM spin_key_value WHERE store=$1
+                     AND (expires_at IS NULL OR expires_at > $2)",
+                )
                 .map_err(log_error)?
-                .query_map([&self.name], |row| row.get(0))
+                .query_map(rusqlite::params![&self.name, now_millis()], |row| {
+                    row.get(0)
+                })
                 .map_err(log_error)?
                 .map(|r| r.map_err(log_error))
                 .collect()
         })
     }
 
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        let mut keys: Vec<String> = task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "SELECT key FROM spin_key_value WHERE store=:name
+                     AND (:prefix IS NULL OR key >= :prefix)
+                     AND (:upper IS NULL OR key < :upper)
+                     AND (:cursor IS NULL OR key > :cursor)
+                     AND (expires_at IS NULL OR expires_at > :now)
+                     ORDER BY key LIMIT :limit",
+                )
+                .map_err(log_error)?
+                .query_map(
+                    named_params! {
+                        ":name": &self.name,
+                        ":prefix": prefix,
+                        ":upper": prefix.and_then(prefix_upper_bound),
+                        ":cursor": cursor,
+                        ":now": now_millis(),
+                        // Fetch an extra key to find out whether there's another page
+                        ":limit": i64::from(limit) + 1,
+                    },
+                    |row| row.get(0),
+                )
+                .map_err(log_error)?
+                .map(|r| r.map_err(log_error))
+                .collect::<Result<_, _>>()
+        })?;
+        let cursor = if keys.len() > limit as usize {
+            keys.truncate(limit as usize);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Ok(KeyPage { keys, cursor })
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
         task::block_in_place(|| {
             let sql_value_keys: Vec<rusqlite::types::Value> =
@@ -169,9 +441,10 @@ impl Store for SqliteStore {
             let row_iter: Vec<Result<(String, Option<Vec<u8>>), Error>> = self.connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT key, value FROM spin_key_value WHERE store=:name AND key IN rarray(:keys)")
+                .prepare_cached("SELECT key, value FROM spin_key_value WHERE store=:name AND key IN rarray(:keys)
+                                 AND (expires_at IS NULL OR expires_at > :now)")
                 .map_err(log_error)?
-                .query_map(named_params! {":name": &self.name, ":keys": ptr}, |row| {
+                .query_map(named_params! {":name": &self.name, ":keys": ptr, ":now": now_millis()}, |row| {
                     <(String, Option<Vec<u8>>)>::try_from(row)
                 })
                 .map_err(log_error)?
@@ -191,10 +464,10 @@ impl Store for SqliteStore {
         task::block_in_place(|| {
             let mut binding = self.connection.lock().unwrap();
             let tx = binding.transaction().map_err(log_error)?;
-            for kv in key_values {
+            for kv in &key_values {
                 tx.prepare_cached(
                     "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
-                     ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=NULL",
                 )
                 .map_err(log_error)?
                 .execute(rusqlite::params![&self.name, kv.0, kv.1])
@@ -202,11 +475,15 @@ impl Store for SqliteStore {
                 .map(drop)?;
             }
             tx.commit().map_err(log_error)
-        })
+        })?;
+        for (key, _) in &key_values {
+            self.notify(key, KeyChangeKind::Set);
+        }
+        Ok(())
     }
 
     async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
-        task::block_in_place(|| {
+        let deleted = task::block_in_place(|| {
             let sql_value_keys: Vec<rusqlite::types::Value> =
                 keys.into_iter().map(rusqlite::types::Value::from).collect();
             let ptr = Rc::new(sql_value_keys);
@@ -214,27 +491,39 @@ impl Store for SqliteStore {
                 .lock()
                 .unwrap()
                 .prepare_cached(
-                    "DELETE FROM spin_key_value WHERE store=:name AND key IN rarray(:keys)",
+                    "DELETE FROM spin_key_value WHERE store=:name AND key IN rarray(:keys)
+                     RETURNING key",
                 )
                 .map_err(log_error)?
-                .execute(named_params! {":name": &self.name, ":keys": ptr})
+                .query_map(named_params! {":name": &self.name, ":keys": ptr}, |row| {
+                    row.get::<_, String>(0)
+                })
+                .map_err(log_error)?
+                .collect::<rusqlite::Result<Vec<_>>>()
                 .map_err(log_error)
-                .map(drop)
-        })
+        })?;
+        for key in &deleted {
+            self.notify(key, KeyChangeKind::Delete);
+        }
+        Ok(())
     }
 
     // The assumption with increment is that if the value for the key does not exist, it will be
     // assumed to be zero. In the case that we are unable to unmarshal the value into an i64 an error will be returned.
     async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
-        task::block_in_place(|| {
+        let new_value = task::block_in_place(|| {
             let mut binding = self.connection.lock().unwrap();
 
             let tx = binding.transaction().map_err(log_error)?;
 
+            let now = now_millis();
             let value: Option<Vec<u8>> = tx
-                .prepare_cached("SELECT value FROM spin_key_value WHERE store=$1 AND key=$2")
+                .prepare_cached(
+                    "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2
+                     AND (expires_at IS NULL OR expires_at > $3)",
+                )
                 .map_err(log_error)?
-                .query_map([&self.name, &key], |row| row.get(0))
+                .query_map(rusqlite::params![&self.name, &key, now], |row| row.get(0))
                 .map_err(log_error)?
                 .next()
                 .transpose()
@@ -246,18 +535,27 @@ impl Store for SqliteStore {
             };
 
             let new_value = numeric + delta;
+            // Incrementing keeps the expiry of a live key, but not that of an expired one
             tx.prepare_cached(
                 "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
-                     ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3,
+                     expires_at=CASE WHEN expires_at > $4 THEN expires_at ELSE NULL END",
             )
             .map_err(log_error)?
-            .execute(rusqlite::params![&self.name, key, new_value.to_le_bytes()])
+            .execute(rusqlite::params![
+                &self.name,
+                &key,
+                new_value.to_le_bytes(),
+                now
+            ])
             .map_err(log_error)
             .map(drop)?;
 
             tx.commit().map_err(log_error)?;
             Ok(new_value)
-        })
+        })?;
+        self.notify(&key, KeyChangeKind::Set);
+        Ok(new_value)
     }
 
     async fn new_compare_and_swap(
@@ -269,10 +567,71 @@ impl Store for SqliteStore {
             name: self.name.clone(),
             key: key.to_string(),
             connection: self.connection.clone(),
+            changes: self.changes.clone(),
             value: Mutex::new(None),
             bucket_rep,
         }))
     }
+
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let applied = task::block_in_place(|| {
+            let mut binding = self.connection.lock().unwrap();
+            let tx = binding.transaction().map_err(log_error)?;
+            let now = now_millis();
+
+            for (key, expected) in transaction.conditions {
+                let current: Option<Vec<u8>> = tx
+                    .prepare_cached(
+                        "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2
+                         AND (expires_at IS NULL OR expires_at > $3)",
+                    )
+                    .map_err(log_error)?
+                    .query_map(rusqlite::params![&self.name, key, now], |row| row.get(0))
+                    .map_err(log_error)?
+                    .next()
+                    .transpose()
+                    .map_err(log_error)?;
+                if current != expected {
+                    // Dropping the transaction rolls it back
+                    return Ok(false);
+                }
+            }
+
+            for write in &transaction.writes {
+                match write {
+                    Write::Set(key, value) => tx
+                        .prepare_cached(
+                            "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
+                             ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=NULL",
+                        )
+                        .map_err(log_error)?
+                        .execute(rusqlite::params![&self.name, key, value]),
+                    Write::Delete(key) => tx
+                        .prepare_cached("DELETE FROM spin_key_value WHERE store=$1 AND key=$2")
+                        .map_err(log_error)?
+                        .execute(rusqlite::params![&self.name, key]),
+                }
+                .map_err(log_error)?;
+            }
+
+            tx.commit().map_err(log_error)?;
+            Ok(true)
+        })?;
+        if applied {
+            for write in &transaction.writes {
+                let kind = match write {
+                    Write::Set(..) => KeyChangeKind::Set,
+                    Write::Delete(_) => KeyChangeKind::Delete,
+                };
+                self.notify(write.key(), kind);
+            }
+        }
+        Ok(applied)
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        Ok(self.changes.watch(self.name.clone(), prefix.to_owned()))
+    }
 }
 
 struct CompareAndSwap {
@@ -280,6 +639,7 @@ struct CompareAndSwap {
     key: String,
     value: Mutex<Option<Vec<u8>>>,
     connection: Arc<Mutex<Connection>>,
+    changes: Changes,
     bucket_rep: u32,
 }
 
@@ -291,9 +651,15 @@ impl Cas for CompareAndSwap {
                 .connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT value FROM spin_key_value WHERE store=$1 AND key=$2")
+                .prepare_cached(
+                    "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2
+                     AND (expires_at IS NULL OR expires_at > $3)",
+                )
                 .map_err(log_error)?
-                .query_map([&self.name, &self.key], |row| row.get(0))
+                .query_map(
+                    rusqlite::params![&self.name, &self.key, now_millis()],
+                    |row| row.get(0),
+                )
                 .map_err(log_error)?
                 .next()
                 .transpose()
@@ -312,13 +678,15 @@ impl Cas for CompareAndSwap {
                 Some(old_val) => {
                     conn
                         .prepare_cached(
-                             "UPDATE spin_key_value SET value=:new_value WHERE store=:name and key=:key and value=:old_value")
+                             "UPDATE spin_key_value SET value=:new_value, expires_at=NULL WHERE store=:name and key=:key and value=:old_value
+                              AND (expires_at IS NULL OR expires_at > :now)")
                         .map_err(log_cas_error)?
                         .execute(named_params! {
                             ":name": &self.name,
                             ":key": self.key,
                             ":old_value": old_val,
                             ":new_value": value,
+                            ":now": now_millis(),
                         })
                         .map_err(log_cas_error)?
                 }
@@ -327,7 +695,7 @@ impl Cas for CompareAndSwap {
                     let rows = tx
                         .prepare_cached(
                             "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
-                     ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=NULL",
                         )
                         .map_err(log_cas_error)?
                         .execute(rusqlite::params![&self.name, self.key, value])
@@ -339,6 +707,8 @@ impl Cas for CompareAndSwap {
 
             // We expect only 1 row to be updated. If 0, we know that the underlying value has changed.
             if rows_changed == 1 {
+                self.changes
+                    .notify(&self.name, &self.key, KeyChangeKind::Set);
                 Ok(())
             } else {
                 Err(SwapError::CasFailed("failed to update 1 row".to_owned()))
@@ -498,6 +868,242 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn ttl() -> Result<()> {
+        use spin_world::spin::key_value::key_value as v3;
+
+        let mut kv = KeyValueDispatch::new(
+            ["default".to_owned()].into_iter().collect(),
+            Arc::new(DelegatingStoreManager::new([(
+                "default".to_owned(),
+                Arc::new(KeyValueSqlite::new(DatabaseLocation::InMemory)) as _,
+            )])),
+        );
+        let store: Resource<v3::Store> =
+            v3::HostStore::open(&mut kv, "default".to_owned()).await??;
+        let rep = store.rep();
+
+        v3::HostStore::set_with_ttl(
+            &mut kv,
+            Resource::new_own(rep),
+            "short".to_owned(),
+            b"a".to_vec(),
+            1,
+        )
+        .await??;
+        v3::HostStore::set_with_ttl(
+            &mut kv,
+            Resource::new_own(rep),
+            "long".to_owned(),
+            b"b".to_vec(),
+            60_000,
+        )
+        .await??;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(
+            None,
+            v3::HostStore::get(&mut kv, Resource::new_own(rep), "short".to_owned()).await??
+        );
+        assert!(
+            !v3::HostStore::exists(&mut kv, Resource::new_own(rep), "short".to_owned()).await??
+        );
+        assert_eq!(
+            &["long".to_owned()] as &[_],
+            &v3::HostStore::get_keys(&mut kv, Resource::new_own(rep)).await??
+        );
+        assert_eq!(
+            None,
+            v3::HostStore::get_ttl(&mut kv, Resource::new_own(rep), "short".to_owned()).await??
+        );
+
+        let remaining = v3::HostStore::get_ttl(&mut kv, Resource::new_own(rep), "long".to_owned())
+            .await??
+            .expect("key should expire");
+        assert!(
+            remaining > 0 && remaining <= 60_000,
+            "remaining: {remaining}"
+        );
+
+        // Setting a key without a TTL removes its expiry
+        v3::HostStore::set(
+            &mut kv,
+            Resource::new_own(rep),
+            "long".to_owned(),
+            b"c".to_vec(),
+        )
+        .await??;
+        assert_eq!(
+            None,
+            v3::HostStore::get_ttl(&mut kv, Resource::new_own(rep), "long".to_owned()).await??
+        );
+
+        // Expired keys don't contribute to increments
+        v3::HostStore::set_with_ttl(
+            &mut kv,
+            Resource::new_own(rep),
+            "counter".to_owned(),
+            5i64.to_le_bytes().to_vec(),
+            1,
+        )
+        .await??;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(kv_incr(&mut kv, rep, 1).await, 1);
+        assert_eq!(
+            None,
+            v3::HostStore::get_ttl(&mut kv, Resource::new_own(rep), "counter".to_owned()).await??
+        );
+
+        assert!(matches!(
+            v3::HostStore::set_with_ttl(
+                &mut kv,
+                Resource::new_own(rep),
+                "zero".to_owned(),
+                b"d".to_vec(),
+                0
+            )
+            .await?,
+            Err(v3::Error::Other(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn list_keys() -> Result<()> {
+        let store = KeyValueSqlite::new(DatabaseLocation::InMemory)
+            .get("default")
+            .await?;
+        for key in ["a1", "a2", "a3", "b1", "a%"] {
+            store.set(key, b"value").await?;
+        }
+
+        let page = store.list_keys(Some("a"), None, 2).await?;
+        assert_eq!(page.keys, ["a%", "a1"]);
+        let page = store
+            .list_keys(Some("a"), page.cursor.as_deref(), 2)
+            .await?;
+        assert_eq!(page.keys, ["a2", "a3"]);
+        // The next page would be empty, which the extra fetched row detects
+        assert_eq!(page.cursor, None);
+
+        let page = store.list_keys(None, None, 10).await?;
+        assert_eq!(page.keys.len(), 5);
+        assert_eq!(page.cursor, None);
+
+        let page = store.list_keys(Some("a%"), None, 10).await?;
+        assert_eq!(page.keys, ["a%"]);
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_upper_bounds() {
+        assert_eq!(prefix_upper_bound("ab").as_deref(), Some("ac"));
+        assert_eq!(
+            prefix_upper_bound("a\u{D7FF}").as_deref(),
+            Some("a\u{E000}")
+        );
+        assert_eq!(prefix_upper_bound("a\u{10FFFF}").as_deref(), Some("b"));
+        assert_eq!(prefix_upper_bound("\u{10FFFF}"), None);
+        assert_eq!(prefix_upper_bound(""), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn transactions() -> Result<()> {
+        use spin_world::spin::key_value::key_value as v3;
+
+        let mut kv = KeyValueDispatch::new(
+            ["default".to_owned()].into_iter().collect(),
+            Arc::new(DelegatingStoreManager::new([(
+                "default".to_owned(),
+                Arc::new(KeyValueSqlite::new(DatabaseLocation::InMemory)) as _,
+            )])),
+        );
+        let store: Resource<v3::Store> =
+            v3::HostStore::open(&mut kv, "default".to_owned()).await??;
+        let rep = store.rep();
+        assert!(v3::HostStore::capabilities(&mut kv, Resource::new_own(rep))
+            .await?
+            .contains(v3::Capabilities::TRANSACTIONS));
+        v3::HostStore::set(
+            &mut kv,
+            Resource::new_own(rep),
+            "a".to_owned(),
+            b"1".to_vec(),
+        )
+        .await??;
+
+        // A failed expectation leaves the store untouched
+        let tx = v3::HostTransaction::new(&mut kv, Resource::new_own(rep)).await?;
+        v3::HostTransaction::expect(&mut kv, Resource::new_own(tx.rep()), "a".to_owned(), None)
+            .await?;
+        v3::HostTransaction::set(
+            &mut kv,
+            Resource::new_own(tx.rep()),
+            "b".to_owned(),
+            b"2".to_vec(),
+        )
+        .await?;
+        assert!(!v3::HostTransaction::commit(&mut kv, Resource::new_own(tx.rep())).await??);
+        assert!(!v3::HostStore::exists(&mut kv, Resource::new_own(rep), "b".to_owned()).await??);
+
+        let tx = v3::HostTransaction::new(&mut kv, Resource::new_own(rep)).await?;
+        v3::HostTransaction::expect(
+            &mut kv,
+            Resource::new_own(tx.rep()),
+            "a".to_owned(),
+            Some(b"1".to_vec()),
+        )
+        .await?;
+        v3::HostTransaction::expect(&mut kv, Resource::new_own(tx.rep()), "b".to_owned(), None)
+            .await?;
+        v3::HostTransaction::set(
+            &mut kv,
+            Resource::new_own(tx.rep()),
+            "b".to_owned(),
+            b"2".to_vec(),
+        )
+        .await?;
+        v3::HostTransaction::delete(&mut kv, Resource::new_own(tx.rep()), "a".to_owned()).await?;
+        assert!(v3::HostTransaction::commit(&mut kv, Resource::new_own(tx.rep())).await??);
+        assert_eq!(
+            &["b".to_owned()] as &[_],
+            &v3::HostStore::get_keys(&mut kv, Resource::new_own(rep)).await??
+        );
+        v3::HostTransaction::drop(&mut kv, tx).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn watch() -> Result<()> {
+        let kv = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = kv.get("default").await?;
+        let other = kv.get("other").await?;
+        assert!(store.capabilities().watch);
+        let mut changes = store.watch("a").await?;
+
+        store.set("a1", b"value").await?;
+        store.set("b1", b"value").await?;
+        other.set("a2", b"value").await?;
+        store.delete("missing").await?;
+        store.increment("a3".to_owned(), 1).await?;
+        store
+            .delete_many(vec!["a1".to_owned(), "b1".to_owned()])
+            .await?;
+
+        let expected = [
+            ("a1", KeyChangeKind::Set),
+            ("a3", KeyChangeKind::Set),
+            ("a1", KeyChangeKind::Delete),
+        ];
+        for (key, kind) in expected {
+            let change = changes.next().await.unwrap();
+            assert_eq!((change.key.as_str(), change.kind), (key, kind));
+        }
+        Ok(())
+    }
+
     async fn cas_failed(kv: &mut KeyValueDispatch, rep: u32) -> Result<()> {
         let cas_key = "fail".to_owned();
         let cas_orig_value = b"baz".to_vec();
@@ -0,0 +1,70 @@
+mod store;
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_factor_key_value::runtime_config::spin::{
+    MakeKeyValueStore, RuntimeConfigResolver, StoreConfig,
+};
+pub use store::KeyValueTiered;
+
+/// The number of values cached per store if not configured.
+const DEFAULT_CAPACITY: NonZeroUsize = NonZeroUsize::new(1024).unwrap();
+
+/// A key-value store that caches the values of another store in process memory.
+///
+/// Reads are served from a bounded LRU cache where possible, and writes are
+/// applied to the backing store before updating the cache.
+pub struct TieredKeyValueStore {
+    /// Resolves the configuration of the backing store.
+    resolver: RuntimeConfigResolver,
+}
+
+impl TieredKeyValueStore {
+    /// Creates a new `TieredKeyValueStore` whose backing stores may be of any
+    /// type registered with `resolver`.
+    pub fn new(resolver: RuntimeConfigResolver) -> Self {
+        Self { resolver }
+    }
+}
+
+/// Runtime configuration for the tiered key-value store.
+#[derive(Deserialize)]
+pub struct TieredKeyValueRuntimeConfig {
+    /// The maximum number of values cached per store.
+    capacity: Option<NonZeroUsize>,
+    /// How long a cached value may be used before it is read from the backing
+    /// store again. If not set, values are cached until evicted or until their
+    /// keys expire in the backing store.
+    ///
+    /// This bounds how long changes made other than through this store, and
+    /// the expiry of keys in the backing store, may go unnoticed. Reads then
+    /// don't look up the expiry of keys, saving a request to the backing store.
+    max_age_ms: Option<u64>,
+    /// The configuration of the backing store, including its `type`.
+    backing: StoreConfig,
+}
+
+impl MakeKeyValueStore for TieredKeyValueStore {
+    const RUNTIME_CONFIG_TYPE: &'static str = "tiered";
+
+    type RuntimeConfig = TieredKeyValueRuntimeConfig;
+
+    type StoreManager = KeyValueTiered;
+
+    fn make_store(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::StoreManager> {
+        let backing = self
+            .resolver
+            .store_manager_from_config(runtime_config.backing)
+            .context("could not make the backing store of a tiered key-value store")?;
+        Ok(KeyValueTiered::new(
+            backing,
+            runtime_config.capacity.unwrap_or(DEFAULT_CAPACITY),
+            runtime_config.max_age_ms.map(Duration::from_millis),
+        ))
+    }
+}
@@ -0,0 +1,475 @@
+use futures::{future::try_join_all, try_join};
+use lru::LruCache;
+use spin_core::async_trait;
+use spin_factor_key_value::{
+    Capabilities, Cas, Error, KeyChanges, KeyPage, Store, StoreManager, SwapError, Transaction,
+};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A [`StoreManager`] which caches the values of the stores of another manager.
+pub struct KeyValueTiered {
+    backing: Arc<dyn StoreManager>,
+    capacity: NonZeroUsize,
+    max_age: Option<Duration>,
+    /// The cache for each label, shared by every store opened with that label.
+    caches: Mutex<HashMap<String, Arc<Mutex<Cache>>>>,
+}
+
+impl KeyValueTiered {
+    pub fn new(
+        backing: Arc<dyn StoreManager>,
+        capacity: NonZeroUsize,
+        max_age: Option<Duration>,
+    ) -> Self {
+        Self {
+            backing,
+            capacity,
+            max_age,
+            caches: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValueTiered {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let backing = self.backing.get(name).await?;
+        let cache = self
+            .caches
+            .lock()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(Cache::new(self.capacity))))
+            .clone();
+        Ok(Arc::new(TieredStore {
+            backing,
+            cache,
+            max_age: self.max_age,
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.backing.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        let backing = self
+            .backing
+            .summary(store_name)
+            .unwrap_or_else(|| "another store".into());
+        Some(format!(
+            "a cache of up to {} values in front of {backing}",
+            self.capacity
+        ))
+    }
+}
+
+/// The cached values of a store, including `None` for keys known not to exist.
+struct Cache {
+    values: LruCache<String, CachedValue>,
+    /// Incremented by every write, so that reads which raced with a write don't
+    /// cache the value from before the write.
+    generation: u64,
+}
+
+struct CachedValue {
+    value: Option<Vec<u8>>,
+    cached_at: Instant,
+    /// When the key expires in the backing store, if it does.
+    expires_at: Option<Instant>,
+}
+
+impl CachedValue {
+    fn is_stale(&self, max_age: Option<Duration>) -> bool {
+        max_age.is_some_and(|max_age| self.cached_at.elapsed() >= max_age)
+            || self
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= Instant::now())
+    }
+}
+
+impl Cache {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            values: LruCache::new(capacity),
+            generation: 0,
+        }
+    }
+}
+
+struct TieredStore {
+    backing: Arc<dyn Store>,
+    cache: Arc<Mutex<Cache>>,
+    max_age: Option<Duration>,
+}
+
+impl TieredStore {
+    /// Returns the cached value of `key`, or `None` if it isn't cached.
+    fn cached(&self, key: &str) -> Option<Option<Vec<u8>>> {
+        let mut cache = self.cache.lock().unwrap();
+        let cached = cache.values.get(key)?;
+        if cached.is_stale(self.max_age) {
+            cache.values.pop(key);
+            return None;
+        }
+        Some(cached.value.clone())
+    }
+
+    /// Returns the current generation of the cache, to be passed to [`Self::fill`].
+    fn generation(&self) -> u64 {
+        self.cache.lock().unwrap().generation
+    }
+
+    /// Returns when `key` expires in the backing store, if it does.
+    ///
+    /// Expiries are only looked up if the backing store supports them and cached values have
+    /// no maximum age, which already bounds how long an expired key's value may be used.
+    async fn expiry(&self, key: &str) -> Result<Option<Instant>, Error> {
+        if self.max_age.is_some() || !self.backing.capabilities().expiry {
+            return Ok(None);
+        }
+        let now = Instant::now();
+        let ttl = self.backing.get_ttl(key).await?;
+        Ok(ttl.and_then(|ttl| now.checked_add(ttl)))
+    }
+
+    /// Caches values read from the backing store, along with when they expire, unless there
+    /// have been any writes since the given generation.
+    fn fill(
+        &self,
+        generation: u64,
+        values: impl IntoIterator<Item = (String, Option<Vec<u8>>, Option<Instant>)>,
+    ) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation != generation {
+            return;
+        }
+        let cached_at = Instant::now();
+        for (key, value, expires_at) in values {
+            cache.values.put(
+                key,
+                CachedValue {
+                    value,
+                    cached_at,
+                    expires_at,
+                },
+            );
+        }
+    }
+
+    /// Records writes which have been applied to the backing store, caching the new values of
+    /// the keys or, for `None`, removing the keys from the cache.
+    fn written(&self, writes: impl IntoIterator<Item = (String, Option<Option<Vec<u8>>>)>) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        let cached_at = Instant::now();
+        for (key, value) in writes {
+            match value {
+                Some(value) => {
+                    let cached = CachedValue {
+                        value,
+                        cached_at,
+                        expires_at: None,
+                    };
+                    cache.values.put(key, cached);
+                }
+                None => {
+                    cache.values.pop(&key);
+                }
+            }
+        }
+    }
+}
+
+/// `TieredStore` reads through its cache and writes through to its backing store.
+///
+/// Writes which the cache can't model exactly (those with a TTL, increments, compare-and-swaps
+/// and transactions) remove the keys they write from the cache instead. Unless cached values
+/// have a maximum age, values read from a backing store which supports expiry are cached with
+/// the key's expiry, after which they're read again. The expiries are looked up concurrently
+/// with the values.
+#[async_trait]
+impl Store for TieredStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.backing.after_open().await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.backing.capabilities()
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        if let Some(value) = self.cached(key) {
+            return Ok(value);
+        }
+        let generation = self.generation();
+        let (expires_at, value) = try_join!(self.expiry(key), self.backing.get(key))?;
+        self.fill(generation, [(key.to_owned(), value.clone(), expires_at)]);
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let result = self.backing.set(key, value).await;
+        let cached = result.is_ok().then(|| Some(value.to_owned()));
+        self.written([(key.to_owned(), cached)]);
+        result
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        let result = self.backing.set_with_ttl(key, value, ttl).await;
+        self.written([(key.to_owned(), None)]);
+        result
+    }
+
+    async fn get_ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        self.backing.get_ttl(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let result = self.backing.delete(key).await;
+        let cached = result.is_ok().then_some(None);
+        self.written([(key.to_owned(), cached)]);
+        result
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        match self.cached(key) {
+            Some(value) => Ok(value.is_some()),
+            None => self.backing.exists(key).await,
+        }
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.backing.get_keys().await
+    }
+
+    async fn list_keys(
+        &self,
+        prefix: Option<&str>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        self.backing.list_keys(prefix, cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let mut cached = HashMap::new();
+        let mut missing = Vec::new();
+        for key in &keys {
+            match self.cached(key) {
+                Some(value) => {
+                    cached.insert(key.clone(), value);
+                }
+                None => missing.push(key.clone()),
+            }
+        }
+        if !missing.is_empty() {
+            let generation = self.generation();
+            let expiries = try_join_all(missing.iter().map(|key| self.expiry(key)));
+            let (expiries, fetched) = try_join!(expiries, self.backing.get_many(missing.clone()))?;
+            let expiries = missing.into_iter().zip(expiries).collect::<HashMap<_, _>>();
+            self.fill(
+                generation,
+                fetched.iter().map(|(key, value)| {
+                    let expires_at = expiries.get(key).copied().flatten();
+                    (key.clone(), value.clone(), expires_at)
+                }),
+            );
+            cached.extend(fetched);
+        }
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = cached.get(&key).cloned().flatten();
+                (key, value)
+            })
+            .collect())
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let result = self.backing.set_many(key_values.clone()).await;
+        let succeeded = result.is_ok();
+        self.written(
+            key_values
+                .into_iter()
+                .map(|(key, value)| (key, succeeded.then_some(Some(value)))),
+        );
+        result
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let result = self.backing.delete_many(keys.clone()).await;
+        let cached = result.is_ok().then_some(None);
+        self.written(keys.into_iter().map(|key| (key, cached.clone())));
+        result
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let result = self.backing.increment(key.clone(), delta).await;
+        // Backends represent counters differently, so the new value can't be cached
+        self.written([(key, None)]);
+        result
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = self.backing.new_compare_and_swap(bucket_rep, key).await?;
+        Ok(Arc::new(CompareAndSwap {
+            inner,
+            key: key.to_owned(),
+            cache: self.cache.clone(),
+        }))
+    }
+
+    async fn transact(&self, transaction: Transaction) -> Result<bool, Error> {
+        let keys = transaction
+            .writes
+            .iter()
+            .map(|write| (write.key().to_owned(), None))
+            .collect::<Vec<_>>();
+        let result = self.backing.transact(transaction).await;
+        self.written(keys);
+        result
+    }
+
+    async fn watch(&self, prefix: &str) -> Result<KeyChanges, Error> {
+        self.backing.watch(prefix).await
+    }
+}
+
+/// A compare-and-swap on the backing store, which removes the key from the cache when swapped.
+struct CompareAndSwap {
+    inner: Arc<dyn Cas>,
+    key: String,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[async_trait]
+impl Cas for CompareAndSwap {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.current().await
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let result = self.inner.swap(value).await;
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        cache.values.pop(&self.key);
+        result
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use spin_key_value_memory::KeyValueMemory;
+
+    struct Stores {
+        backing: Arc<dyn Store>,
+        tiered: Arc<dyn Store>,
+    }
+
+    async fn stores(capacity: usize, max_age: Option<Duration>) -> Result<Stores, Error> {
+        let backing = Arc::new(KeyValueMemory::new());
+        let tiered = KeyValueTiered::new(
+            backing.clone(),
+            NonZeroUsize::new(capacity).unwrap(),
+            max_age,
+        );
+        Ok(Stores {
+            backing: backing.get("default").await?,
+            tiered: tiered.get("default").await?,
+        })
+    }
+
+    #[tokio::test]
+    async fn reads_through_and_writes_through() -> Result<(), Error> {
+        let Stores { backing, tiered } = stores(10, None).await?;
+        backing.set("a", b"1").await?;
+        assert_eq!(tiered.get("a").await?, Some(b"1".to_vec()));
+        assert_eq!(tiered.get("missing").await?, None);
+
+        // Changes made behind the cache's back aren't seen...
+        backing.set("a", b"2").await?;
+        backing.set("missing", b"2").await?;
+        assert_eq!(tiered.get("a").await?, Some(b"1".to_vec()));
+        assert!(!tiered.exists("missing").await?);
+
+        // ...but changes made through it are
+        tiered.set("a", b"3").await?;
+        assert_eq!(backing.get("a").await?, Some(b"3".to_vec()));
+        tiered.delete("missing").await?;
+        assert_eq!(backing.get("missing").await?, None);
+        assert_eq!(
+            tiered
+                .get_many(vec!["a".to_owned(), "missing".to_owned()])
+                .await?,
+            [
+                ("a".to_owned(), Some(b"3".to_vec())),
+                ("missing".to_owned(), None)
+            ]
+        );
+
+        assert_eq!(tiered.increment("n".to_owned(), 2).await?, 2);
+        assert_eq!(tiered.get("n").await?, Some(2i64.to_le_bytes().to_vec()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() -> Result<(), Error> {
+        let Stores { backing, tiered } = stores(1, None).await?;
+        tiered.set("a", b"1").await?;
+        tiered.set("b", b"1").await?;
+        backing.set("a", b"2").await?;
+        backing.set("b", b"2").await?;
+        // "a" was evicted by "b", so is read from the backing store
+        assert_eq!(tiered.get("a").await?, Some(b"2".to_vec()));
+        assert_eq!(tiered.get("b").await?, Some(b"2".to_vec()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expires_values_with_the_backing_store() -> Result<(), Error> {
+        let Stores { backing, tiered } = stores(10, None).await?;
+        backing
+            .set_with_ttl("a", b"1", Duration::from_millis(50))
+            .await?;
+        tiered
+            .set_with_ttl("b", b"1", Duration::from_millis(50))
+            .await?;
+        assert_eq!(tiered.get("a").await?, Some(b"1".to_vec()));
+        assert_eq!(tiered.get("b").await?, Some(b"1".to_vec()));
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(tiered.get("a").await?, None);
+        assert_eq!(
+            tiered.get_many(vec!["b".to_owned()]).await?,
+            [("b".to_owned(), None)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expires_cached_values() -> Result<(), Error> {
+        let Stores { backing, tiered } = stores(10, Some(Duration::ZERO)).await?;
+        tiered.set("a", b"1").await?;
+        backing.set("a", b"2").await?;
+        assert_eq!(tiered.get("a").await?, Some(b"2".to_vec()));
+        Ok(())
+    }
+}
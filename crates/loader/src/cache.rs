@@ -1,4 +1,4 @@
-//! Cache for OCI registry entities.
+//! Cache for OCI registry entities and pre-initialized components.
 
 use anyhow::{ensure, Context, Result};
 
@@ -14,6 +14,7 @@ const REGISTRY_CACHE_DIR: &str = "registry";
 const MANIFESTS_DIR: &str = "manifests";
 const WASM_DIR: &str = "wasm";
 const DATA_DIR: &str = "data";
+const PREINIT_DIR: &str = "preinit";
 
 /// Cache for registry entities.
 #[derive(Debug)]
@@ -57,6 +58,11 @@ impl Cache {
         self.root.join(DATA_DIR)
     }
 
+    /// The pre-initialized components directory for the current cache.
+    fn preinit_dir(&self) -> PathBuf {
+        self.root.join(PREINIT_DIR)
+    }
+
     /// Return the path to a wasm file given its digest.
     pub fn wasm_file(&self, digest: impl AsRef<str>) -> Result<PathBuf> {
         // Check the expected wasm directory first; else check the data directory as a fallback.
@@ -100,6 +106,18 @@ impl Cache {
         Ok(())
     }
 
+    /// Write a pre-initialized component given the digest of the component
+    /// it was produced from.
+    pub async fn write_preinit(
+        &self,
+        bytes: impl AsRef<[u8]>,
+        digest: impl AsRef<str>,
+    ) -> Result<()> {
+        self.ensure_dirs().await?;
+        write_file(&self.preinit_path(digest), bytes.as_ref()).await?;
+        Ok(())
+    }
+
     /// The path of contents in the cache's wasm directory, which may or may not exist.
     pub fn wasm_path(&self, digest: impl AsRef<str>) -> PathBuf {
         self.wasm_dir().join(safe_name(digest).as_ref())
@@ -110,6 +128,12 @@ impl Cache {
         self.data_dir().join(safe_name(digest).as_ref())
     }
 
+    /// The path of the pre-initialized component produced from the component
+    /// with the given digest, which may or may not exist.
+    pub fn preinit_path(&self, digest: impl AsRef<str>) -> PathBuf {
+        self.preinit_dir().join(safe_name(digest).as_ref())
+    }
+
     /// Ensure the expected configuration directories are found in the root.
     ///
     /// ```text
@@ -118,6 +142,7 @@ impl Cache {
     ///             └──manifests
     ///             └──wasm
     ///             └──data
+    ///             └──preinit
     /// ```
     pub async fn ensure_dirs(&self) -> Result<()> {
         tracing::debug!("using cache root directory {}", self.root.display());
@@ -151,6 +176,13 @@ impl Cache {
                 .with_context(|| format!("failed to create assets directory `{}`", p.display()))?;
         }
 
+        let p = root.join(PREINIT_DIR);
+        if !p.is_dir() {
+            create_dir_all(&p)
+                .await
+                .with_context(|| format!("failed to create preinit directory `{}`", p.display()))?;
+        }
+
         self.dirs_ensured_once.store(true, Ordering::Relaxed);
 
         Ok(())
@@ -188,6 +220,10 @@ mod test {
         cache.write_data(data, &digest).await?;
         assert_eq!(data, std::fs::read(cache.data_path(&digest))?);
 
+        let preinit = "Preinit".as_bytes();
+        cache.write_preinit(preinit, &digest).await?;
+        assert_eq!(preinit, std::fs::read(cache.preinit_path(&digest))?);
+
         Ok(())
     }
 }
@@ -155,14 +155,47 @@ impl LocalLoader {
 
         let component_requires_service_chaining = requires_service_chaining(&component);
 
-        let metadata = ValuesMapBuilder::new()
+        let limits = locked_limits(&component.limits)
+            .with_context(|| format!("Component {id} has invalid `limits`"))?;
+
+        let sqlite_migrations = match &component.sqlite_migrations {
+            Some(dir) => {
+                ensure!(
+                    !component.sqlite_databases.is_empty(),
+                    "Component {id} has `sqlite_migrations` but no `sqlite_databases` to apply them to"
+                );
+                let migrations = read_sqlite_migrations(&self.app_root.join(dir))
+                    .with_context(|| format!("Component {id} has invalid `sqlite_migrations`"))?;
+                Some(migrations)
+            }
+            None => None,
+        };
+
+        let mut metadata = ValuesMapBuilder::new();
+        metadata
             .string("description", component.description)
             .string_array("allowed_outbound_hosts", allowed_outbound_hosts)
             .string_array("key_value_stores", component.key_value_stores)
             .string_array("databases", component.sqlite_databases)
             .string_array("ai_models", component.ai_models)
-            .serializable("build", component.build)?
-            .take();
+            .serializable("build", component.build)?;
+        if !limits.is_empty() {
+            metadata.serializable("limits", limits)?;
+        }
+        if let Some(migrations) = sqlite_migrations {
+            metadata.serializable("sqlite_migrations", migrations)?;
+        }
+        if let Some(instance_reuse) = component.instance_reuse {
+            ensure!(
+                instance_reuse > 0,
+                "Component {id} has invalid `instance_reuse`: must be greater than zero"
+            );
+            metadata.entry("instance_reuse", instance_reuse);
+        }
+        if component.preinit {
+            metadata.entry("preinit", true);
+        }
+        let metadata = metadata.build();
 
         let source = self
             .load_component_source(id, component.source.clone())
@@ -576,6 +609,39 @@ fn safe_canonicalize(path: &Path) -> std::io::Result<PathBuf> {
     Ok(path.absolutize()?.into_owned())
 }
 
+fn locked_limits(limits: &v2::ComponentLimits) -> Result<locked::ComponentLimits> {
+    let cpu_time_ms = limits
+        .cpu_time()?
+        .map(|cpu_time| {
+            ensure!(!cpu_time.is_zero(), "`cpu_time` must be greater than zero");
+            Ok(cpu_time.as_millis().try_into().unwrap_or(u64::MAX))
+        })
+        .transpose()?;
+    let memory = limits.memory()?;
+    ensure!(memory != Some(0), "`memory` must be greater than zero");
+    ensure!(
+        limits.concurrency != Some(0),
+        "`concurrency` must be greater than zero"
+    );
+    ensure!(
+        limits.concurrency.is_some() || (limits.queue.is_none() && limits.queue_timeout.is_none()),
+        "`queue` and `queue_timeout` require `concurrency` to be set"
+    );
+    let queue_timeout_ms = limits
+        .queue_timeout()?
+        .map(|queue_timeout| queue_timeout.as_millis().try_into().unwrap_or(u64::MAX));
+    Ok(locked::ComponentLimits {
+        fuel: limits.fuel,
+        cpu_time_ms,
+        memory,
+        tables: limits.tables,
+        instances: limits.instances,
+        concurrency: limits.concurrency,
+        queue: limits.queue,
+        queue_timeout_ms,
+    })
+}
+
 fn locked_metadata(
     details: v2::AppDetails,
     trigger_types: impl Iterator<Item = String>,
@@ -605,6 +671,16 @@ fn locked_metadata(
     Ok(builder.build())
 }
 
+/// Reads the `.sql` files in a migrations directory, in file name order, as
+/// `{ name, sql }` objects.
+fn read_sqlite_migrations(dir: &Path) -> Result<Vec<serde_json::Value>> {
+    let migrations = spin_common::migrations::read_migrations_dir(dir)?
+        .into_iter()
+        .map(|(name, sql)| serde_json::json!({ "name": name, "sql": sql }))
+        .collect();
+    Ok(migrations)
+}
+
 fn locked_variable(variable: v2::Variable) -> Result<locked::Variable> {
     ensure!(
         variable.required ^ variable.default.is_some(),
@@ -249,6 +249,44 @@ pub struct LockedComponent {
     pub host_requirements: ValuesMap,
 }
 
+/// Resource limits applied to each instance of a component, stored in
+/// component metadata under the `limits` key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentLimits {
+    /// The maximum fuel an instance may consume per invocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel: Option<u64>,
+    /// The maximum time, in milliseconds, an instance may spend executing
+    /// Wasm per invocation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_ms: Option<u64>,
+    /// The maximum linear memory, in bytes, an instance may allocate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+    /// The maximum number of elements in each table of an instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<u32>,
+    /// The maximum number of core Wasm instances an instance may create.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<u32>,
+    /// The maximum number of instances that may run at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+    /// The maximum number of invocations that may wait for a free instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<u32>,
+    /// How long, in milliseconds, an invocation may wait for a free instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_timeout_ms: Option<u64>,
+}
+
+impl ComponentLimits {
+    /// Returns true if no limits are set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
 /// A LockedDependency represents a "fully resolved" Spin component dependency.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LockedComponentDependency {
@@ -66,11 +66,15 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
                 exclude_files: component.exclude_files,
                 key_value_stores: component.key_value_stores,
                 sqlite_databases: component.sqlite_databases,
+                sqlite_migrations: None,
                 ai_models: component.ai_models,
                 build: component.build,
                 tool: Default::default(),
                 allowed_outbound_hosts,
                 allowed_http_hosts: Vec::new(),
+                limits: Default::default(),
+                instance_reuse: None,
+                preinit: false,
                 dependencies_inherit_configuration: false,
                 dependencies: Default::default(),
             },
@@ -364,6 +364,14 @@ pub struct Component {
     )]
     #[schemars(with = "Vec<json_schema::SqliteDatabase>")]
     pub sqlite_databases: Vec<String>,
+    /// A directory of SQL migration files to apply to each of the component's
+    /// `sqlite_databases` before the application starts. Files with a `.sql` extension
+    /// are applied in file name order, each at most once per database; a migration
+    /// which has been applied must not be changed afterwards.
+    ///
+    /// Example: `sqlite_migrations = "migrations"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sqlite_migrations: Option<String>,
     /// The AI models which the component is allowed to access. For local execution, you must
     /// download all models; for hosted execution, you should check which models are available
     /// in your target environment.
@@ -374,6 +382,31 @@ pub struct Component {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     #[schemars(with = "Vec<json_schema::AIModel>")]
     pub ai_models: Vec<String>,
+    /// Resource limits applied to each instance of the component.
+    ///
+    /// Example: `limits = { memory = "256Mi", cpu_time = "500ms" }`
+    #[serde(default, skip_serializing_if = "ComponentLimits::is_empty")]
+    pub limits: ComponentLimits,
+    /// If set, an instance of the component may serve up to this many
+    /// sequential invocations before being discarded, rather than a fresh
+    /// instance being created for every invocation. An instance that traps
+    /// is always discarded.
+    ///
+    /// The component must not rely on its global state being reset between
+    /// invocations.
+    ///
+    /// Example: `instance_reuse = 100`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_reuse: Option<u32>,
+    /// If true, the component's `wizer-initialize` export is run once when the
+    /// application is loaded, and the resulting state is snapshotted into the
+    /// component so that each instance starts already initialized. Only WASI
+    /// is available to the initialization export; calling any other import
+    /// fails the load.
+    ///
+    /// Example: `preinit = true`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preinit: bool,
     /// The component build configuration.
     ///
     /// Learn more: https://spinframework.dev/build
@@ -397,6 +430,125 @@ pub struct Component {
     pub dependencies: ComponentDependencies,
 }
 
+/// Component resource limits
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentLimits {
+    /// The maximum fuel an invocation may consume. Fuel roughly corresponds
+    /// to the number of Wasm instructions executed.
+    ///
+    /// Example: `fuel = 1000000000`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel: Option<u64>,
+    /// The maximum time an invocation may spend executing Wasm, such as
+    /// "500ms" or "2s". Time spent waiting on the host (e.g. for network
+    /// responses) is not counted.
+    ///
+    /// Example: `cpu_time = "500ms"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time: Option<String>,
+    /// The maximum linear memory an instance may allocate, such as "256Mi"
+    /// or "1G". Binary (`Ki`, `Mi`, `Gi`) and decimal (`K`, `M`, `G`)
+    /// suffixes are supported.
+    ///
+    /// Example: `memory = "256Mi"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    /// The maximum number of elements in each table of an instance.
+    ///
+    /// Example: `tables = 20000`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<u32>,
+    /// The maximum number of core Wasm instances an instance of the
+    /// component may create. Composed components need one per inner module.
+    ///
+    /// Example: `instances = 50`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instances: Option<u32>,
+    /// The maximum number of instances of the component that may run at
+    /// once. Further invocations wait in a queue until an instance finishes.
+    ///
+    /// Example: `concurrency = 10`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+    /// The maximum number of invocations that may wait for a free instance
+    /// when `concurrency` is reached. Invocations beyond this are rejected.
+    /// If unset, the queue is unbounded.
+    ///
+    /// Example: `queue = 100`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue: Option<u32>,
+    /// How long an invocation may wait in the queue, such as "2s", before
+    /// being rejected. If unset, invocations wait indefinitely.
+    ///
+    /// Example: `queue_timeout = "2s"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_timeout: Option<String>,
+}
+
+impl ComponentLimits {
+    /// Returns true if no limits are set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Parses the `cpu_time` limit, if set.
+    pub fn cpu_time(&self) -> anyhow::Result<Option<std::time::Duration>> {
+        self.cpu_time
+            .as_deref()
+            .map(|cpu_time| {
+                humantime::parse_duration(cpu_time)
+                    .with_context(|| format!("invalid `cpu_time` {cpu_time:?}"))
+            })
+            .transpose()
+    }
+
+    /// Parses the `queue_timeout`, if set.
+    pub fn queue_timeout(&self) -> anyhow::Result<Option<std::time::Duration>> {
+        self.queue_timeout
+            .as_deref()
+            .map(|queue_timeout| {
+                humantime::parse_duration(queue_timeout)
+                    .with_context(|| format!("invalid `queue_timeout` {queue_timeout:?}"))
+            })
+            .transpose()
+    }
+
+    /// Parses the `memory` limit, if set, in bytes.
+    pub fn memory(&self) -> anyhow::Result<Option<u64>> {
+        self.memory
+            .as_deref()
+            .map(|memory| {
+                parse_byte_size(memory).with_context(|| format!("invalid `memory` {memory:?}"))
+            })
+            .transpose()
+    }
+}
+
+fn parse_byte_size(size: &str) -> anyhow::Result<u64> {
+    let size = size.trim();
+    let digits_end = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, suffix) = size.split_at(digits_end);
+    let number: u64 = number
+        .parse()
+        .context("expected a number of bytes optionally followed by a unit such as \"Mi\"")?;
+    let multiplier: u64 = match suffix.trim_start() {
+        "" | "B" => 1,
+        "K" | "KB" => 1_000,
+        "Ki" | "KiB" => 1 << 10,
+        "M" | "MB" => 1_000_000,
+        "Mi" | "MiB" => 1 << 20,
+        "G" | "GB" => 1_000_000_000,
+        "Gi" | "GiB" => 1 << 30,
+        unit => anyhow::bail!("unknown unit {unit:?}"),
+    };
+    number
+        .checked_mul(multiplier)
+        .context("byte size is too large")
+}
+
 /// Component dependencies
 #[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
@@ -760,9 +912,13 @@ mod tests {
             allowed_outbound_hosts: vec![],
             key_value_stores: labels.clone(),
             sqlite_databases: labels,
+            sqlite_migrations: None,
             ai_models: vec![],
             build: None,
             tool: Map::new(),
+            limits: Default::default(),
+            instance_reuse: None,
+            preinit: false,
             dependencies_inherit_configuration: false,
             dependencies: Default::default(),
         }
@@ -815,6 +971,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_component_limits_memory() {
+        let limits = |memory: &str| ComponentLimits {
+            memory: Some(memory.into()),
+            ..Default::default()
+        };
+        assert_eq!(limits("256Mi").memory().unwrap(), Some(256 << 20));
+        assert_eq!(limits("1G").memory().unwrap(), Some(1_000_000_000));
+        assert_eq!(limits("4096").memory().unwrap(), Some(4096));
+        assert!(limits("256Xi").memory().is_err());
+        assert!(limits("Mi").memory().is_err());
+        assert_eq!(ComponentLimits::default().memory().unwrap(), None);
+    }
+
     #[test]
     fn test_check_disjoint() {
         for (a, b) in [
@@ -1,7 +1,9 @@
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Context as _;
 use spin_common::ui::quoted_path;
+use spin_factor_extensions::HostExtensionsFactor;
 use spin_factor_key_value::runtime_config::spin::{self as key_value};
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_llm::{spin as llm, LlmFactor};
@@ -49,6 +51,10 @@ pub struct ResolvedRuntimeConfig<T> {
     pub log_dir: Option<PathBuf>,
     /// The maximum memory allocation limit.
     pub max_instance_memory: Option<usize>,
+    /// The maximum fuel available to each instance.
+    pub max_instance_fuel: Option<u64>,
+    /// The maximum CPU time available to each instance.
+    pub max_instance_cpu_time: Option<Duration>,
     /// The input TOML, for informational summaries.
     pub toml: toml::Table,
 }
@@ -137,19 +143,29 @@ where
         let outbound_networking = runtime_config_dir
             .clone()
             .map(OutboundNetworkingSpinRuntimeConfig::new);
-        let key_value_resolver = key_value_config_resolver(runtime_config_dir, state_dir.clone());
+        // The keys of encrypted key-value stores are read from the app's variables providers
+        let variables_providers =
+            variables::runtime_config_from_toml(&toml_resolver.toml())?.providers;
+        let key_value_resolver = key_value_config_resolver(
+            runtime_config_dir.clone(),
+            state_dir.clone(),
+            variables_providers,
+        );
         let sqlite_resolver = sqlite_config_resolver(state_dir.clone())
             .context("failed to resolve sqlite runtime config")?;
 
         let toml = toml_resolver.toml();
         let log_dir = toml_resolver.log_dir()?;
         let max_instance_memory = toml_resolver.max_instance_memory()?;
+        let max_instance_fuel = toml_resolver.max_instance_fuel()?;
+        let max_instance_cpu_time = toml_resolver.max_instance_cpu_time()?;
 
         let source = TomlRuntimeConfigSource::new(
             toml_resolver,
             &key_value_resolver,
             outbound_networking.as_ref(),
             &sqlite_resolver,
+            runtime_config_dir.as_deref(),
         );
 
         // Note: all valid fields in the runtime config must have been referenced at
@@ -164,6 +180,8 @@ where
             state_dir,
             log_dir,
             max_instance_memory,
+            max_instance_fuel,
+            max_instance_cpu_time,
             toml,
         })
     }
@@ -182,6 +200,16 @@ where
     pub fn max_instance_memory(&self) -> Option<usize> {
         self.max_instance_memory
     }
+
+    /// The maximum fuel available to each instance.
+    pub fn max_instance_fuel(&self) -> Option<u64> {
+        self.max_instance_fuel
+    }
+
+    /// The maximum CPU time available to each instance.
+    pub fn max_instance_cpu_time(&self) -> Option<Duration> {
+        self.max_instance_cpu_time
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -286,6 +314,33 @@ impl<'a> TomlResolver<'a> {
             .map_err(Into::into)
     }
 
+    /// Get the configured maximum fuel per instance.
+    pub fn max_instance_fuel(&self) -> anyhow::Result<Option<u64>> {
+        self.table
+            .get("max_instance_fuel")
+            .and_then(|v| v.as_integer())
+            .map(|toml_value| toml_value.try_into())
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Get the configured maximum CPU time per instance, e.g. `"500ms"`.
+    pub fn max_instance_cpu_time(&self) -> anyhow::Result<Option<Duration>> {
+        let Some(value) = self.table.get("max_instance_cpu_time") else {
+            return Ok(None);
+        };
+        let value = value
+            .as_str()
+            .context("`max_instance_cpu_time` must be a duration string such as \"500ms\"")?;
+        let cpu_time = humantime::parse_duration(value)
+            .with_context(|| format!("invalid `max_instance_cpu_time` {value:?}"))?;
+        anyhow::ensure!(
+            !cpu_time.is_zero(),
+            "`max_instance_cpu_time` must not be zero"
+        );
+        Ok(Some(cpu_time))
+    }
+
     /// Validate that all keys in the TOML file have been used.
     pub fn validate_all_keys_used(&self) -> spin_factors::Result<()> {
         self.table.validate_all_keys_used()
@@ -302,6 +357,7 @@ pub struct TomlRuntimeConfigSource<'a, 'b> {
     key_value: &'a key_value::RuntimeConfigResolver,
     outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
     sqlite: &'a sqlite::RuntimeConfigResolver,
+    runtime_config_dir: Option<&'a Path>,
 }
 
 impl<'a, 'b> TomlRuntimeConfigSource<'a, 'b> {
@@ -310,12 +366,14 @@ impl<'a, 'b> TomlRuntimeConfigSource<'a, 'b> {
         key_value: &'a key_value::RuntimeConfigResolver,
         outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
         sqlite: &'a sqlite::RuntimeConfigResolver,
+        runtime_config_dir: Option<&'a Path>,
     ) -> Self {
         Self {
             toml: toml_resolver,
             key_value,
             outbound_networking,
             sqlite,
+            runtime_config_dir,
         }
     }
 }
@@ -398,6 +456,17 @@ impl FactorRuntimeConfigSource<SqliteFactor> for TomlRuntimeConfigSource<'_, '_>
     }
 }
 
+impl FactorRuntimeConfigSource<HostExtensionsFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_extensions::runtime_config::RuntimeConfig>> {
+        spin_factor_extensions::runtime_config::spin::config_from_table(
+            &self.toml.table,
+            self.runtime_config_dir,
+        )
+    }
+}
+
 impl RuntimeConfigSourceFinalizer for TomlRuntimeConfigSource<'_, '_> {
     fn finalize(&mut self) -> anyhow::Result<()> {
         Ok(self.toml.validate_all_keys_used()?)
@@ -410,10 +479,12 @@ const DEFAULT_KEY_VALUE_STORE_LABEL: &str = "default";
 ///
 /// Takes a base path that all local key-value stores which are configured with
 /// relative paths will be relative to. It also takes a default store base path
-/// which will be used as the directory for the default store.
+/// which will be used as the directory for the default store, and the variables
+/// providers from which the keys of encrypted stores are read.
 pub fn key_value_config_resolver(
     local_store_base_path: Option<PathBuf>,
     default_store_base_path: Option<PathBuf>,
+    variables_providers: Vec<Box<dyn spin_expressions::Provider>>,
 ) -> key_value::RuntimeConfigResolver {
     let mut key_value = key_value::RuntimeConfigResolver::new();
 
@@ -433,6 +504,24 @@ pub fn key_value_config_resolver(
     key_value
         .register_store_type(spin_key_value_aws::AwsDynamoKeyValueStore::new())
         .unwrap();
+    key_value
+        .register_store_type(spin_key_value_memory::MemoryKeyValueStore::new())
+        .unwrap();
+    // Encrypted stores may wrap any of the store types above
+    let backing_types = key_value.clone();
+    key_value
+        .register_store_type(spin_key_value_encrypted::EncryptedKeyValueStore::new(
+            backing_types,
+            variables_providers,
+        ))
+        .unwrap();
+    // Tiered stores may wrap any of the other store types
+    let backing_types = key_value.clone();
+    key_value
+        .register_store_type(spin_key_value_tiered::TieredKeyValueStore::new(
+            backing_types,
+        ))
+        .unwrap();
 
     // Add handling of "default" store.
     let default_store_path = default_store_base_path.map(|p| p.join(DEFAULT_SPIN_STORE_FILENAME));
@@ -454,7 +543,7 @@ const DEFAULT_SPIN_STORE_FILENAME: &str = "sqlite_key_value.db";
 ///
 /// Takes a path to the directory where the default database should be stored.
 /// If the path is `None`, the default database will be in-memory.
-fn sqlite_config_resolver(
+pub fn sqlite_config_resolver(
     default_database_dir: Option<PathBuf>,
 ) -> anyhow::Result<sqlite::RuntimeConfigResolver> {
     let local_database_dir =
@@ -542,6 +631,32 @@ mod tests {
         let toml = toml::Table::new();
         let runtime_config = resolve_toml(toml, "config.toml").unwrap().runtime_config;
         assert_eq!(runtime_config.configured_labels(), vec!["default"]);
+
+        // Test that migrations are read from the configured directory.
+        let migrations_dir = tempfile::tempdir().unwrap();
+        std::fs::write(migrations_dir.path().join("001_init.sql"), "SELECT 1;").unwrap();
+        let mut toml = toml::toml! {
+            [sqlite_database.foo]
+            type = "spin"
+        };
+        toml.get_mut("sqlite_database")
+            .and_then(|databases| databases.get_mut("foo"))
+            .and_then(|foo| foo.as_table_mut())
+            .unwrap()
+            .insert(
+                "migrations".into(),
+                migrations_dir.path().to_str().unwrap().into(),
+            );
+        let runtime_config = resolve_toml(toml, ".").unwrap().runtime_config;
+        let migrations = &runtime_config.sqlite.as_ref().unwrap().migrations;
+        assert_eq!(migrations["foo"][0].name, "001_init.sql");
+
+        let toml = toml::toml! {
+            [sqlite_database.foo]
+            type = "spin"
+            migrations = "does-not-exist"
+        };
+        assert!(resolve_toml(toml, ".").is_err());
     }
 
     #[test]
@@ -563,6 +678,75 @@ mod tests {
         assert!(["default", "foo"]
             .iter()
             .all(|label| runtime_config.has_store_manager(label)));
+
+        // Test that tiered stores can wrap other store types.
+        let toml = toml::toml! {
+            [key_value_store.cache]
+            type = "tiered"
+            capacity = 100
+            backing = { type = "memory" }
+        };
+        let runtime_config = resolve_toml(toml, "config.toml").unwrap().runtime_config;
+        assert!(runtime_config.has_store_manager("cache"));
+
+        let toml = toml::toml! {
+            [key_value_store.cache]
+            type = "tiered"
+            backing = { type = "unknown" }
+        };
+        assert!(resolve_toml(toml, "config.toml").is_err());
+
+        // Test that encrypted stores can wrap other store types.
+        let toml = toml::toml! {
+            [key_value_store.secrets]
+            type = "encrypted"
+            current_key = "k1"
+            keys = { k1 = "kv_key_1" }
+            backing = { type = "memory" }
+        };
+        let runtime_config = resolve_toml(toml, "config.toml").unwrap().runtime_config;
+        assert!(runtime_config.has_store_manager("secrets"));
+
+        let toml = toml::toml! {
+            [key_value_store.secrets]
+            type = "encrypted"
+            current_key = "k2"
+            keys = { k1 = "kv_key_1" }
+            backing = { type = "memory" }
+        };
+        assert!(resolve_toml(toml, "config.toml").is_err());
+
+        // Test that quotas may be set on any store type.
+        let toml = toml::toml! {
+            [key_value_store.limited]
+            type = "memory"
+            quota = { max_keys = 100, max_total_bytes = 1048576 }
+        };
+        let runtime_config = resolve_toml(toml, "config.toml").unwrap().runtime_config;
+        let quotas = runtime_config
+            .key_value
+            .as_ref()
+            .unwrap()
+            .quotas()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            quotas,
+            [(
+                "limited",
+                &spin_factor_key_value::Quota {
+                    max_keys: Some(100),
+                    max_value_bytes: None,
+                    max_total_bytes: Some(1048576),
+                }
+            )]
+        );
+
+        let toml = toml::toml! {
+            [key_value_store.limited]
+            type = "memory"
+            quota = { max_size = 100 }
+        };
+        assert!(resolve_toml(toml, "config.toml").is_err());
     }
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
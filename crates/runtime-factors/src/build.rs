@@ -6,8 +6,9 @@ use anyhow::Context as _;
 use spin_factors_executor::FactorsExecutor;
 use spin_runtime_config::ResolvedRuntimeConfig;
 use spin_trigger::cli::{
-    FactorsConfig, InitialKvSetterHook, KeyValueDefaultStoreSummaryHook, MaxInstanceMemoryHook,
-    RuntimeFactorsBuilder, SqlStatementExecutorHook, SqliteDefaultStoreSummaryHook,
+    ComponentLimitsHook, CoreDumpHook, FactorsConfig, GuestProfilerHook, InitialKvSetterHook,
+    KeyValueDefaultStoreSummaryHook, MaxInstanceMemoryHook, RuntimeFactorsBuilder,
+    SqlStatementExecutorHook, SqliteDefaultStoreSummaryHook, SqliteMigrationsHook,
     StdioLoggingExecutorHooks,
 };
 use spin_variables_static::StaticVariablesProvider;
@@ -55,6 +56,25 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
         Ok((factors, runtime_config))
     }
 
+    fn update_core_config(
+        config: &mut spin_core::Config,
+        runtime_config: &Self::RuntimeConfig,
+        args: &Self::CliArgs,
+    ) -> anyhow::Result<()> {
+        // Fuel metering has a runtime cost, so only enable it when needed
+        if args
+            .max_instance_fuel
+            .or(runtime_config.max_instance_fuel())
+            .is_some()
+        {
+            config.consume_fuel();
+        }
+        if args.core_dump {
+            config.coredump_on_trap();
+        }
+        Ok(())
+    }
+
     fn configure_app<U: Send + 'static>(
         executor: &mut FactorsExecutor<Self::Factors, U>,
         runtime_config: &Self::RuntimeConfig,
@@ -66,6 +86,7 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             runtime_config.log_dir(),
             config.truncate_logs,
         ));
+        executor.add_hooks(SqliteMigrationsHook);
         executor.add_hooks(SqlStatementExecutorHook::new(
             args.sqlite_statements.clone(),
         ));
@@ -82,6 +103,27 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             executor.add_hooks(MaxInstanceMemoryHook::new(max_instance_memory));
         }
 
+        let pooling_limits = executor.core_engine().pooling_limits();
+        executor.add_hooks(ComponentLimitsHook::new(
+            args.max_instance_fuel
+                .or(runtime_config.max_instance_fuel()),
+            args.max_instance_cpu_time
+                .or(runtime_config.max_instance_cpu_time()),
+            max_instance_memory,
+            pooling_limits,
+        ));
+
+        if args.core_dump {
+            match runtime_config.log_dir() {
+                Some(log_dir) => executor.add_hooks(CoreDumpHook::new(log_dir.join("coredumps"))),
+                None => tracing::warn!("Core dumps are disabled because there is no log directory"),
+            }
+        }
+
+        if let Some(profile_dir) = &args.profile {
+            executor.add_hooks(GuestProfilerHook::new(profile_dir.clone()));
+        }
+
         Ok(())
     }
 }
@@ -8,6 +8,7 @@ use std::path::PathBuf;
 
 use anyhow::Context as _;
 use spin_common::arg_parser::parse_kv;
+use spin_factor_extensions::HostExtensionsFactor;
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_llm::LlmFactor;
 use spin_factor_outbound_http::OutboundHttpFactor;
@@ -36,6 +37,7 @@ pub struct TriggerFactors {
     pub pg: OutboundPgFactor,
     pub mysql: OutboundMysqlFactor,
     pub llm: LlmFactor,
+    pub extensions: HostExtensionsFactor,
 }
 
 impl TriggerFactors {
@@ -59,6 +61,7 @@ impl TriggerFactors {
                 spin_factor_llm::spin::default_engine_creator(state_dir)
                     .context("failed to configure LLM factor")?,
             ),
+            extensions: HostExtensionsFactor::new(),
         })
     }
 }
@@ -108,6 +111,29 @@ pub struct TriggerAppArgs {
     #[clap(long, env = "SPIN_MAX_INSTANCE_MEMORY")]
     pub max_instance_memory: Option<usize>,
 
+    /// Sets the maximum fuel an instance may consume per invocation.
+    /// Components may override this with `limits.fuel` in the manifest.
+    #[clap(long, env = "SPIN_MAX_INSTANCE_FUEL")]
+    pub max_instance_fuel: Option<u64>,
+
+    /// Sets the maximum CPU time an instance may use per invocation, e.g.
+    /// "500ms". Components may override this with `limits.cpu_time` in the
+    /// manifest.
+    #[clap(long, env = "SPIN_MAX_INSTANCE_CPU_TIME", value_parser = parse_cpu_time)]
+    pub max_instance_cpu_time: Option<std::time::Duration>,
+
+    /// Writes a core dump to the log directory whenever an instance traps,
+    /// for post-mortem debugging. Not supported for components built with a
+    /// WASI preview 1 adapter.
+    #[clap(long = "core-dump")]
+    pub core_dump: bool,
+
+    /// Profiles each instance and writes the profiles to the given directory,
+    /// one per instance. Profiles can be viewed with the Firefox profiler
+    /// (https://profiler.firefox.com/).
+    #[clap(long = "profile", value_name = "DIR")]
+    pub profile: Option<PathBuf>,
+
     /// Variable(s) to be passed to the app
     ///
     /// A single key-value pair can be passed as `key=value`. Alternatively, the
@@ -152,3 +178,10 @@ impl TriggerAppArgs {
         Ok(self.variables_cache.get().unwrap())
     }
 }
+
+/// Parses a `--max-instance-cpu-time`, which must not be zero.
+fn parse_cpu_time(value: &str) -> anyhow::Result<std::time::Duration> {
+    let cpu_time = humantime::parse_duration(value)?;
+    anyhow::ensure!(!cpu_time.is_zero(), "CPU time limit must not be zero");
+    Ok(cpu_time)
+}
@@ -7,7 +7,7 @@ use std::{
 };
 
 use serde::Deserialize;
-use spin_factor_sqlite::ConnectionCreator;
+use spin_factor_sqlite::{migrations::read_migrations_dir, ConnectionCreator};
 use spin_factors::{
     anyhow::{self, Context as _},
     runtime_config::toml::GetTomlValue,
@@ -48,6 +48,7 @@ impl RuntimeConfigResolver {
     /// ````toml
     /// [sqlite_database.$database-label]
     /// type = "$database-type"
+    /// migrations = "$migrations-dir" # optional
     /// ... extra type specific configuration ...
     /// ```
     ///
@@ -77,13 +78,21 @@ impl RuntimeConfigResolver {
         };
         let config: std::collections::HashMap<String, TomlRuntimeConfig> =
             table.clone().try_into()?;
-        let connection_creators = config
-            .into_iter()
-            .map(|(k, v)| Ok((k, self.get_connection_creator(v)?)))
-            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        let mut connection_creators = HashMap::new();
+        let mut migrations = HashMap::new();
+        for (label, config) in config {
+            if let Some(dir) = &config.migrations {
+                let dir = resolve_relative_path(dir, &self.local_database_dir);
+                let database_migrations = read_migrations_dir(&dir)
+                    .with_context(|| format!("invalid migrations for SQLite database '{label}'"))?;
+                migrations.insert(label.clone(), database_migrations);
+            }
+            connection_creators.insert(label, self.get_connection_creator(config)?);
+        }
 
         Ok(Some(spin_factor_sqlite::runtime_config::RuntimeConfig {
             connection_creators,
+            migrations,
         }))
     }
 
@@ -113,6 +122,9 @@ impl RuntimeConfigResolver {
 pub struct TomlRuntimeConfig {
     #[serde(rename = "type")]
     pub type_: String,
+    /// A directory of migrations to apply to the database, resolved like a local database path.
+    #[serde(default)]
+    pub migrations: Option<PathBuf>,
     #[serde(flatten)]
     pub config: toml::Table,
 }
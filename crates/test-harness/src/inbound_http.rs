@@ -0,0 +1,129 @@
+use anyhow::{anyhow, bail, Context as _};
+use http_body_util::{BodyExt, Full};
+use spin_factor_outbound_http::OutboundHttpFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::FactorsInstanceBuilder;
+use spin_http::trigger::HandlerType;
+use spin_world::v1::http_types;
+use tokio::sync::oneshot;
+use wasmtime_wasi_http::{
+    bindings::http::types::Scheme, body::HostIncomingBody, types::HostIncomingRequest, WasiHttpView,
+};
+
+use crate::HarnessFactors;
+
+type Store = spin_core::Store<
+    spin_factors_executor::InstanceState<<HarnessFactors as RuntimeFactors>::InstanceState, ()>,
+>;
+
+/// How long the component may wait between chunks of the request body.
+const BETWEEN_BYTES_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// Instantiates the component and has its HTTP handler handle the request.
+pub(crate) async fn handle(
+    builder: FactorsInstanceBuilder<'_, HarnessFactors, ()>,
+    request: http::Request<Vec<u8>>,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let (instance, mut store) = builder.instantiate(()).await?;
+    let pre = instance.instance_pre(&store);
+    let indices = match HandlerType::from_instance_pre(&pre)? {
+        HandlerType::Wasi0_2(indices) => indices,
+        HandlerType::Spin => return handle_spin(instance, store, request).await,
+        _ => bail!("the test harness only supports `wasi:http@0.2` and `fermyon:spin/inbound-http` handlers"),
+    };
+    let proxy = indices.load(&mut store, &instance)?;
+
+    let mut wasi_http =
+        OutboundHttpFactor::get_wasi_http_impl(store.data_mut().factors_instance_state_mut())
+            .context("missing OutboundHttpFactor")?;
+    let (parts, body) = request.into_parts();
+    let body = Full::new(body.into()).map_err(|err| match err {}).boxed();
+    let body = HostIncomingBody::new(body, BETWEEN_BYTES_TIMEOUT);
+    let request = HostIncomingRequest::new(&mut wasi_http, parts, Scheme::Http, Some(body))?;
+    let request = wasi_http.table().push(request)?;
+    let (response_tx, response_rx) = oneshot::channel();
+    let response = wasi_http.new_response_outparam(response_tx)?;
+    drop(wasi_http);
+
+    // The store is dropped when the handler returns so that the response
+    // channel closes if the handler failed to set a response
+    let call = async move {
+        proxy
+            .wasi_http_incoming_handler()
+            .call_handle(&mut store, request, response)
+            .await
+    };
+    let receive = async {
+        let response = response_rx
+            .await
+            .context("component failed to produce a response")?
+            .map_err(|code| anyhow!("component responded with an error: {code:?}"))?;
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await?.to_bytes().to_vec();
+        anyhow::Ok(http::Response::from_parts(parts, body))
+    };
+    let (result, response) = tokio::join!(call, receive);
+    result.context("component trapped")?;
+    response
+}
+
+async fn handle_spin(
+    instance: spin_core::Instance,
+    mut store: Store,
+    request: http::Request<Vec<u8>>,
+) -> anyhow::Result<http::Response<Vec<u8>>> {
+    let inbound_http = instance
+        .get_export_index(&mut store, None, "fermyon:spin/inbound-http")
+        .context("no fermyon:spin/inbound-http found")?;
+    let handle_request = instance
+        .get_export_index(&mut store, Some(&inbound_http), "handle-request")
+        .context("no handle-request found")?;
+    let func = instance.get_typed_func::<(http_types::Request,), (http_types::Response,)>(
+        &mut store,
+        &handle_request,
+    )?;
+
+    let (parts, body) = request.into_parts();
+    let method = convert_method(&parts.method)
+        .with_context(|| format!("unsupported method {}", parts.method))?;
+    let headers = parts
+        .headers
+        .iter()
+        .map(|(name, value)| Ok((name.to_string(), value.to_str()?.to_owned())))
+        .collect::<anyhow::Result<_>>()?;
+    let uri = match parts.uri.path_and_query() {
+        Some(path_and_query) => path_and_query.to_string(),
+        None => parts.uri.to_string(),
+    };
+    let request = http_types::Request {
+        method,
+        uri,
+        headers,
+        params: vec![],
+        body: Some(body),
+    };
+
+    let (response,) = func
+        .call_async(&mut store, (request,))
+        .await
+        .context("component trapped")?;
+
+    let mut builder = http::Response::builder().status(response.status);
+    for (name, value) in response.headers.unwrap_or_default() {
+        builder = builder.header(name, value);
+    }
+    Ok(builder.body(response.body.unwrap_or_default())?)
+}
+
+fn convert_method(m: &http::Method) -> Option<http_types::Method> {
+    Some(match *m {
+        http::Method::GET => http_types::Method::Get,
+        http::Method::POST => http_types::Method::Post,
+        http::Method::PUT => http_types::Method::Put,
+        http::Method::DELETE => http_types::Method::Delete,
+        http::Method::PATCH => http_types::Method::Patch,
+        http::Method::HEAD => http_types::Method::Head,
+        http::Method::OPTIONS => http_types::Method::Options,
+        _ => return None,
+    })
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use spin_core::async_trait;
+use spin_factor_key_value::{Cas, Error, Store, StoreManager};
+
+use crate::{HostCall, HostCalls};
+
+/// A [`StoreManager`] for in-memory key-value stores which records the
+/// operations performed on them.
+pub(crate) struct MemoryStoreManager {
+    stores: HashMap<String, Arc<MemoryStore>>,
+}
+
+impl MemoryStoreManager {
+    pub fn new(stores: HashMap<String, Arc<MemoryStore>>) -> Self {
+        Self { stores }
+    }
+}
+
+#[async_trait]
+impl StoreManager for MemoryStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        self.stores
+            .get(name)
+            .map(|store| store.clone() as Arc<dyn Store>)
+            .ok_or(Error::NoSuchStore)
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.stores.contains_key(store_name)
+    }
+
+    fn summary(&self, _store_name: &str) -> Option<String> {
+        Some("in-memory test store".into())
+    }
+}
+
+/// An in-memory key-value store.
+pub(crate) struct MemoryStore {
+    label: String,
+    values: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    calls: HostCalls,
+}
+
+impl MemoryStore {
+    pub fn new(label: String, values: HashMap<String, Vec<u8>>, calls: HostCalls) -> Self {
+        Self {
+            label,
+            values: Arc::new(Mutex::new(values)),
+            calls,
+        }
+    }
+
+    /// Returns a copy of the store's current contents.
+    pub fn entries(&self) -> HashMap<String, Vec<u8>> {
+        self.values.lock().unwrap().clone()
+    }
+
+    fn record(&self, operation: &'static str, keys: impl IntoIterator<Item = String>) {
+        self.calls.record(HostCall::KeyValue {
+            store: self.label.clone(),
+            operation,
+            keys: keys.into_iter().collect(),
+        });
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.record("get", [key.to_owned()]);
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.record("set", [key.to_owned()]);
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.record("delete", [key.to_owned()]);
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.record("exists", [key.to_owned()]);
+        Ok(self.values.lock().unwrap().contains_key(key))
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.record("get_keys", []);
+        Ok(self.values.lock().unwrap().keys().cloned().collect())
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.record("get_many", keys.clone());
+        let values = self.values.lock().unwrap();
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = values.get(&key).cloned();
+                (key, value)
+            })
+            .collect())
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.record("set_many", key_values.iter().map(|(key, _)| key.clone()));
+        self.values.lock().unwrap().extend(key_values);
+        Ok(())
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.record("delete_many", keys.clone());
+        let mut values = self.values.lock().unwrap();
+        for key in keys {
+            values.remove(&key);
+        }
+        Ok(())
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        self.record("increment", [key.clone()]);
+        let mut values = self.values.lock().unwrap();
+        let current = match values.get(&key) {
+            Some(bytes) => i64::from_le_bytes(
+                bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| Error::Other("stored value is not an i64".into()))?,
+            ),
+            None => 0,
+        };
+        let new = current + delta;
+        values.insert(key, new.to_le_bytes().to_vec());
+        Ok(new)
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        self.record("new_compare_and_swap", [key.to_owned()]);
+        Ok(Arc::new(MemoryCas {
+            key: key.to_owned(),
+            bucket_rep,
+            values: self.values.clone(),
+            current: Mutex::new(None),
+        }))
+    }
+}
+
+/// A compare-and-swap on a [`MemoryStore`], which succeeds if the value is
+/// unchanged since it was read with [`Cas::current`].
+struct MemoryCas {
+    key: String,
+    bucket_rep: u32,
+    values: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    current: Mutex<Option<Option<Vec<u8>>>>,
+}
+
+#[async_trait]
+impl Cas for MemoryCas {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.values.lock().unwrap().get(&self.key).cloned();
+        *self.current.lock().unwrap() = Some(value.clone());
+        Ok(value)
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), spin_factor_key_value::SwapError> {
+        let mut values = self.values.lock().unwrap();
+        let expected = self.current.lock().unwrap().take();
+        if let Some(expected) = expected {
+            if values.get(&self.key) != expected.as_ref() {
+                return Err(spin_factor_key_value::SwapError::CasFailed(format!(
+                    "{:?} was modified",
+                    self.key
+                )));
+            }
+        }
+        values.insert(self.key.clone(), value);
+        Ok(())
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.bucket_rep
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
@@ -0,0 +1,439 @@
+//! A harness for testing Spin components with `cargo test`.
+//!
+//! [`TestHarness`] loads a component from a Wasm file with in-memory
+//! key-value stores and SQLite databases, fixed variable values and mock
+//! outbound HTTP responses, then invokes its exports as a trigger would. Each
+//! [`Invocation`] returns the export's result along with the [`HostCall`]s the
+//! component made.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use spin_test_harness::{HostCall, TestHarness};
+//!
+//! let app = TestHarness::new("target/wasm32-wasip1/release/my_component.wasm")
+//!     .key_value_store("default", [("greeting", "hello")])
+//!     .variable("api_url", "https://example.com/api")
+//!     .outbound_http(
+//!         "https://example.com/api",
+//!         http::Response::new(b"world".to_vec()),
+//!     )
+//!     .load()
+//!     .await?;
+//!
+//! let invocation = app.invoke_http(http::Request::new(vec![])).await?;
+//! assert_eq!(invocation.output.status(), 200);
+//! assert!(invocation.host_calls.contains(&HostCall::Variable {
+//!     name: "api_url".into()
+//! }));
+//! # Ok(())
+//! # }
+//! ```
+
+mod inbound_http;
+mod key_value;
+mod outbound_http;
+mod sqlite;
+mod variables;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context as _;
+use http::Method;
+use spin_common::{ui::quoted_path, url::parse_file_url};
+use spin_core::{async_trait, wasmtime, Component};
+use spin_factor_key_value::KeyValueFactor;
+use spin_factor_outbound_http::OutboundHttpFactor;
+use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_sqlite::SqliteFactor;
+use spin_factor_variables::VariablesFactor;
+use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
+use spin_factors::{App, AppComponent, RuntimeFactors};
+use spin_factors_executor::{FactorsExecutor, FactorsExecutorApp, FactorsInstanceBuilder};
+use spin_world::{exports::fermyon::spin::inbound_redis, spin::sqlite::sqlite::QueryResult};
+
+use key_value::{MemoryStore, MemoryStoreManager};
+use outbound_http::{MockInterceptor, MockResponse};
+use sqlite::MemoryDatabase;
+use variables::RecordingProvider;
+
+/// The ID of the component under test.
+const COMPONENT_ID: &str = "test-component";
+
+/// A call made by a component to a host interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostCall {
+    /// A key-value store operation (e.g. `"get"`) and the keys it accessed.
+    KeyValue {
+        store: String,
+        operation: &'static str,
+        keys: Vec<String>,
+    },
+    /// A statement run against a SQLite database.
+    Sqlite { database: String, statement: String },
+    /// The resolution of an application variable.
+    Variable { name: String },
+    /// An outbound HTTP request.
+    OutboundHttp { method: Method, url: String },
+}
+
+/// The host calls recorded during invocations.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HostCalls(Arc<Mutex<Vec<HostCall>>>);
+
+impl HostCalls {
+    fn record(&self, call: HostCall) {
+        self.0.lock().unwrap().push(call);
+    }
+
+    fn take(&self) -> Vec<HostCall> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// The result of invoking a component export.
+#[derive(Debug)]
+pub struct Invocation<T> {
+    /// The output of the export.
+    pub output: T,
+    /// The host calls made by the component, in order.
+    pub host_calls: Vec<HostCall>,
+}
+
+/// A builder for a [`TestApp`] running a single component.
+pub struct TestHarness {
+    source: PathBuf,
+    key_value_stores: BTreeMap<String, HashMap<String, Vec<u8>>>,
+    sqlite_databases: BTreeMap<String, String>,
+    variables: HashMap<String, String>,
+    http_mocks: Vec<MockResponse>,
+}
+
+impl TestHarness {
+    /// Creates a harness for the component in the given Wasm file.
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        Self {
+            source: source.into(),
+            key_value_stores: Default::default(),
+            sqlite_databases: Default::default(),
+            variables: Default::default(),
+            http_mocks: Default::default(),
+        }
+    }
+
+    /// Gives the component access to an in-memory key-value store with the
+    /// given label, initially containing the given entries.
+    pub fn key_value_store<K, V>(
+        mut self,
+        label: impl Into<String>,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<Vec<u8>>,
+    {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()));
+        self.key_value_stores
+            .entry(label.into())
+            .or_default()
+            .extend(entries);
+        self
+    }
+
+    /// Gives the component access to an in-memory SQLite database with the
+    /// given label, initialized by running the given statements.
+    pub fn sqlite_database(
+        mut self,
+        label: impl Into<String>,
+        statements: impl Into<String>,
+    ) -> Self {
+        self.sqlite_databases
+            .insert(label.into(), statements.into());
+        self
+    }
+
+    /// Gives the component a variable with the given name and value.
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Answers outbound HTTP requests for the given URL with the given
+    /// response. Requests to URLs without a response are denied.
+    pub fn outbound_http(
+        mut self,
+        url: impl Into<String>,
+        response: http::Response<Vec<u8>>,
+    ) -> Self {
+        let (parts, body) = response.into_parts();
+        self.http_mocks.push(MockResponse {
+            url: url.into(),
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        });
+        self
+    }
+
+    /// Loads the component, ready to be invoked.
+    pub async fn load(self) -> anyhow::Result<TestApp> {
+        let calls = HostCalls::default();
+        let source = std::fs::canonicalize(&self.source)
+            .with_context(|| format!("failed to resolve {}", quoted_path(&self.source)))?;
+
+        let key_value_stores: HashMap<_, _> = self
+            .key_value_stores
+            .iter()
+            .map(|(label, entries)| {
+                let store = MemoryStore::new(label.clone(), entries.clone(), calls.clone());
+                (label.clone(), Arc::new(store))
+            })
+            .collect();
+        let mut key_value = spin_factor_key_value::RuntimeConfig::default();
+        let manager = Arc::new(MemoryStoreManager::new(key_value_stores.clone()));
+        for label in key_value_stores.keys() {
+            key_value.add_store_manager(label.clone(), manager.clone());
+        }
+
+        let mut sqlite_databases = HashMap::new();
+        let mut sqlite = spin_factor_sqlite::RuntimeConfig::default();
+        for (label, statements) in &self.sqlite_databases {
+            let database = MemoryDatabase::new(label.clone(), calls.clone())?;
+            database
+                .seed(statements)
+                .await
+                .with_context(|| format!("failed to initialize database {label:?}"))?;
+            sqlite
+                .connection_creators
+                .insert(label.clone(), Arc::new(database.clone()));
+            sqlite_databases.insert(label.clone(), database);
+        }
+
+        let variables = spin_factor_variables::runtime_config::RuntimeConfig {
+            providers: vec![Box::new(RecordingProvider::new(
+                self.variables.clone(),
+                calls.clone(),
+            ))],
+        };
+
+        let runtime_config = HarnessFactorsRuntimeConfig {
+            wasi: None,
+            variables: Some(variables),
+            key_value: Some(key_value),
+            outbound_networking: None,
+            outbound_http: None,
+            sqlite: Some(sqlite),
+        };
+
+        let manifest = self.manifest(&source);
+        let locked_app = spin_factors_test::build_locked_app(&manifest)
+            .await
+            .context("failed to build test app")?;
+        let app = App::new("test-app", locked_app);
+
+        let engine_builder = spin_core::Engine::builder(&spin_core::Config::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, HarnessFactors::new())?);
+        let app = executor
+            .load_app(app, runtime_config, &FileComponentLoader)
+            .await?;
+
+        Ok(TestApp {
+            app,
+            key_value_stores,
+            sqlite_databases,
+            http_mocks: self.http_mocks.into(),
+            calls,
+        })
+    }
+
+    /// Builds a manifest for the component under test.
+    fn manifest(&self, source: &std::path::Path) -> toml::Table {
+        let mut component = toml::Table::new();
+        component.insert("source".into(), source.to_string_lossy().as_ref().into());
+        let labels = |labels: Vec<&String>| {
+            labels
+                .into_iter()
+                .map(|label| toml::Value::from(label.as_str()))
+                .collect::<Vec<_>>()
+        };
+        component.insert(
+            "key_value_stores".into(),
+            labels(self.key_value_stores.keys().collect()).into(),
+        );
+        component.insert(
+            "sqlite_databases".into(),
+            labels(self.sqlite_databases.keys().collect()).into(),
+        );
+        // Outbound requests never leave the harness
+        component.insert(
+            "allowed_outbound_hosts".into(),
+            vec!["http://*:*", "https://*:*"].into(),
+        );
+
+        let mut app_variables = toml::Table::new();
+        let mut component_variables = toml::Table::new();
+        for name in self.variables.keys() {
+            let mut variable = toml::Table::new();
+            variable.insert("required".into(), true.into());
+            app_variables.insert(name.clone(), variable.into());
+            component_variables.insert(name.clone(), format!("{{{{ {name} }}}}").into());
+        }
+        component.insert("variables".into(), component_variables.into());
+
+        let mut trigger = toml::Table::new();
+        trigger.insert("component".into(), COMPONENT_ID.into());
+
+        let mut manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+        };
+        manifest.insert("variables".into(), app_variables.into());
+        manifest.insert(
+            "trigger".into(),
+            toml::Table::from_iter([("test".into(), vec![trigger].into())]).into(),
+        );
+        manifest.insert(
+            "component".into(),
+            toml::Table::from_iter([(COMPONENT_ID.into(), component.into())]).into(),
+        );
+        manifest
+    }
+}
+
+/// A component loaded by a [`TestHarness`].
+pub struct TestApp {
+    app: FactorsExecutorApp<HarnessFactors, ()>,
+    key_value_stores: HashMap<String, Arc<MemoryStore>>,
+    sqlite_databases: HashMap<String, MemoryDatabase>,
+    http_mocks: Arc<[MockResponse]>,
+    calls: HostCalls,
+}
+
+impl TestApp {
+    /// Invokes the component's HTTP handler, either `wasi:http/incoming-handler`
+    /// or `fermyon:spin/inbound-http`, with the given request.
+    ///
+    /// The request is passed to the component as given; unlike the HTTP
+    /// trigger, no Spin-specific headers are added.
+    pub async fn invoke_http(
+        &self,
+        request: http::Request<Vec<u8>>,
+    ) -> anyhow::Result<Invocation<http::Response<Vec<u8>>>> {
+        self.invoke(|builder| inbound_http::handle(builder, request))
+            .await
+    }
+
+    /// Invokes the component's `fermyon:spin/inbound-redis` handler with the
+    /// given message payload.
+    pub async fn invoke_redis(
+        &self,
+        payload: impl Into<Vec<u8>>,
+    ) -> anyhow::Result<Invocation<()>> {
+        let payload = payload.into();
+        self.invoke(|builder| async move {
+            let (instance, mut store) = builder.instantiate(()).await?;
+            let pre = instance.instance_pre(&store);
+            let guest_indices = inbound_redis::GuestIndices::new(&pre)?;
+            let guest = guest_indices.load(&mut store, &instance)?;
+            guest
+                .call_handle_message(&mut store, &payload)
+                .await
+                .context("component trapped")?
+                .context("Redis handler returned an error")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns the current contents of the key-value store with the given
+    /// label, or `None` if there is no such store.
+    pub fn key_value_entries(&self, label: &str) -> Option<HashMap<String, Vec<u8>>> {
+        Some(self.key_value_stores.get(label)?.entries())
+    }
+
+    /// Runs a query against the SQLite database with the given label. The
+    /// query is not recorded as a host call.
+    pub async fn sqlite_query(&self, label: &str, query: &str) -> anyhow::Result<QueryResult> {
+        let database = self
+            .sqlite_databases
+            .get(label)
+            .with_context(|| format!("no such database {label:?}"))?;
+        database.inspect(query, vec![]).await
+    }
+
+    async fn invoke<'a, T, F, Fut>(&'a self, f: F) -> anyhow::Result<Invocation<T>>
+    where
+        F: FnOnce(FactorsInstanceBuilder<'a, HarnessFactors, ()>) -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<T>>,
+    {
+        // Discard calls left over from a failed invocation
+        self.calls.take();
+
+        let mut builder = self.app.prepare(COMPONENT_ID).await?;
+        builder
+            .factor_builder::<OutboundHttpFactor>()
+            .context("missing OutboundHttpFactor")?
+            .set_request_interceptor(MockInterceptor::new(
+                self.http_mocks.clone(),
+                self.calls.clone(),
+            ))?;
+        let output = f(builder).await?;
+
+        Ok(Invocation {
+            output,
+            host_calls: self.calls.take(),
+        })
+    }
+}
+
+#[derive(RuntimeFactors)]
+struct HarnessFactors {
+    wasi: WasiFactor,
+    variables: VariablesFactor,
+    key_value: KeyValueFactor,
+    outbound_networking: OutboundNetworkingFactor,
+    outbound_http: OutboundHttpFactor,
+    sqlite: SqliteFactor,
+}
+
+impl HarnessFactors {
+    fn new() -> Self {
+        Self {
+            wasi: WasiFactor::new(DummyFilesMounter),
+            variables: VariablesFactor::default(),
+            key_value: KeyValueFactor::new(),
+            outbound_networking: OutboundNetworkingFactor::new(),
+            outbound_http: OutboundHttpFactor::default(),
+            sqlite: SqliteFactor::new(),
+        }
+    }
+}
+
+/// Loads components directly from their source files.
+struct FileComponentLoader;
+
+#[async_trait]
+impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for FileComponentLoader {
+    async fn load_component(
+        &self,
+        engine: &wasmtime::Engine,
+        component: &AppComponent,
+    ) -> anyhow::Result<Component> {
+        let source = component
+            .source()
+            .content
+            .source
+            .as_ref()
+            .context("LockedComponentSource missing source field")?;
+        let path = parse_file_url(source)?;
+        Component::from_file(engine, &path)
+            .with_context(|| format!("failed to compile component from {}", quoted_path(&path)))
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use http::{HeaderMap, StatusCode};
+use http_body_util::{BodyExt, Full};
+use spin_core::async_trait;
+use spin_factor_outbound_http::{
+    intercept::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor},
+    ErrorCode, HttpResult,
+};
+
+use crate::{HostCall, HostCalls};
+
+/// A canned response to outbound requests for a URL.
+pub(crate) struct MockResponse {
+    pub url: String,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// An [`OutboundHttpInterceptor`] which answers requests with mock responses,
+/// denying requests to URLs without one, and records every request.
+pub(crate) struct MockInterceptor {
+    mocks: Arc<[MockResponse]>,
+    calls: HostCalls,
+}
+
+impl MockInterceptor {
+    pub fn new(mocks: Arc<[MockResponse]>, calls: HostCalls) -> Self {
+        Self { mocks, calls }
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for MockInterceptor {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        let url = request.uri().to_string();
+        self.calls.record(HostCall::OutboundHttp {
+            method: request.method().clone(),
+            url: url.clone(),
+        });
+
+        let Some(mock) = self.mocks.iter().find(|mock| mock.url == url) else {
+            return Err(ErrorCode::HttpRequestDenied.into());
+        };
+        let mut response = http::Response::new(
+            Full::new(mock.body.clone().into())
+                .map_err(|err| match err {})
+                .boxed(),
+        );
+        *response.status_mut() = mock.status;
+        *response.headers_mut() = mock.headers.clone();
+        Ok(InterceptOutcome::Complete(response))
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use spin_core::async_trait;
+use spin_factor_sqlite::{Connection, ConnectionCreator};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::sqlite::sqlite as v3;
+
+use crate::{HostCall, HostCalls};
+
+/// An in-memory SQLite database which records the statements run against it.
+///
+/// Unlike other connection creators, every connection shares the same
+/// underlying database, so data persists across connections and invocations.
+#[derive(Clone)]
+pub(crate) struct MemoryDatabase {
+    label: String,
+    connection: Arc<InProcConnection>,
+    calls: HostCalls,
+}
+
+impl MemoryDatabase {
+    pub fn new(label: String, calls: HostCalls) -> anyhow::Result<Self> {
+        let connection = InProcConnection::new(InProcDatabaseLocation::InMemory)
+            .map_err(|err| anyhow::anyhow!("failed to create in-memory database: {err:?}"))?;
+        Ok(Self {
+            label,
+            connection: Arc::new(connection),
+            calls,
+        })
+    }
+
+    /// Runs statements against the database without recording them.
+    pub async fn seed(&self, statements: &str) -> anyhow::Result<()> {
+        self.connection.execute_batch(statements).await
+    }
+
+    /// Runs a query against the database without recording it.
+    pub async fn inspect(
+        &self,
+        query: &str,
+        parameters: Vec<v3::Value>,
+    ) -> anyhow::Result<v3::QueryResult> {
+        self.connection
+            .query(query, parameters)
+            .await
+            .map_err(|err| anyhow::anyhow!("query failed: {err:?}"))
+    }
+
+    fn record(&self, statement: &str) {
+        self.calls.record(HostCall::Sqlite {
+            database: self.label.clone(),
+            statement: statement.to_owned(),
+        });
+    }
+}
+
+#[async_trait]
+impl ConnectionCreator for MemoryDatabase {
+    async fn create_connection(
+        &self,
+        _label: &str,
+    ) -> Result<Box<dyn Connection + 'static>, v3::Error> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+#[async_trait]
+impl Connection for MemoryDatabase {
+    async fn query(
+        &self,
+        query: &str,
+        parameters: Vec<v3::Value>,
+    ) -> Result<v3::QueryResult, v3::Error> {
+        self.record(query);
+        self.connection.query(query, parameters).await
+    }
+
+    async fn execute_batch(&self, statements: &str) -> anyhow::Result<()> {
+        self.record(statements);
+        self.connection.execute_batch(statements).await
+    }
+
+    async fn changes(&self) -> Result<u64, v3::Error> {
+        self.connection.changes().await
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, v3::Error> {
+        self.connection.last_insert_rowid().await
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.connection.summary()
+    }
+}
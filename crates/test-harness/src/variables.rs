@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use spin_expressions::{async_trait::async_trait, Key, Provider};
+
+use crate::{HostCall, HostCalls};
+
+/// A variables [`Provider`] with fixed values which records the variables
+/// resolved through it.
+#[derive(Debug)]
+pub(crate) struct RecordingProvider {
+    values: HashMap<String, String>,
+    calls: HostCalls,
+}
+
+impl RecordingProvider {
+    pub fn new(values: HashMap<String, String>, calls: HostCalls) -> Self {
+        Self { values, calls }
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        self.calls.record(HostCall::Variable {
+            name: key.as_str().to_owned(),
+        });
+        Ok(self.values.get(key.as_str()).cloned())
+    }
+}
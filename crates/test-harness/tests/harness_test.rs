@@ -0,0 +1,97 @@
+use spin_test_harness::{HostCall, Invocation, TestHarness};
+use spin_world::spin::sqlite::sqlite::Value;
+
+fn harness(component: &str) -> TestHarness {
+    TestHarness::new(test_components::path(component).expect("no such test component"))
+}
+
+fn assert_ok(invocation: &Invocation<http::Response<Vec<u8>>>) {
+    let response = &invocation.output;
+    assert_eq!(
+        response.status(),
+        200,
+        "{}",
+        String::from_utf8_lossy(response.body())
+    );
+}
+
+#[tokio::test]
+async fn key_value_calls_are_recorded() -> anyhow::Result<()> {
+    let app = harness("key-value")
+        .key_value_store("default", [("unrelated", "value")])
+        .load()
+        .await?;
+
+    let invocation = app.invoke_http(http::Request::new(vec![])).await?;
+    assert_ok(&invocation);
+
+    assert_eq!(
+        invocation.host_calls.first(),
+        Some(&HostCall::KeyValue {
+            store: "default".into(),
+            operation: "delete",
+            keys: vec!["bar".into()],
+        })
+    );
+    assert!(invocation.host_calls.contains(&HostCall::KeyValue {
+        store: "default".into(),
+        operation: "set",
+        keys: vec!["qux".into()],
+    }));
+    let entries = app.key_value_entries("default").unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries["unrelated"], b"value");
+    Ok(())
+}
+
+#[tokio::test]
+async fn variables_are_resolved_from_harness() -> anyhow::Result<()> {
+    let app = harness("variables")
+        .variable("variable", "value")
+        .load()
+        .await?;
+
+    let invocation = app.invoke_http(http::Request::new(vec![])).await?;
+    assert_ok(&invocation);
+    assert!(invocation.host_calls.contains(&HostCall::Variable {
+        name: "variable".into()
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_database_persists_across_invocation() -> anyhow::Result<()> {
+    let app = harness("sqlite")
+        .sqlite_database(
+            "default",
+            "CREATE TABLE test_data(key TEXT NOT NULL, value TEXT NOT NULL);",
+        )
+        .load()
+        .await?;
+
+    let invocation = app.invoke_http(http::Request::new(vec![])).await?;
+    assert_ok(&invocation);
+    assert!(invocation.host_calls.iter().any(|call| matches!(
+        call,
+        HostCall::Sqlite { database, statement }
+            if database == "default" && statement.starts_with("INSERT INTO test_data")
+    )));
+
+    let result = app
+        .sqlite_query("default", "SELECT value FROM test_data")
+        .await?;
+    assert_eq!(result.rows.len(), 1);
+    assert!(matches!(&result.rows[0].values[0], Value::Text(value) if value == "my_value"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn undeclared_stores_are_denied() -> anyhow::Result<()> {
+    // The component expects a "default" store, which the harness doesn't provide
+    let app = harness("key-value").load().await?;
+
+    let invocation = app.invoke_http(http::Request::new(vec![])).await?;
+    assert_eq!(invocation.output.status(), 500);
+    assert!(invocation.host_calls.is_empty());
+    Ok(())
+}
@@ -0,0 +1,519 @@
+//! Implementation for the Spin filesystem-watch trigger.
+//!
+//! Each `[[trigger.fs]]` entry watches a host directory and invokes its
+//! component (via the `wasi:cli/run` export) when files matching the
+//! configured glob patterns are created, modified or removed. Bursts of
+//! events are debounced so that a file being written in several chunks
+//! results in a single invocation. Events are dispatched anyway once they
+//! have been pending for the maximum batching window, so that a file which
+//! is written to continuously is still reported.
+
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{event::ModifyKind, event::RenameMode, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use spin_factor_wasi::WasiFactor;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{cli::NoCliArgs, App, Trigger, TriggerApp};
+use tokio::sync::mpsc;
+use tracing::{instrument, Level};
+use wasmtime_wasi::p2::bindings::CommandIndices;
+
+/// The environment variable containing the kind of event (`created`, `modified` or `removed`).
+pub const EVENT_KIND_ENV: &str = "SPIN_FS_EVENT_KIND";
+/// The environment variable containing the changed path, relative to the watched directory.
+pub const EVENT_PATH_ENV: &str = "SPIN_FS_EVENT_PATH";
+/// The environment variable containing the guest path of the changed file.
+///
+/// This is not set for `removed` events.
+pub const EVENT_FILE_ENV: &str = "SPIN_FS_EVENT_FILE";
+/// The guest directory at which a copy of the changed file is mounted.
+///
+/// The directory holds only the changed file, so that the guest can't see any
+/// of the other files in the watched directory.
+pub const EVENT_DIR_GUEST_PATH: &str = "/fs-event";
+
+const DEFAULT_DEBOUNCE_MILLIS: u64 = 250;
+const DEFAULT_MAX_BATCH_MILLIS: u64 = 5000;
+
+pub struct FsTrigger {
+    watches: Vec<WatchConfig>,
+}
+
+/// Filesystem trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Directory to watch, relative to the application directory
+    path: PathBuf,
+    /// Glob patterns (relative to `path`) of files to report; empty means all files
+    #[serde(default)]
+    include: Vec<String>,
+    /// Glob patterns (relative to `path`) of files to ignore
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Kinds of event to report; empty means all kinds
+    #[serde(default)]
+    events: Vec<FsEventKind>,
+    /// Whether to watch subdirectories of `path`
+    #[serde(default = "default_recursive")]
+    recursive: bool,
+    /// How long the watched files must be quiet before events are dispatched
+    debounce_ms: Option<u64>,
+    /// The longest events are held back waiting for the watched files to be quiet
+    max_batch_ms: Option<u64>,
+}
+
+fn default_recursive() -> bool {
+    true
+}
+
+/// The kind of change reported to a component.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FsEventKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl FsEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Modified => "modified",
+            Self::Removed => "removed",
+        }
+    }
+}
+
+impl<F: RuntimeFactors> Trigger<F> for FsTrigger {
+    const TYPE: &'static str = "fs";
+
+    type CliArgs = NoCliArgs;
+
+    type InstanceState = ();
+
+    fn new(_cli_args: Self::CliArgs, app: &App) -> anyhow::Result<Self> {
+        // Relative watch paths are resolved against the app directory if
+        // there is one (i.e. for local apps), else the current directory.
+        let base_dir = match std::env::var(spin_trigger::cli::SPIN_LOCAL_APP_DIR) {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => std::env::current_dir().context("failed to get current directory")?,
+        };
+
+        let watches = app
+            .trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+            .into_iter()
+            .map(|(trigger_id, config)| {
+                WatchConfig::new(&base_dir, config)
+                    .with_context(|| format!("invalid fs trigger {trigger_id:?}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { watches })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        if self.watches.is_empty() {
+            return Ok(());
+        }
+
+        let trigger_app = Arc::new(trigger_app);
+
+        println!("Watching paths:");
+        let mut watcher_tasks = Vec::new();
+        for watch in self.watches {
+            println!("\t{}: [{}]", watch.root.display(), watch.component_id);
+            let watcher = FsWatcher::new(watch, trigger_app.clone())?;
+            watcher_tasks.push(tokio::spawn(watcher.run()));
+        }
+
+        // Wait for any task to complete
+        let (res, _, _) = futures::future::select_all(watcher_tasks).await;
+        res?
+    }
+}
+
+/// A resolved and validated [`TriggerConfig`].
+struct WatchConfig {
+    component_id: String,
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    events: Vec<FsEventKind>,
+    recursive: bool,
+    debounce: Duration,
+    max_batch: Duration,
+}
+
+impl WatchConfig {
+    fn new(base_dir: &Path, config: TriggerConfig) -> anyhow::Result<Self> {
+        let root = base_dir.join(&config.path);
+        anyhow::ensure!(
+            root.is_dir(),
+            "watched path {} is not a directory",
+            root.display()
+        );
+        let include = if config.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(&config.include)?)
+        };
+        let exclude = build_glob_set(&config.exclude)?;
+        let debounce = Duration::from_millis(config.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MILLIS));
+        let max_batch =
+            Duration::from_millis(config.max_batch_ms.unwrap_or(DEFAULT_MAX_BATCH_MILLIS));
+        Ok(Self {
+            component_id: config.component,
+            root,
+            include,
+            exclude,
+            events: config.events,
+            recursive: config.recursive,
+            debounce,
+            // Waiting less than the debounce period would defeat debouncing
+            max_batch: max_batch.max(debounce),
+        })
+    }
+
+    /// Returns true if an event of the given kind on the given path (relative
+    /// to the watched directory) should be reported to the component.
+    fn matches(&self, kind: FsEventKind, relative_path: &Path) -> bool {
+        if !self.events.is_empty() && !self.events.contains(&kind) {
+            return false;
+        }
+        if self.exclude.is_match(relative_path) {
+            return false;
+        }
+        match &self.include {
+            Some(include) => include.is_match(relative_path),
+            None => true,
+        }
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob =
+            Glob::new(pattern).with_context(|| format!("invalid glob pattern {pattern:?}"))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Watches a single directory on behalf of a single component.
+struct FsWatcher<F: RuntimeFactors> {
+    config: WatchConfig,
+    trigger_app: Arc<TriggerApp<FsTrigger, F>>,
+    command_indices: CommandIndices,
+}
+
+impl<F: RuntimeFactors> FsWatcher<F> {
+    fn new(
+        config: WatchConfig,
+        trigger_app: Arc<TriggerApp<FsTrigger, F>>,
+    ) -> anyhow::Result<Self> {
+        let component_id = &config.component_id;
+        let pre = trigger_app.get_instance_pre(component_id)?;
+        let command_indices = CommandIndices::new(pre).with_context(|| {
+            format!("fs trigger component {component_id:?} must export the wasi command interface")
+        })?;
+        Ok(Self {
+            config,
+            trigger_app,
+            command_indices,
+        })
+    }
+
+    async fn run(self) -> anyhow::Result<()> {
+        let root = &self.config.root;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher: RecommendedWatcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                // The receiver only goes away when this watcher is shutting down.
+                _ = tx.send(res);
+            })
+            .context("failed to create filesystem watcher")?;
+        let mode = if self.config.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(root, mode)
+            .with_context(|| format!("failed to watch {}", root.display()))?;
+        tracing::info!(
+            "Watching {} for component {}",
+            root.display(),
+            self.config.component_id
+        );
+
+        let mut pending = PendingEvents::default();
+        loop {
+            let Some(res) = rx.recv().await else {
+                break;
+            };
+            self.record(&mut pending, res);
+
+            // Keep collecting until the watched files have been quiet for
+            // the debounce period, or the batching window has closed.
+            let deadline = tokio::time::Instant::now() + self.config.max_batch;
+            loop {
+                let quiet_until = tokio::time::Instant::now() + self.config.debounce;
+                match tokio::time::timeout_at(quiet_until.min(deadline), rx.recv()).await {
+                    Ok(Some(res)) => self.record(&mut pending, res),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+
+            for (path, kind) in pending.take() {
+                if let Err(err) = self.handle_event(kind, &path).await {
+                    tracing::error!("Error handling fs event for {}: {err:?}", path.display());
+                }
+            }
+        }
+        Err(anyhow!("stopped watching {}", root.display()))
+    }
+
+    fn record(&self, pending: &mut PendingEvents, res: notify::Result<notify::Event>) {
+        let event = match res {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!("Error watching {}: {err}", self.config.root.display());
+                return;
+            }
+        };
+        for (path, kind) in classify_event(&event) {
+            pending.record(path, kind);
+        }
+    }
+
+    #[instrument(name = "spin_trigger_fs.handle_event", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("fs {}", kind.as_str()),
+        fs.event.kind = kind.as_str(),
+        error.type = tracing::field::Empty,
+    ))]
+    async fn handle_event(&self, kind: FsEventKind, path: &Path) -> anyhow::Result<()> {
+        let Ok(relative_path) = path.strip_prefix(&self.config.root) else {
+            return Ok(());
+        };
+        if kind != FsEventKind::Removed && !path.is_file() {
+            // Directories, and files that have already gone away again
+            return Ok(());
+        }
+        if !self.config.matches(kind, relative_path) {
+            return Ok(());
+        }
+        let relative_path = to_slash_path(relative_path)?;
+        tracing::trace!(%relative_path, "Received fs event");
+
+        let component_id = &self.config.component_id;
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "fs",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let mut instance_builder = self.trigger_app.prepare(component_id).await?;
+        let wasi_builder = instance_builder
+            .factor_builder::<WasiFactor>()
+            .context("The fs trigger was configured without the required wasi support")?;
+
+        let mut env = vec![
+            (EVENT_KIND_ENV.to_owned(), kind.as_str().to_owned()),
+            (EVENT_PATH_ENV.to_owned(), relative_path),
+        ];
+        // Kept until the component has run
+        let mut event_dir = None;
+        if kind != FsEventKind::Removed {
+            // Mount (read-only) a directory holding just a copy of the changed
+            // file so that the guest can read it.
+            if let Some(file_name) = path.file_name() {
+                let dir = tempfile::tempdir().context("failed to create fs event directory")?;
+                tokio::fs::copy(path, dir.path().join(file_name))
+                    .await
+                    .with_context(|| format!("failed to copy {}", path.display()))?;
+                wasi_builder.preopened_dir(dir.path(), EVENT_DIR_GUEST_PATH, false)?;
+                let file_name = file_name.to_str().context("file name is not valid UTF-8")?;
+                env.push((
+                    EVENT_FILE_ENV.to_owned(),
+                    format!("{EVENT_DIR_GUEST_PATH}/{file_name}"),
+                ));
+                event_dir = Some(dir);
+            }
+        }
+        wasi_builder.env(env);
+
+        let (instance, mut store) = instance_builder.instantiate(()).await?;
+        let command = self.command_indices.load(&mut store, &instance)?;
+
+        tracing::trace!("Executing fs component {component_id}");
+        let result = command
+            .wasi_cli_run()
+            .call_run(&mut store)
+            .await
+            .or_else(ignore_successful_proc_exit_trap);
+        store.finish_profile().await;
+        if let Err(err) = &result {
+            store.record_failure(err);
+        }
+        drop(event_dir);
+        result
+            .with_context(|| format!("component {component_id} trapped"))?
+            .map_err(|()| anyhow!("component {component_id} returned an error"))
+    }
+}
+
+/// Maps a raw [`notify::Event`] to the paths and kinds reported to components.
+fn classify_event(event: &notify::Event) -> Vec<(PathBuf, FsEventKind)> {
+    use notify::EventKind;
+
+    let kind = match event.kind {
+        EventKind::Create(_) => FsEventKind::Created,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            // Paths are [from, to]
+            let mut events = Vec::with_capacity(2);
+            if let Some(from) = event.paths.first() {
+                events.push((from.clone(), FsEventKind::Removed));
+            }
+            if let Some(to) = event.paths.get(1) {
+                events.push((to.clone(), FsEventKind::Created));
+            }
+            return events;
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FsEventKind::Removed,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FsEventKind::Created,
+        EventKind::Modify(_) => FsEventKind::Modified,
+        EventKind::Remove(_) => FsEventKind::Removed,
+        EventKind::Access(_) | EventKind::Any | EventKind::Other => return Vec::new(),
+    };
+    event.paths.iter().map(|p| (p.clone(), kind)).collect()
+}
+
+/// Events received during a debounce period, coalesced per path.
+#[derive(Default)]
+struct PendingEvents {
+    events: BTreeMap<PathBuf, FsEventKind>,
+}
+
+impl PendingEvents {
+    fn record(&mut self, path: PathBuf, kind: FsEventKind) {
+        use FsEventKind::*;
+
+        match (self.events.get(&path), kind) {
+            // A file that was created and then written is still just created
+            (Some(Created), Modified) => {}
+            // A file that came and went within the debounce period never existed
+            (Some(Created), Removed) => {
+                self.events.remove(&path);
+            }
+            // A file that was replaced has been modified
+            (Some(Removed), Created) => {
+                self.events.insert(path, Modified);
+            }
+            _ => {
+                self.events.insert(path, kind);
+            }
+        }
+    }
+
+    fn take(&mut self) -> BTreeMap<PathBuf, FsEventKind> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// Converts a relative path to a `/`-separated string.
+fn to_slash_path(path: &Path) -> anyhow::Result<String> {
+    let segments = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(s) => s.to_str().context("path is not valid UTF-8"),
+            _ => Err(anyhow!("unexpected path component in {}", path.display())),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(segments.join("/"))
+}
+
+fn ignore_successful_proc_exit_trap(guest_err: anyhow::Error) -> anyhow::Result<Result<(), ()>> {
+    match guest_err
+        .root_cause()
+        .downcast_ref::<wasmtime_wasi::I32Exit>()
+    {
+        Some(trap) => match trap.0 {
+            0 => Ok(Ok(())),
+            _ => Err(guest_err),
+        },
+        None => Err(guest_err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watch_config(include: &[&str], exclude: &[&str], events: &[FsEventKind]) -> WatchConfig {
+        let to_strings = |ss: &[&str]| ss.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        WatchConfig {
+            component_id: "test".into(),
+            root: PathBuf::from("/watched"),
+            include: (!include.is_empty()).then(|| build_glob_set(&to_strings(include)).unwrap()),
+            exclude: build_glob_set(&to_strings(exclude)).unwrap(),
+            events: events.to_vec(),
+            recursive: true,
+            debounce: Duration::ZERO,
+            max_batch: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn filters_by_glob_and_kind() {
+        let config = watch_config(&["**/*.csv"], &["tmp/**"], &[FsEventKind::Created]);
+        assert!(config.matches(FsEventKind::Created, Path::new("a.csv")));
+        assert!(config.matches(FsEventKind::Created, Path::new("in/b.csv")));
+        assert!(!config.matches(FsEventKind::Created, Path::new("a.json")));
+        assert!(!config.matches(FsEventKind::Created, Path::new("tmp/a.csv")));
+        assert!(!config.matches(FsEventKind::Modified, Path::new("a.csv")));
+
+        let config = watch_config(&[], &[], &[]);
+        assert!(config.matches(FsEventKind::Removed, Path::new("anything")));
+    }
+
+    #[test]
+    fn coalesces_pending_events() {
+        let mut pending = PendingEvents::default();
+        pending.record("/w/new".into(), FsEventKind::Created);
+        pending.record("/w/new".into(), FsEventKind::Modified);
+        pending.record("/w/gone".into(), FsEventKind::Created);
+        pending.record("/w/gone".into(), FsEventKind::Removed);
+        pending.record("/w/replaced".into(), FsEventKind::Removed);
+        pending.record("/w/replaced".into(), FsEventKind::Created);
+
+        let events = pending.take().into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (PathBuf::from("/w/new"), FsEventKind::Created),
+                (PathBuf::from("/w/replaced"), FsEventKind::Modified),
+            ]
+        );
+        assert!(pending.take().is_empty());
+    }
+
+    #[test]
+    fn relative_paths_use_forward_slashes() {
+        let path: PathBuf = ["in", "2024", "a.csv"].iter().collect();
+        assert_eq!(to_slash_path(&path).unwrap(), "in/2024/a.csv");
+    }
+}
@@ -20,6 +20,7 @@ macro_rules! http_span {
             "client.address" = $request.headers().get("x-forwarded-for").and_then(|val| val.to_str().ok()),
             // Recorded later
             "error.type" = ::tracing::field::Empty,
+            "exception.stacktrace" = ::tracing::field::Empty,
             "http.response.status_code" = ::tracing::field::Empty,
             "http.route" = ::tracing::field::Empty,
             "otel.name" = ::tracing::field::Empty,
@@ -69,7 +70,14 @@ pub(crate) fn finalize_http_span(
 pub(crate) fn instrument_error(err: &anyhow::Error) {
     let span = tracing::Span::current();
     tracing::event!(target:module_path!(), Level::INFO, error = %err);
-    span.record("error.type", format!("{err:?}"));
+    if spin_core::is_cpu_limit_exceeded(err) {
+        span.record("error.type", "cpu_limit_exceeded");
+    } else {
+        span.record("error.type", format!("{err:?}"));
+    }
+    if let Some(backtrace) = spin_core::guest_backtrace(err) {
+        span.record("exception.stacktrace", backtrace.to_string());
+    }
 }
 
 /// MatchedRoute is used as a response extension to track the route that was matched for OTel
@@ -21,7 +21,7 @@ use clap::Args;
 use serde::Deserialize;
 use spin_app::App;
 use spin_factors::RuntimeFactors;
-use spin_trigger::Trigger;
+use spin_trigger::{reload::AppReloads, Trigger};
 use wasmtime_wasi_http::bindings::http::types::ErrorCode;
 
 pub use server::HttpServer;
@@ -104,6 +104,34 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
         Ok(())
     }
 
+    fn supports_reload(&self) -> bool {
+        true
+    }
+
+    async fn run_with_reloads(
+        self,
+        trigger_app: TriggerApp<F>,
+        mut reloads: AppReloads<Self, F>,
+    ) -> anyhow::Result<()> {
+        let server = self.into_server(trigger_app)?;
+
+        let reloading_server = server.clone();
+        let reload_task = tokio::spawn(async move {
+            while let Some(reload) = reloads.next().await {
+                reload.apply(|trigger_app| {
+                    Self::validate_app(trigger_app.app())?;
+                    reloading_server.reload(trigger_app)?;
+                    tracing::info!("Reloaded app routes");
+                    Ok(())
+                });
+            }
+        });
+
+        let res = server.serve().await;
+        reload_task.abort();
+        res
+    }
+
     fn supported_host_requirements() -> Vec<&'static str> {
         vec![spin_app::locked::SERVICE_CHAINING_KEY]
     }
@@ -3,7 +3,7 @@ use std::{
     future::Future,
     io::{ErrorKind, IsTerminal},
     net::SocketAddr,
-    sync::Arc,
+    sync::{Arc, RwLock},
 };
 
 use anyhow::{bail, Context};
@@ -30,6 +30,7 @@ use spin_http::{
     routes::{RouteMatch, Router},
     trigger::HandlerType,
 };
+use spin_trigger::ConcurrencyLimitExceeded;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     net::TcpListener,
@@ -59,6 +60,13 @@ pub struct HttpServer<F: RuntimeFactors> {
     tls_config: Option<TlsConfig>,
     /// Whether to find a free port if the specified port is already in use.
     find_free_port: bool,
+    /// The app currently being served. Requests hold on to the app they
+    /// started with, so replacing it does not affect requests in flight.
+    app: RwLock<Arc<HttpApp<F>>>,
+}
+
+/// An app being served by an [`HttpServer`], along with its routing information.
+struct HttpApp<F: RuntimeFactors> {
     /// Request router.
     router: Router,
     /// The app being triggered.
@@ -77,92 +85,25 @@ impl<F: RuntimeFactors> HttpServer<F> {
         find_free_port: bool,
         trigger_app: TriggerApp<F>,
     ) -> anyhow::Result<Self> {
-        // This needs to be a vec before building the router to handle duplicate routes
-        let component_trigger_configs = trigger_app
-            .app()
-            .trigger_configs::<HttpTriggerConfig>("http")?
-            .into_iter()
-            .map(|(trigger_id, config)| (config.lookup_key(trigger_id).map(|k| (k, config))))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        // Build router
-        let component_routes = component_trigger_configs
-            .iter()
-            .map(|(key, config)| (key, &config.route));
-        let mut duplicate_routes = Vec::new();
-        let router = Router::build("/", component_routes, Some(&mut duplicate_routes))?;
-        if !duplicate_routes.is_empty() {
-            tracing::error!(
-                "The following component routes are duplicates and will never be used:"
-            );
-            for dup in &duplicate_routes {
-                tracing::error!(
-                    "  {}: {} (duplicate of {})",
-                    dup.replaced_id,
-                    dup.route(),
-                    dup.effective_id,
-                );
-            }
-        }
-        if router.contains_reserved_route() {
-            tracing::error!(
-                "Routes under {} are handled by the Spin runtime and will never be reached",
-                spin_http::WELL_KNOWN_PREFIX
-            );
-        }
-        tracing::trace!(
-            "Constructed router: {:?}",
-            router.routes().collect::<Vec<_>>()
-        );
-
-        // Now that router is built we can merge duplicate routes by component
-        let component_trigger_configs = HashMap::from_iter(component_trigger_configs);
-
-        let component_handler_types = component_trigger_configs
-            .iter()
-            .filter_map(|(key, trigger_config)| match key {
-                spin_http::routes::TriggerLookupKey::Component(component) => Some(
-                    Self::handler_type_for_component(
-                        &trigger_app,
-                        component,
-                        &trigger_config.executor,
-                    )
-                    .map(|ht| (component.clone(), ht)),
-                ),
-                spin_http::routes::TriggerLookupKey::Trigger(_) => None,
-            })
-            .collect::<anyhow::Result<_>>()?;
         Ok(Self {
             listen_addr,
             tls_config,
             find_free_port,
-            router,
-            trigger_app,
-            component_trigger_configs,
-            component_handler_types,
+            app: RwLock::new(Arc::new(HttpApp::new(trigger_app)?)),
         })
     }
 
-    fn handler_type_for_component(
-        trigger_app: &TriggerApp<F>,
-        component_id: &str,
-        executor: &Option<HttpExecutorType>,
-    ) -> anyhow::Result<HandlerType> {
-        let pre = trigger_app.get_instance_pre(component_id)?;
-        let handler_type = match executor {
-            None | Some(HttpExecutorType::Http) => HandlerType::from_instance_pre(pre)?,
-            Some(HttpExecutorType::Wagi(wagi_config)) => {
-                anyhow::ensure!(
-                    wagi_config.entrypoint == "_start",
-                    "Wagi component '{component_id}' cannot use deprecated 'entrypoint' field"
-                );
-                HandlerType::Wagi(
-                    CommandIndices::new(pre)
-                        .context("failed to find wasi command interface for wagi executor")?,
-                )
-            }
-        };
-        Ok(handler_type)
+    /// Replaces the app being served with a reloaded version.
+    ///
+    /// Requests already in progress complete against the previous app.
+    pub fn reload(&self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
+        let app = Arc::new(HttpApp::new(trigger_app)?);
+        *self.app.write().unwrap() = app;
+        Ok(())
+    }
+
+    fn current_app(&self) -> Arc<HttpApp<F>> {
+        self.app.read().unwrap().clone()
     }
 
     /// Serve incoming requests over the provided [`TcpListener`].
@@ -263,6 +204,8 @@ impl<F: RuntimeFactors> HttpServer<F> {
 
         tracing::info!("Processing request on path '{path}'");
 
+        let app = self.current_app();
+
         // Handle well-known spin paths
         if let Some(well_known) = path.strip_prefix(spin_http::WELL_KNOWN_PREFIX) {
             return match well_known {
@@ -270,14 +213,14 @@ impl<F: RuntimeFactors> HttpServer<F> {
                     Response::new(body::full(Bytes::from_static(b"OK"))),
                     path,
                 )),
-                "info" => self.app_info(path),
+                "info" => Self::app_info(&app, path),
                 _ => Self::not_found(NotFoundRouteKind::WellKnown),
             };
         }
 
-        match self.router.route(&path) {
+        match app.router.route(&path) {
             Ok(route_match) => {
-                self.handle_trigger_route(req, route_match, server_scheme, client_addr)
+                self.handle_app_route(&app, req, route_match, server_scheme, client_addr)
                     .await
             }
             Err(_) => Self::not_found(NotFoundRouteKind::Normal(path.to_string())),
@@ -287,13 +230,27 @@ impl<F: RuntimeFactors> HttpServer<F> {
     /// Handles a successful route match.
     pub async fn handle_trigger_route(
         self: &Arc<Self>,
+        req: Request<Body>,
+        route_match: RouteMatch<'_, '_>,
+        server_scheme: Scheme,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<Response<Body>> {
+        let app = self.current_app();
+        self.handle_app_route(&app, req, route_match, server_scheme, client_addr)
+            .await
+    }
+
+    /// Handles a successful route match against the given app.
+    async fn handle_app_route(
+        self: &Arc<Self>,
+        app: &HttpApp<F>,
         mut req: Request<Body>,
         route_match: RouteMatch<'_, '_>,
         server_scheme: Scheme,
         client_addr: SocketAddr,
     ) -> anyhow::Result<Response<Body>> {
         set_req_uri(&mut req, server_scheme.clone())?;
-        let app_id = self
+        let app_id = app
             .trigger_app
             .app()
             .get_metadata(APP_NAME_KEY)?
@@ -308,7 +265,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
             component_id = lookup_key.to_string()
         );
 
-        let trigger_config = self
+        let trigger_config = app
             .component_trigger_configs
             .get(lookup_key)
             .with_context(|| format!("unknown routing destination '{lookup_key}'"))?;
@@ -316,6 +273,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
         match (&trigger_config.component, &trigger_config.static_response) {
             (Some(component), None) => {
                 self.respond_wasm_component(
+                    app,
                     req,
                     route_match,
                     server_scheme,
@@ -334,8 +292,10 @@ impl<F: RuntimeFactors> HttpServer<F> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn respond_wasm_component(
         self: &Arc<Self>,
+        app: &HttpApp<F>,
         req: Request<Body>,
         route_match: RouteMatch<'_, '_>,
         server_scheme: Scheme,
@@ -343,7 +303,14 @@ impl<F: RuntimeFactors> HttpServer<F> {
         component_id: &str,
         executor: &Option<HttpExecutorType>,
     ) -> anyhow::Result<Response<Body>> {
-        let mut instance_builder = self.trigger_app.prepare(component_id)?;
+        let mut instance_builder = match app.trigger_app.prepare(component_id).await {
+            Ok(instance_builder) => instance_builder,
+            Err(err) if err.is::<ConcurrencyLimitExceeded>() => {
+                instrument_error(&err);
+                return Self::service_unavailable(route_match.raw_route());
+            }
+            Err(err) => return Err(err),
+        };
 
         // Set up outbound HTTP request origin and service chaining
         // The outbound HTTP factor is required since both inbound and outbound wasi HTTP
@@ -359,7 +326,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
         outbound_http.set_request_interceptor(OutboundHttpInterceptor::new(self.clone()))?;
 
         // Prepare HTTP executor
-        let handler_type = self
+        let handler_type = app
             .component_handler_types
             .get(component_id)
             .with_context(|| format!("unknown component ID {component_id:?}"))?;
@@ -401,7 +368,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 route_match.raw_route(),
             )),
             Err(err) => {
-                tracing::error!("Error processing request: {err:?}");
+                tracing::error!(component_id, "Error processing request: {err:?}");
                 instrument_error(&err);
                 Self::internal_error(None, route_match.raw_route())
             }
@@ -427,8 +394,8 @@ impl<F: RuntimeFactors> HttpServer<F> {
     }
 
     /// Returns spin status information.
-    fn app_info(&self, route: String) -> anyhow::Result<Response<Body>> {
-        let info = AppInfo::new(self.trigger_app.app());
+    fn app_info(app: &HttpApp<F>, route: String) -> anyhow::Result<Response<Body>> {
+        let info = AppInfo::new(app.trigger_app.app());
         let body = serde_json::to_vec_pretty(&info)?;
         Ok(MatchedRoute::with_response_extension(
             Response::builder()
@@ -456,6 +423,16 @@ impl<F: RuntimeFactors> HttpServer<F> {
         ))
     }
 
+    /// Creates an HTTP 503 response.
+    fn service_unavailable(route: impl Into<String>) -> anyhow::Result<Response<Body>> {
+        Ok(MatchedRoute::with_response_extension(
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(body::empty())?,
+            route,
+        ))
+    }
+
     /// Creates an HTTP 404 response.
     fn not_found(kind: NotFoundRouteKind) -> anyhow::Result<Response<Body>> {
         use std::sync::atomic::{AtomicBool, Ordering};
@@ -528,11 +505,12 @@ impl<F: RuntimeFactors> HttpServer<F> {
         terminal::step!("\nServing", "{base_url}");
         tracing::info!("Serving {base_url}");
 
+        let app = self.current_app();
         println!("Available Routes:");
-        for (route, key) in self.router.routes() {
+        for (route, key) in app.router.routes() {
             println!("  {key}: {base_url}{route}");
             if let spin_http::routes::TriggerLookupKey::Component(component_id) = &key {
-                if let Some(component) = self.trigger_app.app().get_component(component_id) {
+                if let Some(component) = app.trigger_app.app().get_component(component_id) {
                     if let Some(description) = component.get_metadata(APP_DESCRIPTION_KEY)? {
                         println!("    {description}");
                     }
@@ -543,6 +521,94 @@ impl<F: RuntimeFactors> HttpServer<F> {
     }
 }
 
+impl<F: RuntimeFactors> HttpApp<F> {
+    fn new(trigger_app: TriggerApp<F>) -> anyhow::Result<Self> {
+        // This needs to be a vec before building the router to handle duplicate routes
+        let component_trigger_configs = trigger_app
+            .app()
+            .trigger_configs::<HttpTriggerConfig>("http")?
+            .into_iter()
+            .map(|(trigger_id, config)| config.lookup_key(trigger_id).map(|k| (k, config)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Build router
+        let component_routes = component_trigger_configs
+            .iter()
+            .map(|(key, config)| (key, &config.route));
+        let mut duplicate_routes = Vec::new();
+        let router = Router::build("/", component_routes, Some(&mut duplicate_routes))?;
+        if !duplicate_routes.is_empty() {
+            tracing::error!(
+                "The following component routes are duplicates and will never be used:"
+            );
+            for dup in &duplicate_routes {
+                tracing::error!(
+                    "  {}: {} (duplicate of {})",
+                    dup.replaced_id,
+                    dup.route(),
+                    dup.effective_id,
+                );
+            }
+        }
+        if router.contains_reserved_route() {
+            tracing::error!(
+                "Routes under {} are handled by the Spin runtime and will never be reached",
+                spin_http::WELL_KNOWN_PREFIX
+            );
+        }
+        tracing::trace!(
+            "Constructed router: {:?}",
+            router.routes().collect::<Vec<_>>()
+        );
+
+        // Now that router is built we can merge duplicate routes by component
+        let component_trigger_configs = HashMap::from_iter(component_trigger_configs);
+
+        let component_handler_types = component_trigger_configs
+            .iter()
+            .filter_map(|(key, trigger_config)| match key {
+                spin_http::routes::TriggerLookupKey::Component(component) => Some(
+                    Self::handler_type_for_component(
+                        &trigger_app,
+                        component,
+                        &trigger_config.executor,
+                    )
+                    .map(|ht| (component.clone(), ht)),
+                ),
+                spin_http::routes::TriggerLookupKey::Trigger(_) => None,
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            router,
+            trigger_app,
+            component_trigger_configs,
+            component_handler_types,
+        })
+    }
+
+    fn handler_type_for_component(
+        trigger_app: &TriggerApp<F>,
+        component_id: &str,
+        executor: &Option<HttpExecutorType>,
+    ) -> anyhow::Result<HandlerType> {
+        let pre = trigger_app.get_instance_pre(component_id)?;
+        let handler_type = match executor {
+            None | Some(HttpExecutorType::Http) => HandlerType::from_instance_pre(pre)?,
+            Some(HttpExecutorType::Wagi(wagi_config)) => {
+                anyhow::ensure!(
+                    wagi_config.entrypoint == "_start",
+                    "Wagi component '{component_id}' cannot use deprecated 'entrypoint' field"
+                );
+                HandlerType::Wagi(
+                    CommandIndices::new(pre)
+                        .context("failed to find wasi command interface for wagi executor")?,
+                )
+            }
+        };
+        Ok(handler_type)
+    }
+}
+
 /// The incoming request's scheme and authority
 ///
 /// The incoming request's URI is relative to the server, so we need to set the scheme and authority.
@@ -35,6 +35,7 @@ impl HttpExecutor for SpinHttpExecutor {
 
         tracing::trace!("Executing request using the Spin executor for component {component_id}");
 
+        let recycler = instance_builder.recycler();
         let (instance, mut store) = instance_builder.instantiate(()).await?;
 
         let headers = prepare_request_headers(&req, route_match, client_addr)?;
@@ -80,7 +81,19 @@ impl HttpExecutor for SpinHttpExecutor {
             body: Some(bytes),
         };
 
-        let (resp,) = func.call_async(&mut store, (req,)).await?;
+        let result = func.call_async(&mut store, (req,)).await;
+        store.finish_profile().await;
+        let (resp,) = match result {
+            Ok(resp) => resp,
+            Err(err) => {
+                store.record_failure(&err);
+                return Err(err);
+            }
+        };
+        if recycler.is_enabled() {
+            func.post_return_async(&mut store).await?;
+            recycler.recycle(instance, store);
+        }
 
         if resp.status < 100 || resp.status > 600 {
             tracing::error!("malformed HTTP status code");
@@ -94,12 +94,16 @@ impl HttpExecutor for WagiHttpExecutor<'_> {
         let command = self.indices.load(&mut store, &instance)?;
 
         tracing::trace!("Calling Wasm entry point");
-        if let Err(()) = command
+        let result = command
             .wasi_cli_run()
             .call_run(&mut store)
             .await
-            .or_else(ignore_successful_proc_exit_trap)?
-        {
+            .or_else(ignore_successful_proc_exit_trap);
+        store.finish_profile().await;
+        if let Err(err) = &result {
+            store.record_failure(err);
+        }
+        if let Err(()) = result? {
             tracing::error!("Wagi main function returned unsuccessful result");
         }
         tracing::info!("Wagi execution complete");
@@ -38,6 +38,7 @@ impl HttpExecutor for WasiHttpExecutor<'_> {
 
         tracing::trace!("Executing request using the Wasi executor for component {component_id}");
 
+        let recycler = instance_builder.recycler();
         let (instance, mut store) = instance_builder.instantiate(()).await?;
 
         let headers = prepare_request_headers(&req, route_match, client_addr)?;
@@ -126,6 +127,13 @@ impl HttpExecutor for WasiHttpExecutor<'_> {
                     "wasi-http memory consumed: {}",
                     store.data().core_state().memory_consumed()
                 );
+                store.finish_profile().await;
+                match &result {
+                    // The guest has returned from the handler. Any resources it
+                    // left open are dropped when the instance is next reset.
+                    Ok(()) => recycler.recycle(instance, store),
+                    Err(err) => store.record_failure(err),
+                }
 
                 result
             }
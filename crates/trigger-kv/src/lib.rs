@@ -0,0 +1,313 @@
+//! Implementation for the Spin key-value trigger.
+//!
+//! Each `[[trigger.kv]]` entry watches a key-value store for changes to keys
+//! starting with an optional prefix and invokes its component (via the
+//! `spin:key-value/inbound-key-value` export) for each key which is set or
+//! deleted. The store must support watching keys (see
+//! [`spin_factor_key_value::Capabilities::watch`]).
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use futures::StreamExt;
+use serde::Deserialize;
+use spin_factor_key_value::{KeyChange, KeyChangeKind, KeyChanges, KeyValueFactor};
+use spin_factors::RuntimeFactors;
+use spin_trigger::retry::{Invocation, RetryConfig, RetryPolicy};
+use spin_trigger::{cli::NoCliArgs, App, Trigger, TriggerApp};
+use spin_world::exports::spin::key_value::inbound_key_value::{self, ChangeKind};
+use tracing::{instrument, Level};
+
+pub struct KvTrigger {
+    watches: Vec<WatchConfig>,
+}
+
+/// Key-value trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Label of the store to watch
+    store: String,
+    /// Prefix of the keys to report; empty means all keys
+    #[serde(default)]
+    prefix: String,
+    /// Kinds of change to report; empty means all kinds
+    #[serde(default)]
+    events: Vec<KvEventKind>,
+    /// Optionally retry failed invocations
+    retry: Option<RetryConfig>,
+}
+
+/// The kind of change reported to a component.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum KvEventKind {
+    Set,
+    Delete,
+}
+
+impl KvEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Set => "set",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl From<KeyChangeKind> for KvEventKind {
+    fn from(kind: KeyChangeKind) -> Self {
+        match kind {
+            KeyChangeKind::Set => Self::Set,
+            KeyChangeKind::Delete => Self::Delete,
+        }
+    }
+}
+
+impl From<KvEventKind> for ChangeKind {
+    fn from(kind: KvEventKind) -> Self {
+        match kind {
+            KvEventKind::Set => Self::Set,
+            KvEventKind::Delete => Self::Delete,
+        }
+    }
+}
+
+impl<F: RuntimeFactors> Trigger<F> for KvTrigger {
+    const TYPE: &'static str = "kv";
+
+    type CliArgs = NoCliArgs;
+
+    type InstanceState = ();
+
+    fn new(_cli_args: Self::CliArgs, app: &App) -> anyhow::Result<Self> {
+        let watches = app
+            .trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+            .into_iter()
+            .map(|(_, config)| WatchConfig::from(config))
+            .collect();
+        Ok(Self { watches })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        if self.watches.is_empty() {
+            return Ok(());
+        }
+
+        let key_value = trigger_app
+            .configured_app()
+            .app_state::<KeyValueFactor>()
+            .context("KvTrigger depends on KeyValueFactor")?;
+
+        // Start watching every store before invoking any components
+        let mut watchers = Vec::new();
+        for watch in self.watches {
+            let component_id = &watch.component_id;
+            let label = &watch.store;
+            let store = key_value.get_store(label).await.with_context(|| {
+                format!("unknown key-value store {label:?} for kv trigger component {component_id}")
+            })?;
+            anyhow::ensure!(
+                store.capabilities().watch,
+                "key-value store {label:?} does not support watching keys, as required by kv trigger component {component_id}"
+            );
+            let changes = store
+                .watch(&watch.prefix)
+                .await
+                .with_context(|| format!("failed to watch key-value store {label:?}"))?;
+            let retry_policy = match &watch.retry {
+                Some(retry) => RetryPolicy::from_config(retry, trigger_app.configured_app())
+                    .await
+                    .with_context(|| {
+                        format!("invalid kv trigger retry config for component {component_id}")
+                    })?,
+                None => RetryPolicy::default(),
+            };
+            watchers.push((watch, changes, retry_policy));
+        }
+
+        let trigger_app = Arc::new(trigger_app);
+
+        println!("Watching key-value stores:");
+        let mut watcher_tasks = Vec::new();
+        for (config, changes, retry_policy) in watchers {
+            println!(
+                "\t{}/{}*: [{}]",
+                config.store, config.prefix, config.component_id
+            );
+            let watcher = KvWatcher {
+                config,
+                retry_policy,
+                trigger_app: trigger_app.clone(),
+            };
+            watcher_tasks.push(tokio::spawn(watcher.run(changes)));
+        }
+
+        // Wait for any task to complete
+        let (res, _, _) = futures::future::select_all(watcher_tasks).await;
+        res?
+    }
+}
+
+/// A resolved [`TriggerConfig`].
+struct WatchConfig {
+    component_id: String,
+    store: String,
+    prefix: String,
+    events: Vec<KvEventKind>,
+    retry: Option<RetryConfig>,
+}
+
+impl From<TriggerConfig> for WatchConfig {
+    fn from(config: TriggerConfig) -> Self {
+        Self {
+            component_id: config.component,
+            store: config.store,
+            prefix: config.prefix,
+            events: config.events,
+            retry: config.retry,
+        }
+    }
+}
+
+impl WatchConfig {
+    /// Returns true if a change of the given kind should be reported to the component.
+    ///
+    /// The store only reports changes to keys with the configured prefix.
+    fn matches(&self, kind: KvEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// Watches a single store on behalf of a single component.
+struct KvWatcher<F: RuntimeFactors> {
+    config: WatchConfig,
+    retry_policy: RetryPolicy,
+    trigger_app: Arc<TriggerApp<KvTrigger, F>>,
+}
+
+impl<F: RuntimeFactors> KvWatcher<F> {
+    async fn run(self, mut changes: KeyChanges) -> anyhow::Result<()> {
+        // Changes are handled one at a time so that the component sees them in order
+        while let Some(change) = changes.next().await {
+            if let Err(err) = self.handle_change(change).await {
+                tracing::error!(
+                    "Error handling change to key-value store {}: {err:?}",
+                    self.config.store
+                );
+            }
+        }
+        Err(anyhow!(
+            "stopped watching key-value store {}",
+            self.config.store
+        ))
+    }
+
+    #[instrument(name = "spin_trigger_kv.handle_change", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("kv {}", KvEventKind::from(change.kind).as_str()),
+        kv.store = %self.config.store,
+        kv.event.kind = KvEventKind::from(change.kind).as_str(),
+        error.type = tracing::field::Empty,
+    ))]
+    async fn handle_change(&self, change: KeyChange) -> anyhow::Result<()> {
+        let kind = KvEventKind::from(change.kind);
+        if !self.config.matches(kind) {
+            return Ok(());
+        }
+        tracing::trace!(key = %change.key, "Received key-value change");
+
+        let component_id = self.config.component_id.as_str();
+        let invocation = Invocation {
+            trigger_type: "kv",
+            component_id,
+            payload: change.key.as_bytes(),
+        };
+        self.retry_policy
+            .run(invocation, || self.dispatch_handler(&change.key, kind))
+            .await
+    }
+
+    async fn dispatch_handler(&self, key: &str, kind: KvEventKind) -> anyhow::Result<()> {
+        let component_id = &self.config.component_id;
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "kv",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let instance_builder = self.trigger_app.prepare(component_id).await?;
+        let recycler = instance_builder.recycler();
+        let (instance, mut store) = instance_builder.instantiate(()).await?;
+
+        let pre = instance.instance_pre(&store);
+        let guest_indices = inbound_key_value::GuestIndices::new(&pre).with_context(|| {
+            format!("kv trigger component {component_id:?} must export spin:key-value/inbound-key-value")
+        })?;
+        let guest = guest_indices.load(&mut store, &instance)?;
+
+        tracing::trace!("Executing kv component {component_id}");
+        let result = guest
+            .call_handle_change(&mut store, &self.config.store, key, kind.into())
+            .await;
+        store.finish_profile().await;
+        if let Err(err) = &result {
+            store.record_failure(err);
+        }
+        result
+            .with_context(|| format!("component {component_id} trapped"))?
+            .map_err(|err| anyhow!("component {component_id} returned an error: {err}"))?;
+        recycler.recycle(instance, store);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger_config(toml: &str) -> TriggerConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn filters_by_kind() {
+        let config: WatchConfig = trigger_config(
+            r#"
+            component = "test"
+            store = "default"
+            prefix = "orders/"
+            events = ["delete"]
+            "#,
+        )
+        .into();
+        assert_eq!(config.prefix, "orders/");
+        assert!(config.matches(KvEventKind::Delete));
+        assert!(!config.matches(KvEventKind::Set));
+
+        let config: WatchConfig = trigger_config(
+            r#"
+            component = "test"
+            store = "default"
+            "#,
+        )
+        .into();
+        assert_eq!(config.prefix, "");
+        assert!(config.matches(KvEventKind::Set));
+        assert!(config.matches(KvEventKind::Delete));
+    }
+
+    #[test]
+    fn rejects_unknown_events() {
+        let err = toml::from_str::<TriggerConfig>(
+            r#"
+            component = "test"
+            store = "default"
+            events = ["created"]
+            "#,
+        );
+        assert!(err.is_err());
+    }
+}
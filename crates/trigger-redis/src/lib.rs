@@ -6,8 +6,10 @@ use redis::{Client, Msg};
 use serde::Deserialize;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::RuntimeFactors;
+use spin_trigger::retry::{Invocation, RetryConfig, RetryPolicy};
 use spin_trigger::{cli::NoCliArgs, App, Trigger, TriggerApp};
 use spin_world::exports::fermyon::spin::inbound_redis;
+use tokio::sync::Semaphore;
 use tracing::{instrument, Level};
 
 pub struct RedisTrigger;
@@ -29,6 +31,8 @@ struct TriggerConfig {
     channel: String,
     /// Optionally override address for trigger
     address: Option<String>,
+    /// Optionally retry failed invocations
+    retry: Option<RetryConfig>,
 }
 
 impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
@@ -61,7 +65,7 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
                 format!("failed to resolve redis trigger default address {default_address_expr:?}")
             })?;
 
-        // Maps <server address> -> <channel> -> <component IDs>
+        // Maps <server address> -> <channel> -> <components>
         let mut server_channel_components: HashMap<String, ChannelComponents> = HashMap::new();
 
         // Resolve trigger configs before starting any subscribers
@@ -92,12 +96,24 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
                     )
                 })?;
 
+            let retry_policy = match &config.retry {
+                Some(retry) => RetryPolicy::from_config(retry, trigger_app.configured_app())
+                    .await
+                    .with_context(|| {
+                        format!("invalid redis trigger retry config for component {component_id}")
+                    })?,
+                None => RetryPolicy::default(),
+            };
+
             server_channel_components
                 .entry(address)
                 .or_default()
                 .entry(channel)
                 .or_default()
-                .push(component_id);
+                .push(ChannelComponent {
+                    component_id,
+                    retry_policy,
+                });
         }
 
         // Start subscriber(s)
@@ -105,7 +121,7 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
         let mut subscriber_tasks = Vec::new();
         for (address, channel_components) in server_channel_components {
             let subscriber = Subscriber::new(address, trigger_app.clone(), channel_components)?;
-            let task = tokio::spawn(subscriber.run_listener());
+            let task = tokio::spawn(Arc::new(subscriber).run_listener());
             subscriber_tasks.push(task);
         }
 
@@ -115,14 +131,25 @@ impl<F: RuntimeFactors> Trigger<F> for RedisTrigger {
     }
 }
 
-/// Maps <channel> -> <component IDs>
-type ChannelComponents = HashMap<String, Vec<String>>;
+/// The maximum number of messages which may be retried that a subscriber handles at once.
+const MAX_CONCURRENT_RETRYING_MESSAGES: usize = 32;
+
+/// Maps <channel> -> <components>
+type ChannelComponents = HashMap<String, Vec<ChannelComponent>>;
+
+/// A component subscribed to a channel.
+struct ChannelComponent {
+    component_id: String,
+    retry_policy: RetryPolicy,
+}
 
 /// Subscribes to channels from a single Redis server.
 struct Subscriber<F: RuntimeFactors> {
     client: Client,
     trigger_app: Arc<TriggerApp<RedisTrigger, F>>,
     channel_components: ChannelComponents,
+    /// Limits the number of messages which may be retried that are handled at once.
+    retrying_messages: Arc<Semaphore>,
 }
 
 impl<F: RuntimeFactors> Subscriber<F> {
@@ -136,10 +163,22 @@ impl<F: RuntimeFactors> Subscriber<F> {
             client,
             trigger_app,
             channel_components,
+            retrying_messages: Arc::new(Semaphore::new(MAX_CONCURRENT_RETRYING_MESSAGES)),
         })
     }
 
-    async fn run_listener(self) -> anyhow::Result<()> {
+    /// Returns true if a message from `channel` may be retried by any of its components.
+    fn retries(&self, channel: &str) -> bool {
+        self.channel_components
+            .get(channel)
+            .is_some_and(|components| {
+                components
+                    .iter()
+                    .any(|component| component.retry_policy.max_attempts() > 1)
+            })
+    }
+
+    async fn run_listener(self: Arc<Self>) -> anyhow::Result<()> {
         let server_addr = &self.client.get_connection_info().addr;
 
         tracing::info!("Connecting to Redis server at {server_addr}");
@@ -157,14 +196,34 @@ impl<F: RuntimeFactors> Subscriber<F> {
             pubsub.subscribe(channel).await.with_context(|| {
                 format!("Redis trigger failed to subscribe to channel {channel:?} on {server_addr}")
             })?;
-            println!("\t{server_addr}/{channel}: [{}]", components.join(","));
+            let component_ids = components
+                .iter()
+                .map(|c| c.component_id.as_str())
+                .collect::<Vec<_>>();
+            println!("\t{server_addr}/{channel}: [{}]", component_ids.join(","));
         }
 
         let mut message_stream = pubsub.on_message();
         while let Some(msg) = message_stream.next().await {
-            if let Err(err) = self.handle_message(msg).await {
-                tracing::error!("Error handling message from {server_addr}: {err}");
+            if !self.retries(msg.get_channel_name()) {
+                // Messages are handled in order unless they may be retried
+                if let Err(err) = self.handle_message(msg).await {
+                    tracing::error!("Error handling message from {server_addr}: {err:?}");
+                }
+                continue;
             }
+            // Handle messages which may be retried in their own tasks, so that
+            // retry backoff doesn't hold up reading the messages after them.
+            // Waiting for a permit limits how many are handled at once.
+            let permit = self.retrying_messages.clone().acquire_owned().await?;
+            let subscriber = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = subscriber.handle_message(msg).await {
+                    let server_addr = &subscriber.client.get_connection_info().addr;
+                    tracing::error!("Error handling message from {server_addr}: {err:?}");
+                }
+                drop(permit);
+            });
         }
         Err(anyhow::anyhow!("disconnected from {server_addr}"))
     }
@@ -173,20 +232,29 @@ impl<F: RuntimeFactors> Subscriber<F> {
         otel.name = format!("{} receive", msg.get_channel_name()),
         otel.kind = "consumer",
         messaging.operation = "receive",
-        messaging.system = "redis"
+        messaging.system = "redis",
+        error.type = tracing::field::Empty,
     ))]
     async fn handle_message(&self, msg: Msg) -> anyhow::Result<()> {
         let server_addr = &self.client.get_connection_info().addr;
         let channel = msg.get_channel_name();
         tracing::trace!(%server_addr, %channel, "Received message");
 
-        let Some(component_ids) = self.channel_components.get(channel) else {
+        let Some(components) = self.channel_components.get(channel) else {
             anyhow::bail!("message from unexpected channel {channel:?}");
         };
 
-        let dispatch_futures = component_ids.iter().map(|component_id| {
+        let dispatch_futures = components.iter().map(|component| {
+            let component_id = component.component_id.as_str();
             tracing::trace!("Executing Redis component {component_id}");
-            self.dispatch_handler(&msg, component_id)
+            let invocation = Invocation {
+                trigger_type: "redis",
+                component_id,
+                payload: msg.get_payload_bytes(),
+            };
+            component
+                .retry_policy
+                .run(invocation, || self.dispatch_handler(&msg, component_id))
                 .inspect_err(move |err| {
                     tracing::info!("Component {component_id} handler failed: {err}");
                 })
@@ -204,11 +272,9 @@ impl<F: RuntimeFactors> Subscriber<F> {
             component_id = component_id
         );
 
-        let (instance, mut store) = self
-            .trigger_app
-            .prepare(component_id)?
-            .instantiate(())
-            .await?;
+        let instance_builder = self.trigger_app.prepare(component_id).await?;
+        let recycler = instance_builder.recycler();
+        let (instance, mut store) = instance_builder.instantiate(()).await?;
 
         let pre = instance.instance_pre(&store);
         let guest_indices = inbound_redis::GuestIndices::new(&pre)?;
@@ -216,9 +282,15 @@ impl<F: RuntimeFactors> Subscriber<F> {
 
         let payload = msg.get_payload_bytes().to_vec();
 
-        guest
-            .call_handle_message(&mut store, &payload)
-            .await?
-            .context("Redis handler returned an error")
+        let result = guest.call_handle_message(&mut store, &payload).await;
+        store.finish_profile().await;
+        if let Err(err) = &result {
+            store.record_failure(err);
+        }
+        result
+            .with_context(|| format!("component {component_id} trapped"))?
+            .context("Redis handler returned an error")?;
+        recycler.recycle(instance, store);
+        Ok(())
     }
 }
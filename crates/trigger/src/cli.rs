@@ -1,26 +1,46 @@
+mod admin;
+pub(crate) mod component_limits;
+mod core_dump;
 mod initial_kv_setter;
 mod launch_metadata;
 mod max_instance_memory;
+mod profiler;
+mod reloader;
+mod sqlite_migrations;
 mod sqlite_statements;
 mod stdio;
 mod summary;
 
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::{future::Future, sync::Arc};
 
 use anyhow::{Context, Result};
 use clap::{Args, IntoApp, Parser};
+use futures::FutureExt as _;
 use spin_app::App;
 use spin_common::sloth;
 use spin_common::ui::quoted_path;
 use spin_common::url::parse_file_url;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{ComponentLoader, FactorsExecutor};
-
-use crate::{loader::ComponentLoader as ComponentLoaderImpl, Trigger, TriggerApp};
+use tokio::sync::mpsc;
+
+use crate::{
+    loader::{ComponentLoader as ComponentLoaderImpl, PRECOMPILED_DIR},
+    reload::AppReloads,
+    Trigger, TriggerApp,
+};
+pub use admin::{AdminHooks, AdminState};
+pub use component_limits::ComponentLimitsHook;
+pub use core_dump::CoreDumpHook;
 pub use initial_kv_setter::InitialKvSetterHook;
 pub use launch_metadata::LaunchMetadata;
 pub use max_instance_memory::MaxInstanceMemoryHook;
+pub use profiler::GuestProfilerHook;
+pub use reloader::ReloadRequest;
+use reloader::Reloader;
+pub use sqlite_migrations::SqliteMigrationsHook;
 pub use sqlite_statements::SqlStatementExecutorHook;
 use stdio::FollowComponents;
 pub use stdio::StdioLoggingExecutorHooks;
@@ -118,6 +138,13 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     #[clap(long)]
     pub state_dir: Option<String>,
 
+    /// IP address and port on which to serve the admin API (health and
+    /// readiness checks, loaded components and invocation counts). If not
+    /// set, the admin API is disabled. Reloads are only accepted from
+    /// loopback addresses.
+    #[clap(long = "admin-listen", env = "SPIN_ADMIN_LISTEN")]
+    pub admin_listen: Option<SocketAddr>,
+
     #[clap(flatten)]
     pub trigger_args: T::CliArgs,
 
@@ -129,6 +156,11 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
 
     #[clap(long = "launch-metadata-only", hide = true)]
     pub launch_metadata_only: bool,
+
+    /// Compile the app's components into the precompiled directory, and exit
+    /// without running the app.
+    #[clap(long = "precompile-only", hide = true)]
+    pub precompile_only: bool,
 }
 
 /// Configuration options that are common to all triggers.
@@ -183,14 +215,7 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
         let follow_components = self.follow_components();
 
         // Load App
-        let app = {
-            let path = parse_file_url(&locked_url)?;
-            let contents = std::fs::read(&path)
-                .with_context(|| format!("failed to read manifest at {}", quoted_path(&path)))?;
-            let locked =
-                serde_json::from_slice(&contents).context("failed to parse app lock file JSON")?;
-            App::new(locked_url, locked)
-        };
+        let app = load_locked_app(&locked_url)?;
 
         // Validate required host features
         if let Err(unmet) = app.ensure_needs_only(T::TYPE, &T::supported_host_requirements()) {
@@ -232,15 +257,86 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
             truncate_logs: self.truncate_logs,
         };
 
-        let run_fut = builder
-            .run(
-                app,
-                common_options,
-                self.builder_args,
-                &ComponentLoaderImpl::new(),
-            )
+        let mut loader = ComponentLoaderImpl::new();
+        if let Some(local_app_dir) = &local_app_dir {
+            loader.set_precompiled_dir(Path::new(local_app_dir).join(PRECOMPILED_DIR));
+        }
+
+        if self.precompile_only {
+            loader.enable_writing_precompiled_components();
+            let dir = local_app_dir
+                .as_ref()
+                .map(|dir| Path::new(dir).join(PRECOMPILED_DIR))
+                .context("only local apps can be precompiled")?;
+            let count = builder
+                .precompile_app(app, &common_options, &self.builder_args, &loader)
+                .await?;
+            println!("Precompiled {count} component(s) to {}", quoted_path(dir));
+            return Ok(());
+        }
+
+        let admin_state = match self.admin_listen {
+            Some(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("failed to bind admin server to {addr}"))?;
+                println!(
+                    "Serving admin API on http://{}",
+                    listener.local_addr().unwrap_or(addr)
+                );
+                let admin_state = Arc::new(AdminState::default());
+                let serve_fut = admin_state.clone().serve(listener);
+                tokio::spawn(async move {
+                    if let Err(err) = serve_fut.await {
+                        tracing::error!("Admin server failed: {err:#}");
+                    }
+                });
+                builder.admin_state(admin_state.clone());
+                Some(admin_state)
+            }
+            None => None,
+        };
+
+        let trigger_app = builder
+            .build_app(app, &common_options, &self.builder_args, &loader)
             .await?;
 
+        // Reloads may be requested via the admin API, or with SIGHUP if the
+        // trigger supports them; otherwise SIGHUP keeps its default behaviour
+        let (reload_sender, reload_requests) = mpsc::unbounded_channel();
+        if let Some(admin_state) = &admin_state {
+            admin_state.enable_reload(reload_sender.clone());
+        }
+
+        let trigger = builder.trigger;
+        let (run_fut, app_sender) = if trigger.supports_reload() {
+            reload_on_hangup(reload_sender)?;
+            let (app_sender, reloads) = AppReloads::channel();
+            let run_fut = trigger.run_with_reloads(trigger_app.clone(), reloads);
+            (run_fut.left_future(), Some(app_sender))
+        } else {
+            (trigger.run(trigger_app.clone()).right_future(), None)
+        };
+        let reloader = Reloader::<T, B, _>::new(
+            locked_url,
+            common_options,
+            self.builder_args,
+            loader,
+            trigger_app,
+            app_sender,
+            builder.engine_config.consumes_fuel(),
+        );
+        let run_fut = async move {
+            tokio::select! {
+                res = run_fut => res,
+                () = reloader.run(reload_requests) => unreachable!("reloader never completes"),
+            }
+        };
+
+        if let Some(admin_state) = &admin_state {
+            admin_state.set_ready();
+        }
+
         let (abortable, abort_handle) = futures::future::abortable(run_fut);
         ctrlc::set_handler(move || abort_handle.abort())?;
         match abortable.await {
@@ -271,6 +367,37 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
     }
 }
 
+/// Loads the locked app at the given `file:` URL.
+fn load_locked_app(locked_url: &str) -> Result<App> {
+    let path = parse_file_url(locked_url)?;
+    let contents = std::fs::read(&path)
+        .with_context(|| format!("failed to read manifest at {}", quoted_path(&path)))?;
+    let locked = serde_json::from_slice(&contents).context("failed to parse app lock file JSON")?;
+    Ok(App::new(locked_url, locked))
+}
+
+/// Sends a reload request whenever the process receives SIGHUP.
+#[cfg(unix)]
+fn reload_on_hangup(requests: mpsc::UnboundedSender<ReloadRequest>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangups = signal(SignalKind::hangup()).context("failed to listen for SIGHUP")?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            // The reloader reports the outcome itself, so nobody needs to wait for it here
+            let (respond, _) = tokio::sync::oneshot::channel();
+            if requests.send(respond).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reload_on_hangup(_requests: mpsc::UnboundedSender<ReloadRequest>) -> Result<()> {
+    Ok(())
+}
+
 const SLOTH_WARNING_DELAY_MILLIS: u64 = 1250;
 
 fn warn_if_wasm_build_slothful() -> sloth::SlothGuard {
@@ -298,6 +425,7 @@ fn help_heading<T: Trigger<F>, F: RuntimeFactors>() -> Option<&'static str> {
 /// A builder for a [`TriggerApp`].
 pub struct TriggerAppBuilder<T, B> {
     engine_config: spin_core::Config,
+    admin_state: Option<Arc<AdminState>>,
     pub trigger: T,
     _factors_builder: std::marker::PhantomData<B>,
 }
@@ -306,6 +434,7 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
     pub fn new(trigger: T) -> Self {
         Self {
             engine_config: spin_core::Config::default(),
+            admin_state: None,
             trigger,
             _factors_builder: Default::default(),
         }
@@ -315,6 +444,11 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         &mut self.engine_config
     }
 
+    /// Sets the [`AdminState`] to be updated as the app is loaded and run.
+    pub fn admin_state(&mut self, admin_state: Arc<AdminState>) {
+        self.admin_state = Some(admin_state);
+    }
+
     /// Build a [`TriggerApp`] from the given [`App`] and options.
     pub async fn build(
         &mut self,
@@ -323,17 +457,25 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         options: B::CliArgs,
         loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
     ) -> anyhow::Result<TriggerApp<T, B::Factors>> {
-        let mut core_engine_builder = {
-            self.trigger.update_core_config(&mut self.engine_config)?;
-
-            spin_core::Engine::builder(&self.engine_config)?
-        };
-        self.trigger.add_to_linker(core_engine_builder.linker())?;
-
-        let (factors, runtime_config) = B::build(&common_options, &options)?;
+        self.build_app(app, &common_options, &options, loader).await
+    }
 
-        let mut executor = FactorsExecutor::new(core_engine_builder, factors)?;
-        B::configure_app(&mut executor, &runtime_config, &common_options, &options)?;
+    /// Build a [`TriggerApp`] from the given [`App`], borrowing the options so
+    /// that they can be reused to reload the app.
+    pub(crate) async fn build_app(
+        &mut self,
+        app: App,
+        common_options: &FactorsConfig,
+        options: &B::CliArgs,
+        loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
+    ) -> anyhow::Result<TriggerApp<T, B::Factors>> {
+        let (factors, runtime_config) = B::build(common_options, options)?;
+        let mut executor = self.executor(&app, factors, &runtime_config, options)?;
+        B::configure_app(&mut executor, &runtime_config, common_options, options)?;
+        if let Some(admin_state) = &self.admin_state {
+            // Added last so that its `configure_app` sees the results of all the others
+            executor.add_hooks(AdminHooks::new(admin_state.clone()));
+        }
         let executor = Arc::new(executor);
 
         let configured_app = {
@@ -346,6 +488,48 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         Ok(configured_app)
     }
 
+    /// Compiles each component of the given [`App`] with the engine it would
+    /// be run with, without running the app or any of the executor hooks
+    /// which prepare to run it. Returns the number of components compiled.
+    ///
+    /// This is only useful with a loader that keeps the compiled components,
+    /// such as one with a precompiled directory.
+    pub(crate) async fn precompile_app(
+        &mut self,
+        app: App,
+        common_options: &FactorsConfig,
+        options: &B::CliArgs,
+        loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
+    ) -> anyhow::Result<usize> {
+        let (factors, runtime_config) = B::build(common_options, options)?;
+        let executor = self.executor(&app, factors, &runtime_config, options)?;
+        let _sloth_guard = warn_if_wasm_build_slothful();
+        let trigger_app = Arc::new(executor)
+            .load_app(app, runtime_config.into(), loader)
+            .await?;
+        let count = trigger_app.app().components().len();
+        Ok(count)
+    }
+
+    /// Builds the engine for the app, and an executor using it.
+    fn executor(
+        &mut self,
+        app: &App,
+        factors: B::Factors,
+        runtime_config: &B::RuntimeConfig,
+        options: &B::CliArgs,
+    ) -> anyhow::Result<FactorsExecutor<B::Factors, T::InstanceState>> {
+        self.trigger.update_core_config(&mut self.engine_config)?;
+        B::update_core_config(&mut self.engine_config, runtime_config, options)?;
+        if component_limits::app_uses_fuel(app) {
+            self.engine_config.consume_fuel();
+        }
+
+        let mut core_engine_builder = spin_core::Engine::builder(&self.engine_config)?;
+        self.trigger.add_to_linker(core_engine_builder.linker())?;
+        FactorsExecutor::new(core_engine_builder, factors)
+    }
+
     /// Run the [`TriggerApp`] with the given [`App`] and options.
     pub async fn run(
         mut self,
@@ -374,6 +558,16 @@ pub trait RuntimeFactorsBuilder {
         args: &Self::CliArgs,
     ) -> anyhow::Result<(Self::Factors, Self::RuntimeConfig)>;
 
+    /// Update the engine configuration before the engine is built.
+    fn update_core_config(
+        config: &mut spin_core::Config,
+        runtime_config: &Self::RuntimeConfig,
+        args: &Self::CliArgs,
+    ) -> anyhow::Result<()> {
+        let _ = (config, runtime_config, args);
+        Ok(())
+    }
+
     /// Configure the factors in the executor.
     fn configure_app<U: Send + 'static>(
         executor: &mut FactorsExecutor<Self::Factors, U>,
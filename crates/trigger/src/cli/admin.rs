@@ -0,0 +1,305 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use anyhow::Context as _;
+use http_body_util::Full;
+use hyper::{
+    body::Bytes, server::conn::http1, service::service_fn, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use spin_core::async_trait;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+};
+
+use super::ReloadRequest;
+
+/// State reported by the admin server.
+///
+/// This is populated by [`AdminHooks`] as the app is loaded and run.
+#[derive(Default)]
+pub struct AdminState {
+    ready: AtomicBool,
+    /// Maps component IDs -> invocation counts
+    invocations: Mutex<BTreeMap<String, u64>>,
+    /// The names of non-secret variables which resolved successfully
+    variables: Mutex<Vec<String>>,
+    reload_requests: OnceLock<mpsc::UnboundedSender<ReloadRequest>>,
+}
+
+impl AdminState {
+    /// Marks the app as ready to handle requests.
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the app is ready to handle requests.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Enables `POST /reload`, which sends requests to the given channel.
+    pub fn enable_reload(&self, requests: mpsc::UnboundedSender<ReloadRequest>) {
+        _ = self.reload_requests.set(requests);
+    }
+
+    fn record_invocation(&self, component_id: &str) {
+        let mut invocations = self.invocations.lock().unwrap();
+        *invocations.entry(component_id.to_owned()).or_default() += 1;
+    }
+
+    fn components(&self) -> Vec<ComponentStatus> {
+        self.invocations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, invocations)| ComponentStatus {
+                id: id.clone(),
+                invocations: *invocations,
+            })
+            .collect()
+    }
+
+    /// Serves the admin API on the given listener until an error occurs.
+    ///
+    /// Endpoints:
+    /// - `GET /healthz`: 200 while the process is running
+    /// - `GET /readyz`: 200 once the app is loaded, 503 before that
+    /// - `GET /components`: loaded components and their invocation counts
+    /// - `GET /variables`: names of resolved non-secret variables
+    /// - `POST /reload`: reloads the app, responding once the reload is done.
+    ///   This is only accepted from loopback addresses, as the admin API is
+    ///   unauthenticated and often listens on all interfaces for health checks.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .await
+                .context("admin server failed to accept connection")?;
+            let state = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(state.respond(&req, peer.ip()).await) }
+                });
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Admin server connection error: {err}");
+                }
+            });
+        }
+    }
+
+    async fn respond<B>(&self, req: &Request<B>, peer: IpAddr) -> Response<Full<Bytes>> {
+        if req.method() == Method::POST && req.uri().path() == "/reload" {
+            if !peer.to_canonical().is_loopback() {
+                return text_response(
+                    StatusCode::FORBIDDEN,
+                    "reload is only accepted from loopback addresses",
+                );
+            }
+            return self.reload().await;
+        }
+        self.handle(req)
+    }
+
+    async fn reload(&self) -> Response<Full<Bytes>> {
+        let Some(requests) = self.reload_requests.get() else {
+            return text_response(StatusCode::NOT_IMPLEMENTED, "reload is not enabled");
+        };
+        let (respond, outcome) = oneshot::channel();
+        if requests.send(respond).is_err() {
+            return text_response(StatusCode::SERVICE_UNAVAILABLE, "app is shutting down");
+        }
+        match outcome.await {
+            Ok(Ok(())) => text_response(StatusCode::OK, "reloaded"),
+            Ok(Err(err)) => text_response(StatusCode::INTERNAL_SERVER_ERROR, err),
+            Err(_) => text_response(StatusCode::SERVICE_UNAVAILABLE, "app is shutting down"),
+        }
+    }
+
+    fn handle<B>(&self, req: &Request<B>) -> Response<Full<Bytes>> {
+        if req.method() != Method::GET {
+            return text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+        }
+        match req.uri().path() {
+            "/healthz" => text_response(StatusCode::OK, "ok"),
+            "/readyz" if self.is_ready() => text_response(StatusCode::OK, "ready"),
+            "/readyz" => text_response(StatusCode::SERVICE_UNAVAILABLE, "not ready"),
+            "/components" => json_response(&self.components()),
+            "/variables" => json_response(&*self.variables.lock().unwrap()),
+            _ => text_response(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ComponentStatus {
+    id: String,
+    invocations: u64,
+}
+
+fn text_response(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response
+}
+
+fn json_response(value: &impl Serialize) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => {
+            let mut response = Response::new(Full::new(Bytes::from(body)));
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            response
+        }
+        Err(err) => {
+            tracing::error!("Admin server failed to serialize response: {err}");
+            text_response(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+        }
+    }
+}
+
+/// An [`ExecutorHooks`] that records app information and component
+/// invocations for the admin server.
+pub struct AdminHooks {
+    state: Arc<AdminState>,
+}
+
+impl AdminHooks {
+    pub fn new(state: Arc<AdminState>) -> Self {
+        Self { state }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for AdminHooks {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let app = configured_app.app();
+        {
+            // This runs again when the app is reloaded, so forget removed components
+            let mut invocations = self.state.invocations.lock().unwrap();
+            invocations.retain(|id, _| app.get_component(id).is_some());
+            for component in app.components() {
+                invocations.entry(component.id().to_owned()).or_default();
+            }
+        }
+
+        let mut variables = Vec::new();
+        if let Ok(variables_state) = configured_app.app_state::<VariablesFactor>() {
+            for (name, variable) in app.variables() {
+                if variable.secret {
+                    continue;
+                }
+                let expr = format!("{{{{ {name} }}}}");
+                if variables_state.resolve_expression(expr).await.is_ok() {
+                    variables.push(name.clone());
+                }
+            }
+        }
+        variables.sort();
+        *self.state.variables.lock().unwrap() = variables;
+        Ok(())
+    }
+
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        self.state.record_invocation(builder.app_component().id());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+
+    use super::*;
+
+    async fn get(state: &AdminState, path: &str) -> (StatusCode, String) {
+        let req = Request::get(path).body(()).unwrap();
+        let response = state.handle(&req);
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn readiness_follows_state() {
+        let state = AdminState::default();
+        assert_eq!(get(&state, "/healthz").await.0, StatusCode::OK);
+        assert_eq!(
+            get(&state, "/readyz").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        state.set_ready();
+        assert_eq!(get(&state, "/readyz").await.0, StatusCode::OK);
+        assert_eq!(get(&state, "/nope").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reload_reports_outcome() {
+        let state = AdminState::default();
+        let reload = || Request::post("/reload").body(()).unwrap();
+        let local = IpAddr::from([127, 0, 0, 1]);
+        assert_eq!(
+            state.respond(&reload(), local).await.status(),
+            StatusCode::NOT_IMPLEMENTED
+        );
+
+        let (sender, mut requests) = mpsc::unbounded_channel::<ReloadRequest>();
+        state.enable_reload(sender);
+        tokio::spawn(async move {
+            let respond = requests.recv().await.unwrap();
+            _ = respond.send(Ok(()));
+            let respond = requests.recv().await.unwrap();
+            _ = respond.send(Err("bad component".into()));
+        });
+        assert_eq!(
+            state
+                .respond(&reload(), IpAddr::from([10, 0, 0, 1]))
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            state.respond(&reload(), local).await.status(),
+            StatusCode::OK
+        );
+        let response = state.respond(&reload(), local).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "bad component");
+    }
+
+    #[tokio::test]
+    async fn components_report_invocation_counts() {
+        let state = AdminState::default();
+        state.record_invocation("b");
+        state.record_invocation("a");
+        state.record_invocation("b");
+        let (status, body) = get(&state, "/components").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"[{"id":"a","invocations":1},{"id":"b","invocations":2}]"#
+        );
+    }
+}
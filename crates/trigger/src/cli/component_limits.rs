@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use spin_app::{locked::ComponentLimits, AppComponent, COMPONENT_LIMITS_KEY};
+use spin_core::{async_trait, PoolingLimits};
+use spin_factors::{ConfiguredApp, RuntimeFactors};
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that applies the resource limits set on each
+/// component in the manifest.
+///
+/// Fuel and CPU time limits set on a component take precedence over the
+/// defaults given here. A component memory limit may only lower the host's
+/// maximum instance memory, if any. Component memory, table and instance
+/// limits are checked against the capacity of the pooling allocator, if any.
+#[derive(Default)]
+pub struct ComponentLimitsHook {
+    max_fuel: Option<u64>,
+    max_cpu_time: Option<Duration>,
+    max_memory: Option<usize>,
+    pooling_limits: Option<PoolingLimits>,
+}
+
+impl ComponentLimitsHook {
+    pub fn new(
+        max_fuel: Option<u64>,
+        max_cpu_time: Option<Duration>,
+        max_memory: Option<usize>,
+        pooling_limits: Option<PoolingLimits>,
+    ) -> Self {
+        Self {
+            max_fuel,
+            max_cpu_time,
+            max_memory,
+            pooling_limits,
+        }
+    }
+
+    fn component_limits(component: &AppComponent) -> anyhow::Result<ComponentLimits> {
+        Ok(component
+            .get_metadata(COMPONENT_LIMITS_KEY)
+            .with_context(|| format!("invalid limits for component {:?}", component.id()))?
+            .unwrap_or_default())
+    }
+}
+
+/// Returns true if any component of the app sets a fuel limit, in which case
+/// the engine must be built with fuel consumption enabled.
+pub(crate) fn app_uses_fuel(app: &spin_app::App) -> bool {
+    app.components().any(|component| {
+        ComponentLimitsHook::component_limits(&component).is_ok_and(|limits| limits.fuel.is_some())
+    })
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for ComponentLimitsHook {
+    async fn configure_app(&self, configured_app: &ConfiguredApp<F>) -> anyhow::Result<()> {
+        // Surface malformed limits at startup rather than on first request
+        for component in configured_app.app().components() {
+            let limits = Self::component_limits(&component)?;
+            if let Some(max_memory) = self.max_memory {
+                check_max_memory(component.id(), &limits, max_memory)?;
+            }
+            if let Some(pooling_limits) = &self.pooling_limits {
+                check_pooling_capacity(component.id(), &limits, pooling_limits)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        let limits = Self::component_limits(builder.app_component())?;
+        let max_fuel = limits.fuel.or(self.max_fuel);
+        let max_cpu_time = limits
+            .cpu_time_ms
+            .map(Duration::from_millis)
+            .or(self.max_cpu_time);
+        let store_builder = builder.store_builder();
+        if let Some(max_fuel) = max_fuel {
+            store_builder.max_fuel(max_fuel);
+        }
+        if let Some(max_cpu_time) = max_cpu_time {
+            store_builder.max_cpu_time(max_cpu_time);
+        }
+        if let Some(memory) = limits.memory {
+            let memory = memory.try_into().unwrap_or(usize::MAX);
+            let memory = self.max_memory.map_or(memory, |max| memory.min(max));
+            store_builder.max_memory_size(memory);
+        }
+        if let Some(tables) = limits.tables {
+            store_builder.max_table_elements(tables as usize);
+        }
+        if let Some(instances) = limits.instances {
+            store_builder.max_instances(instances as usize);
+        }
+        Ok(())
+    }
+}
+
+fn check_max_memory(
+    component_id: &str,
+    limits: &ComponentLimits,
+    max_memory: usize,
+) -> anyhow::Result<()> {
+    if let Some(memory) = limits.memory.filter(|&memory| memory > max_memory as u64) {
+        anyhow::bail!(
+            "component {component_id:?} has a memory limit of {memory} bytes, but this host allows at most {max_memory} bytes per instance",
+        );
+    }
+    Ok(())
+}
+
+fn check_pooling_capacity(
+    component_id: &str,
+    limits: &ComponentLimits,
+    pooling_limits: &PoolingLimits,
+) -> anyhow::Result<()> {
+    let exceeds = |limit: Option<u64>, capacity: usize| limit.is_some_and(|l| l > capacity as u64);
+    if exceeds(limits.memory, pooling_limits.max_memory_size) {
+        anyhow::bail!(
+            "component {component_id:?} has a memory limit of {} bytes, but this host allows at most {} bytes per instance",
+            limits.memory.unwrap(),
+            pooling_limits.max_memory_size,
+        );
+    }
+    if exceeds(
+        limits.tables.map(u64::from),
+        pooling_limits.max_table_elements,
+    ) {
+        anyhow::bail!(
+            "component {component_id:?} has a table limit of {} elements, but this host allows at most {} elements per table",
+            limits.tables.unwrap(),
+            pooling_limits.max_table_elements,
+        );
+    }
+    if exceeds(
+        limits.instances.map(u64::from),
+        pooling_limits.max_core_instances,
+    ) {
+        anyhow::bail!(
+            "component {component_id:?} has an instance limit of {}, but this host allows at most {} core instances per component",
+            limits.instances.unwrap(),
+            pooling_limits.max_core_instances,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POOLING_LIMITS: PoolingLimits = PoolingLimits {
+        max_memory_size: 1 << 30,
+        max_table_elements: 10_000,
+        max_core_instances: 100,
+    };
+
+    #[test]
+    fn memory_limits_above_host_max_are_rejected() {
+        let limits = |memory| ComponentLimits {
+            memory: Some(memory),
+            ..Default::default()
+        };
+        check_max_memory("test", &limits(256 << 20), 256 << 20).unwrap();
+        check_max_memory("test", &limits((256 << 20) + 1), 256 << 20).unwrap_err();
+        check_max_memory("test", &ComponentLimits::default(), 256 << 20).unwrap();
+    }
+
+    #[test]
+    fn limits_within_pooling_capacity_are_accepted() {
+        let limits = ComponentLimits {
+            memory: Some(256 << 20),
+            tables: Some(10_000),
+            instances: Some(50),
+            ..Default::default()
+        };
+        check_pooling_capacity("test", &limits, &POOLING_LIMITS).unwrap();
+    }
+
+    #[test]
+    fn limits_exceeding_pooling_capacity_are_rejected() {
+        for limits in [
+            ComponentLimits {
+                memory: Some(2 << 30),
+                ..Default::default()
+            },
+            ComponentLimits {
+                tables: Some(20_000),
+                ..Default::default()
+            },
+            ComponentLimits {
+                instances: Some(101),
+                ..Default::default()
+            },
+        ] {
+            check_pooling_capacity("test", &limits, &POOLING_LIMITS).unwrap_err();
+        }
+    }
+}
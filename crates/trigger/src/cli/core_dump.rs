@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use spin_core::async_trait;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that sets the directory core dumps of trapping
+/// instances are written to, named after the component.
+pub struct CoreDumpHook {
+    dir: PathBuf,
+}
+
+impl CoreDumpHook {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for CoreDumpHook {
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        let component_id = builder.app_component().id().to_owned();
+        builder
+            .store_builder()
+            .core_dump_dir(&self.dir, component_id);
+        Ok(())
+    }
+}
@@ -42,4 +42,9 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for InitialKvSetterHook {
 
         Ok(())
     }
+
+    fn configure_on_reload(&self) -> bool {
+        // Reapplying these to a running app would overwrite its data
+        false
+    }
 }
@@ -0,0 +1,57 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use spin_common::ui::quoted_path;
+use spin_core::async_trait;
+use spin_factors::{ConfiguredApp, RuntimeFactors};
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that profiles every instance, writing one profile
+/// per instance to a directory. Instance reuse is disabled while profiling so
+/// that each profile covers a single invocation.
+pub struct GuestProfilerHook {
+    dir: PathBuf,
+    count: AtomicU64,
+}
+
+impl GuestProfilerHook {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for GuestProfilerHook {
+    async fn configure_app(&self, _configured_app: &ConfiguredApp<F>) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create {}", quoted_path(&self.dir)))?;
+        terminal::einfo!("Writing guest profiles to", "{}", quoted_path(&self.dir));
+        Ok(())
+    }
+
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        let component_id = builder.app_component().id().to_owned();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        let path = self
+            .dir
+            .join(format!("{component_id}-{timestamp}-{count}.json"));
+        let component = builder.component().clone();
+        builder.disable_reuse();
+        builder
+            .store_builder()
+            .profile(&component, &component_id, path);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Context as _;
+use spin_factors_executor::ComponentLoader;
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    component_limits::app_uses_fuel, load_locked_app, warn_if_wasm_build_slothful, FactorsConfig,
+    RuntimeFactorsBuilder,
+};
+use crate::{reload::AppReloadSender, Trigger, TriggerApp};
+
+/// A request to reload the app. The outcome is sent back on the channel.
+pub type ReloadRequest = oneshot::Sender<Result<(), String>>;
+
+/// Reloads a running app from its locked app file on request.
+pub(crate) struct Reloader<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder, L> {
+    locked_url: String,
+    common_options: FactorsConfig,
+    options: B::CliArgs,
+    loader: L,
+    current: TriggerApp<T, B::Factors>,
+    /// Publishes reloaded apps to the trigger; `None` if the trigger doesn't
+    /// support reloading.
+    sender: Option<AppReloadSender<T, B::Factors>>,
+    /// Whether the engine, which is kept across reloads, consumes fuel.
+    consumes_fuel: bool,
+}
+
+impl<T, B, L> Reloader<T, B, L>
+where
+    T: Trigger<B::Factors>,
+    B: RuntimeFactorsBuilder,
+    L: ComponentLoader<B::Factors, T::InstanceState>,
+{
+    pub fn new(
+        locked_url: String,
+        common_options: FactorsConfig,
+        options: B::CliArgs,
+        loader: L,
+        current: TriggerApp<T, B::Factors>,
+        sender: Option<AppReloadSender<T, B::Factors>>,
+        consumes_fuel: bool,
+    ) -> Self {
+        Self {
+            locked_url,
+            common_options,
+            options,
+            loader,
+            current,
+            sender,
+            consumes_fuel,
+        }
+    }
+
+    /// Handles reload requests. This never completes, even once all request
+    /// senders have been dropped.
+    pub async fn run(mut self, mut requests: mpsc::UnboundedReceiver<ReloadRequest>) {
+        while let Some(respond) = requests.recv().await {
+            if self.sender.is_none() {
+                let mut trigger_types = self
+                    .current
+                    .app()
+                    .triggers()
+                    .map(|trigger| trigger.trigger_type())
+                    .collect::<Vec<_>>();
+                trigger_types.sort();
+                trigger_types.dedup();
+                let msg = format!(
+                    "Reloading is not supported for apps with '{}' triggers; restart the application to pick up changes",
+                    trigger_types.join("', '")
+                );
+                tracing::warn!("{msg}");
+                _ = respond.send(Err(msg));
+                continue;
+            }
+            println!("Reloading application...");
+            let res = match self.reload().await {
+                Ok(()) => {
+                    println!("Application reloaded");
+                    Ok(())
+                }
+                Err(err) => {
+                    tracing::error!("Failed to reload app; keeping previous version: {err:?}");
+                    Err(format!("{err:#}"))
+                }
+            };
+            _ = respond.send(res);
+        }
+        std::future::pending().await
+    }
+
+    async fn reload(&mut self) -> anyhow::Result<()> {
+        let app = load_locked_app(&self.locked_url)?;
+        if let Err(unmet) = app.ensure_needs_only(T::TYPE, &T::supported_host_requirements()) {
+            anyhow::bail!("This application requires the following features that are not available in this version of the '{}' trigger: {unmet}", T::TYPE);
+        }
+        if app_uses_fuel(&app) && !self.consumes_fuel {
+            anyhow::bail!("The reloaded application sets component fuel limits, which require fuel consumption to be enabled when the application starts; restart the application to apply them");
+        }
+        let (_, runtime_config) = B::build(&self.common_options, &self.options)
+            .context("failed to rebuild runtime config")?;
+
+        let trigger_app = {
+            let _sloth_guard = warn_if_wasm_build_slothful();
+            self.current
+                .reload(app, runtime_config.into(), &self.loader)
+                .await?
+        };
+        if let Some(sender) = &self.sender {
+            sender.send(trigger_app.clone()).await?;
+        }
+        self.current = trigger_app;
+        Ok(())
+    }
+}
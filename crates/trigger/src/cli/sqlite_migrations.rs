@@ -0,0 +1,51 @@
+use anyhow::Context as _;
+use spin_core::async_trait;
+use spin_factor_sqlite::{migrations::apply_migrations, SqliteFactor};
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// ExecutorHook for applying the SQLite migrations declared in the manifest
+/// or runtime config before the app starts.
+///
+/// This executor assumes that the configured app has access to `SqliteFactor`.
+/// It will silently ignore the hook if the app does not have access to `SqliteFactor`.
+pub struct SqliteMigrationsHook;
+
+impl SqliteMigrationsHook {
+    /// Applies any pending migrations to each database.
+    pub async fn execute(&self, sqlite: &spin_factor_sqlite::AppState) -> anyhow::Result<()> {
+        let mut databases = sqlite.migrations().collect::<Vec<_>>();
+        databases.sort_by_key(|(label, _)| *label);
+        for (label, migrations) in databases {
+            if migrations.is_empty() {
+                continue;
+            }
+            let connection = sqlite
+                .get_connection(label)
+                .await
+                .transpose()
+                .with_context(|| format!("failed connect to database with label '{label}'"))?
+                .with_context(|| format!("no database named '{label}' is registered"))?;
+            apply_migrations(connection.as_ref(), migrations)
+                .await
+                .with_context(|| format!("failed to migrate database '{label}'"))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<F, U> ExecutorHooks<F, U> for SqliteMigrationsHook
+where
+    F: RuntimeFactors,
+{
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let Some(sqlite) = configured_app.app_state::<SqliteFactor>().ok() else {
+            return Ok(());
+        };
+        self.execute(sqlite).await
+    }
+}
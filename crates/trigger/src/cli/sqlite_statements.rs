@@ -80,6 +80,11 @@ where
         self.execute(sqlite).await?;
         Ok(())
     }
+
+    fn configure_on_reload(&self) -> bool {
+        // The statements are not necessarily idempotent, e.g. inserts
+        false
+    }
 }
 
 /// Parses a @{file:label} sqlite statement
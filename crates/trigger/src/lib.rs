@@ -1,5 +1,7 @@
 pub mod cli;
 pub mod loader;
+pub mod reload;
+pub mod retry;
 
 use std::future::Future;
 
@@ -8,7 +10,10 @@ use spin_core::Linker;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{FactorsExecutorApp, FactorsInstanceBuilder};
 
+use crate::reload::AppReloads;
+
 pub use spin_app::App;
+pub use spin_factors_executor::ConcurrencyLimitExceeded;
 
 /// Type alias for a [`spin_factors_executor::FactorsExecutorApp`] specialized to a [`Trigger`].
 pub type TriggerApp<T, F> = FactorsExecutorApp<F, <T as Trigger<F>>::InstanceState>;
@@ -21,7 +26,7 @@ pub type TriggerInstanceBuilder<'a, T, F> =
 pub type Store<T, F> = spin_core::Store<TriggerInstanceState<T, F>>;
 
 /// Type alias for [`spin_factors_executor::InstanceState`] specialized to a [`Trigger`].
-type TriggerInstanceState<T, F> = spin_factors_executor::InstanceState<
+pub type TriggerInstanceState<T, F> = spin_factors_executor::InstanceState<
     <F as RuntimeFactors>::InstanceState,
     <T as Trigger<F>>::InstanceState,
 >;
@@ -65,6 +70,27 @@ pub trait Trigger<F: RuntimeFactors>: Sized + Send {
         trigger_app: TriggerApp<Self, F>,
     ) -> impl Future<Output = anyhow::Result<()>> + Send;
 
+    /// Returns true if this trigger can switch to a reloaded app without
+    /// restarting; see [`Trigger::run_with_reloads`].
+    fn supports_reload(&self) -> bool {
+        false
+    }
+
+    /// Run this trigger, switching to each reloaded app received from
+    /// `reloads`. Work already in progress should be allowed to complete
+    /// against the app it started with.
+    ///
+    /// This is only called if [`Trigger::supports_reload`] returns true. The
+    /// default implementation ignores reloads.
+    fn run_with_reloads(
+        self,
+        trigger_app: TriggerApp<Self, F>,
+        reloads: AppReloads<Self, F>,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send {
+        let _ = reloads;
+        self.run(trigger_app)
+    }
+
     /// Returns a list of host requirements supported by this trigger specifically.
     ///
     /// See [`App::ensure_needs_only`].
@@ -1,12 +1,29 @@
+mod extensions;
+mod precompiled;
+mod preinit;
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
 use anyhow::Context as _;
+use sha2::{Digest, Sha256};
+use spin_app::{locked::LockedComponentSource, COMPONENT_PREINIT_KEY};
 use spin_common::{ui::quoted_path, url::parse_file_url};
 use spin_compose::ComponentSourceLoaderFs;
 use spin_core::{async_trait, wasmtime, Component};
-use spin_factors::{AppComponent, RuntimeFactors};
+use spin_factor_extensions::{HostExtension, HostExtensionsFactor};
+use spin_factors::{AppComponent, ConfiguredApp, RuntimeFactors};
+
+pub use precompiled::PRECOMPILED_DIR;
 
 #[derive(Default)]
 pub struct ComponentLoader {
     _private: (),
+    precompiled_dir: Option<PathBuf>,
+    write_precompiled: bool,
+    host_extensions: Mutex<Option<Arc<[HostExtension]>>>,
     #[cfg(feature = "unsafe-aot-compilation")]
     aot_compilation_enabled: bool,
 }
@@ -17,6 +34,22 @@ impl ComponentLoader {
         Self::default()
     }
 
+    /// Updates the loader to load components from the native artifacts in
+    /// the given directory, if there are any, rather than compiling them.
+    ///
+    /// Artifacts are authenticated with a key private to this host, so
+    /// artifacts written by any other host are ignored.
+    pub fn set_precompiled_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.precompiled_dir = Some(dir.into());
+    }
+
+    /// Updates the loader to write the native artifacts of the components it
+    /// compiles to the precompiled directory, if one is set. Otherwise
+    /// components without an artifact are only compiled in memory.
+    pub fn enable_writing_precompiled_components(&mut self) {
+        self.write_precompiled = true;
+    }
+
     /// Updates the TriggerLoader to load AOT precompiled components
     ///
     /// **Warning: This feature may bypass important security guarantees of the
@@ -46,6 +79,15 @@ impl ComponentLoader {
         self.aot_compilation_enabled = true;
     }
 
+    /// Returns the host extensions of the most recently configured app.
+    fn host_extensions(&self) -> Arc<[HostExtension]> {
+        self.host_extensions
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Arc::new([]))
+    }
+
     #[cfg(feature = "unsafe-aot-compilation")]
     fn load_precompiled_component(
         &self,
@@ -67,8 +109,34 @@ impl ComponentLoader {
     }
 }
 
+/// Adds a component or dependency source to a fingerprint. The contents of
+/// local files are included as they may have changed without their (digest-less)
+/// source reference changing.
+async fn hash_source(hasher: &mut Sha256, source: &LockedComponentSource) -> anyhow::Result<()> {
+    hasher.update(serde_json::to_vec(source)?);
+    if let Some(source) = &source.content.source {
+        if let Ok(path) = parse_file_url(source) {
+            let contents = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("failed to read {}", quoted_path(&path)))?;
+            hasher.update(Sha256::digest(contents));
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for ComponentLoader {
+    async fn configure_app(&self, configured_app: &ConfiguredApp<T>) -> anyhow::Result<()> {
+        // Runtimes without the host extensions factor have no extensions
+        let extensions = configured_app
+            .app_state::<HostExtensionsFactor>()
+            .ok()
+            .map(|state| state.extensions());
+        *self.host_extensions.lock().unwrap() = extensions;
+        Ok(())
+    }
+
     async fn load_component(
         &self,
         engine: &wasmtime::Engine,
@@ -89,16 +157,97 @@ impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for Comp
                 .with_context(|| format!("error deserializing component from {path:?}"));
         }
 
-        let composed = spin_compose::compose(&ComponentSourceLoaderFs, component.locked)
-            .await
-            .with_context(|| {
-                format!(
-                    "failed to resolve dependencies for component {:?}",
-                    component.locked.id
-                )
-            })?;
-
-        spin_core::Component::new(engine, composed)
-            .with_context(|| format!("failed to compile component from {}", quoted_path(&path)))
+        let extensions = self.host_extensions();
+        let Some(dir) = &self.precompiled_dir else {
+            let composed = component_bytes(component, &extensions).await?;
+            return spin_core::Component::new(engine, composed).with_context(|| {
+                format!("failed to compile component from {}", quoted_path(&path))
+            });
+        };
+
+        // Look for an artifact before composing, which may be as costly as compiling
+        let fingerprint = fingerprint(component, &extensions).await?;
+        let artifact = precompiled::artifact_path(dir, engine, &fingerprint);
+        if let Some(component) = precompiled::load_artifact(engine, &artifact).await {
+            return Ok(component);
+        }
+        let composed = component_bytes(component, &extensions).await?;
+        let compiled = if self.write_precompiled {
+            precompiled::compile_artifact(engine, &artifact, &composed).await
+        } else {
+            spin_core::Component::new(engine, composed)
+        };
+        compiled.with_context(|| format!("failed to compile component from {}", quoted_path(&path)))
+    }
+
+    async fn component_fingerprint(
+        &self,
+        component: &AppComponent,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(Some(fingerprint(component, &self.host_extensions()).await?))
+    }
+}
+
+/// Returns a hash of everything that goes into the bytes returned by
+/// [`component_bytes`].
+async fn fingerprint(
+    component: &AppComponent<'_>,
+    extensions: &[HostExtension],
+) -> anyhow::Result<Vec<u8>> {
+    let locked = extensions::with_host_extensions(component.locked, extensions).await?;
+    let mut hasher = Sha256::new();
+    // Composition may differ between versions
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hash_source(&mut hasher, component.source()).await?;
+    for (name, dependency) in &locked.dependencies {
+        hasher.update(name.to_string());
+        hasher.update(serde_json::to_vec(&dependency.export)?);
+        hash_source(&mut hasher, &dependency.source).await?;
+    }
+    if is_preinit(component)? {
+        hasher.update(preinit::INIT_EXPORT);
+        hasher.update(preinit::PREINIT_VERSION);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Returns the bytes of the component to be compiled, after composing it with
+/// its dependencies and any host extensions it imports, and pre-initializing
+/// it if requested.
+async fn component_bytes(
+    component: &AppComponent<'_>,
+    extensions: &[HostExtension],
+) -> anyhow::Result<Vec<u8>> {
+    let locked = extensions::with_host_extensions(component.locked, extensions)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to resolve host extensions for component {:?}",
+                component.locked.id
+            )
+        })?;
+    let composed = spin_compose::compose(&ComponentSourceLoaderFs, &locked)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to resolve dependencies for component {:?}",
+                component.locked.id
+            )
+        })?;
+
+    if !is_preinit(component)? {
+        return Ok(composed);
     }
+    preinit::preinitialize(composed).await.with_context(|| {
+        format!(
+            "failed to pre-initialize component {:?}",
+            component.locked.id
+        )
+    })
+}
+
+fn is_preinit(component: &AppComponent) -> anyhow::Result<bool> {
+    Ok(component
+        .get_metadata(COMPONENT_PREINIT_KEY)?
+        .unwrap_or_default())
 }
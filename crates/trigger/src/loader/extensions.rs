@@ -0,0 +1,83 @@
+//! Composition of host extensions into components.
+
+use std::borrow::Cow;
+
+use anyhow::{anyhow, ensure, Context as _};
+use spin_app::locked::{
+    ContentRef, InheritConfiguration, LockedComponent, LockedComponentDependency,
+    LockedComponentSource,
+};
+use spin_common::ui::quoted_path;
+use spin_compose::{ComponentSourceLoader as _, ComponentSourceLoaderFs};
+use spin_factor_extensions::HostExtension;
+
+/// Returns the component with a dependency added for each of its imports
+/// that a host extension provides. Imports already satisfied by one of the
+/// component's own dependencies are left alone.
+pub(crate) async fn with_host_extensions<'a>(
+    component: &'a LockedComponent,
+    extensions: &[HostExtension],
+) -> anyhow::Result<Cow<'a, LockedComponent>> {
+    if extensions.is_empty() {
+        return Ok(Cow::Borrowed(component));
+    }
+
+    let source = ComponentSourceLoaderFs
+        .load_component_source(component)
+        .await?;
+    let mut imports = vec![];
+    for import in spin_compose::component_imports(&source)? {
+        if !is_satisfied(component, &import)? {
+            imports.push(import);
+        }
+    }
+
+    let mut component = Cow::Borrowed(component);
+    for extension in extensions {
+        for name in &extension.provides {
+            let mut matched = false;
+            for import in &imports {
+                matched |= spin_compose::dependency_matches_import(name, import)?;
+            }
+            if !matched {
+                continue;
+            }
+            let dependencies = &mut component.to_mut().dependencies;
+            ensure!(
+                !dependencies.contains_key(name),
+                "{name:?} is provided by more than one host extension"
+            );
+            dependencies.insert(name.clone(), extension_dependency(extension)?);
+        }
+    }
+    Ok(component)
+}
+
+fn is_satisfied(component: &LockedComponent, import: &str) -> anyhow::Result<bool> {
+    for name in component.dependencies.keys() {
+        if spin_compose::dependency_matches_import(name, import)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn extension_dependency(extension: &HostExtension) -> anyhow::Result<LockedComponentDependency> {
+    let path = std::fs::canonicalize(&extension.source)
+        .with_context(|| format!("failed to resolve {}", quoted_path(&extension.source)))?;
+    let source = url::Url::from_file_path(&path)
+        .map_err(|_| anyhow!("cannot convert to file URL: {}", quoted_path(&path)))?;
+    Ok(LockedComponentDependency {
+        source: LockedComponentSource {
+            content_type: "application/wasm".into(),
+            content: ContentRef {
+                source: Some(source.into()),
+                ..Default::default()
+            },
+        },
+        export: None,
+        // Host extensions are trusted by the host, and act on behalf of the
+        // component they are composed into
+        inherit: InheritConfiguration::All,
+    })
+}
@@ -0,0 +1,216 @@
+//! Ahead-of-time compiled component artifacts.
+//!
+//! When a [`ComponentLoader`](super::ComponentLoader) has a precompiled
+//! directory, loads of a component with a native artifact there use the
+//! artifact instead of compiling the component. `spin build --precompile`
+//! writes the artifacts ahead of time by loading the app without running it;
+//! other loads never write to the directory.
+//!
+//! Artifacts are keyed by the component's fingerprint and by a hash of the
+//! engine configuration, so an artifact is only ever used by an engine
+//! compatible with the one that produced it. Since deserializing an artifact
+//! runs its native code unchecked, each artifact is authenticated with a key
+//! private to this host; artifacts produced anywhere else, or modified since
+//! they were written, are ignored and the component compiled as usual.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use spin_common::ui::quoted_path;
+use spin_core::{wasmtime, Component};
+use tokio::io::AsyncWriteExt as _;
+
+/// The directory, relative to the app directory, containing precompiled
+/// component artifacts.
+pub const PRECOMPILED_DIR: &str = ".spin/precompiled";
+
+/// The file, in Spin's data directory, holding the key which authenticates
+/// artifacts.
+const KEY_FILE: &str = "precompile.key";
+
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 32;
+
+/// Returns the path of the artifact for the component with the given
+/// fingerprint compiled with the given engine, which may or may not exist.
+pub(crate) fn artifact_path(dir: &Path, engine: &wasmtime::Engine, fingerprint: &[u8]) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    let fingerprint = fingerprint
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    dir.join(format!("{fingerprint}-{:016x}.cwasm", hasher.finish()))
+}
+
+/// Loads the artifact at the given path, returning `None` if it doesn't
+/// exist, was not written by this host, or was not produced by a compatible
+/// engine.
+pub(crate) async fn load_artifact(engine: &wasmtime::Engine, path: &Path) -> Option<Component> {
+    let contents = tokio::fs::read(path).await.ok()?;
+    let verified = match host_key(false).await {
+        Ok(Some(key)) => verify(&key, path, &contents),
+        Ok(None) => Ok(None),
+        Err(err) => Err(err),
+    };
+    let artifact = match verified {
+        Ok(Some(artifact)) => artifact,
+        Ok(None) => {
+            tracing::warn!(
+                "Ignoring precompiled component {} as it was not written by this host",
+                quoted_path(path)
+            );
+            return None;
+        }
+        Err(err) => {
+            tracing::warn!(
+                "Ignoring precompiled component {}: {err:#}",
+                quoted_path(path)
+            );
+            return None;
+        }
+    };
+    // Safety: the artifact is authenticated with this host's key, so it was
+    // produced by `compile_artifact` with `Engine::precompile_component` and
+    // has not been modified since.
+    match unsafe { Component::deserialize(engine, artifact) } {
+        Ok(component) => {
+            tracing::debug!("Loaded precompiled component from {}", quoted_path(path));
+            Some(component)
+        }
+        Err(err) => {
+            tracing::warn!(
+                "Ignoring precompiled component {}: {err:#}",
+                quoted_path(path)
+            );
+            None
+        }
+    }
+}
+
+/// Compiles the given component bytes, writing the artifact to the given
+/// path for later loads.
+pub(crate) async fn compile_artifact(
+    engine: &wasmtime::Engine,
+    path: &Path,
+    component: &[u8],
+) -> anyhow::Result<Component> {
+    let artifact = engine.precompile_component(component)?;
+    // Safety: the artifact was just produced by the same engine
+    let compiled = unsafe { Component::deserialize(engine, &artifact)? };
+    if let Err(err) = write_artifact(path, &artifact).await {
+        tracing::warn!(
+            "Failed to write precompiled component {}: {err:#}",
+            quoted_path(path)
+        );
+    }
+    Ok(compiled)
+}
+
+async fn write_artifact(path: &Path, artifact: &[u8]) -> anyhow::Result<()> {
+    let key = host_key(true).await?.context("no precompile key")?;
+    let tag = mac(&key, path, artifact)?.finalize().into_bytes();
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("failed to create {}", quoted_path(dir)))?;
+    }
+    // Write to a temporary file first so that concurrent loads never see a
+    // partially written artifact
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    tokio::fs::write(&temp_path, [tag.as_slice(), artifact].concat()).await?;
+    tokio::fs::rename(&temp_path, path).await?;
+    Ok(())
+}
+
+/// Returns the artifact in the given file contents if its tag is valid.
+fn verify<'a>(key: &[u8], path: &Path, contents: &'a [u8]) -> anyhow::Result<Option<&'a [u8]>> {
+    if contents.len() < TAG_LEN {
+        return Ok(None);
+    }
+    let (tag, artifact) = contents.split_at(TAG_LEN);
+    Ok(mac(key, path, artifact)?
+        .verify_slice(tag)
+        .is_ok()
+        .then_some(artifact))
+}
+
+/// Computes the tag of an artifact. The file name is included so that an
+/// artifact can't be substituted for that of another component.
+fn mac(key: &[u8], path: &Path, artifact: &[u8]) -> anyhow::Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    let name = path.file_name().context("artifact path has no file name")?;
+    mac.update(name.as_encoded_bytes());
+    mac.update(&[0]);
+    mac.update(artifact);
+    Ok(mac)
+}
+
+/// Reads this host's artifact key, creating it if `create` is true.
+async fn host_key(create: bool) -> anyhow::Result<Option<Vec<u8>>> {
+    let path = spin_common::data_dir::data_dir()?.join(KEY_FILE);
+    match tokio::fs::read(&path).await {
+        Ok(key) if key.len() == KEY_LEN => return Ok(Some(key)),
+        Ok(_) => anyhow::bail!("{} is not a valid key", quoted_path(&path)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && create => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", quoted_path(&path)))
+        }
+    }
+
+    let key: [u8; KEY_LEN] = rand::random();
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    match options.open(&path).await {
+        Ok(mut file) => {
+            file.write_all(&key).await?;
+            file.sync_all().await?;
+            Ok(Some(key.to_vec()))
+        }
+        // Another process created the key first
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            Ok(Some(tokio::fs::read(&path).await?))
+        }
+        Err(err) => Err(err).with_context(|| format!("failed to create {}", quoted_path(&path))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_only_untampered_artifacts() {
+        let key = [1; KEY_LEN];
+        let path = Path::new("/precompiled/abc-0.cwasm");
+        let artifact = b"artifact";
+        let tag = mac(&key, path, artifact).unwrap().finalize().into_bytes();
+        let contents = [tag.as_slice(), artifact].concat();
+
+        assert_eq!(
+            verify(&key, path, &contents).unwrap(),
+            Some(artifact.as_slice())
+        );
+        // A different key, i.e. another host
+        assert_eq!(verify(&[2; KEY_LEN], path, &contents).unwrap(), None);
+        // Moved to stand in for another component
+        let other = Path::new("/precompiled/def-0.cwasm");
+        assert_eq!(verify(&key, other, &contents).unwrap(), None);
+        // Modified
+        let mut modified = contents.clone();
+        *modified.last_mut().unwrap() ^= 1;
+        assert_eq!(verify(&key, path, &modified).unwrap(), None);
+        assert_eq!(verify(&key, path, b"short").unwrap(), None);
+    }
+}
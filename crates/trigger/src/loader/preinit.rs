@@ -0,0 +1,154 @@
+//! Pre-initialization of components.
+//!
+//! A component with `preinit = true` has its `wizer-initialize` export run
+//! once at load time; the state it leaves behind in memories and globals is
+//! then snapshotted into a new component so that instances of the component
+//! start already initialized.
+
+use anyhow::Context as _;
+use component_init_transform::Invoker;
+use futures::FutureExt as _;
+use spin_common::sha256::hex_digest_from_bytes;
+use spin_core::{
+    async_trait,
+    wasmtime::{
+        self,
+        component::{Component, Instance, Linker},
+        Store,
+    },
+};
+use spin_loader::cache::Cache;
+use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
+
+/// The export run to initialize a component.
+pub const INIT_EXPORT: &str = "wizer-initialize";
+
+/// Bumped whenever the snapshot format changes, invalidating cached snapshots.
+pub const PREINIT_VERSION: &str = "1";
+
+/// Returns the pre-initialized form of the given component, from the cache if
+/// it has been pre-initialized before.
+///
+/// Initialization runs with only WASI available. Other imports are linked to
+/// stubs that trap, so initialization fails if it calls any of them.
+pub async fn preinitialize(component: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    let cache = Cache::new(None).await?;
+    let digest = format!(
+        "sha256-{}",
+        hex_digest_from_bytes([PREINIT_VERSION.as_bytes(), &component].concat())
+    );
+    let path = cache.preinit_path(&digest);
+    if let Ok(bytes) = tokio::fs::read(&path).await {
+        tracing::debug!("Using cached pre-initialized component {}", path.display());
+        return Ok(bytes);
+    }
+
+    // The initialization future isn't `Send`, so it can't run on the
+    // multi-threaded runtime directly
+    let runtime = tokio::runtime::Handle::current();
+    let initialized = tokio::task::spawn_blocking(move || {
+        runtime.block_on(component_init_transform::initialize(
+            &component,
+            |instrumented| async move { instantiate(&instrumented).await }.boxed(),
+        ))
+    })
+    .await?;
+    let bytes = initialized?;
+    if let Err(err) = cache.write_preinit(&bytes, &digest).await {
+        tracing::warn!("Failed to cache pre-initialized component: {err:?}");
+    }
+    Ok(bytes)
+}
+
+/// Instantiates the instrumented component and runs its initialization export.
+async fn instantiate(instrumented: &[u8]) -> anyhow::Result<Box<dyn Invoker>> {
+    // A separate engine is used so that limits configured on the app's engine
+    // (e.g. fuel or epoch deadlines) don't apply to initialization.
+    let mut config = wasmtime::Config::new();
+    config.async_support(true);
+    let engine = wasmtime::Engine::new(&config)?;
+    let component = Component::new(&engine, instrumented)
+        .context("failed to compile instrumented component")?;
+
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker.define_unknown_imports_as_traps(&component)?;
+    let instance_pre = linker.instantiate_pre(&component)?;
+    let mut store = Store::new(
+        &engine,
+        InitState {
+            ctx: WasiCtxBuilder::new().inherit_stdio().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let instance = instance_pre.instantiate_async(&mut store).await?;
+
+    let init = instance
+        .get_typed_func::<(), ()>(&mut store, INIT_EXPORT)
+        .with_context(|| format!("component has no `{INIT_EXPORT}` export"))?;
+    init.call_async(&mut store, ())
+        .await
+        .with_context(|| {
+            format!("failed to run `{INIT_EXPORT}` (only WASI imports are available during pre-initialization)")
+        })?;
+    init.post_return_async(&mut store).await?;
+
+    Ok(Box::new(InitInvoker { store, instance }))
+}
+
+struct InitState {
+    ctx: WasiCtx,
+    table: ResourceTable,
+}
+
+impl WasiView for InitState {
+    fn ctx(&mut self) -> WasiCtxView<'_> {
+        WasiCtxView {
+            ctx: &mut self.ctx,
+            table: &mut self.table,
+        }
+    }
+}
+
+/// Reads back the initialized state through the instrumented component's exports.
+struct InitInvoker {
+    store: Store<InitState>,
+    instance: Instance,
+}
+
+impl InitInvoker {
+    async fn call<R>(&mut self, function: &str) -> anyhow::Result<R>
+    where
+        R: wasmtime::component::ComponentType + wasmtime::component::Lift + Send + Sync + 'static,
+    {
+        let func = self
+            .instance
+            .get_typed_func::<(), (R,)>(&mut self.store, function)?;
+        let (result,) = func.call_async(&mut self.store, ()).await?;
+        func.post_return_async(&mut self.store).await?;
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Invoker for InitInvoker {
+    async fn call_s32(&mut self, function: &str) -> anyhow::Result<i32> {
+        self.call(function).await
+    }
+
+    async fn call_s64(&mut self, function: &str) -> anyhow::Result<i64> {
+        self.call(function).await
+    }
+
+    async fn call_f32(&mut self, function: &str) -> anyhow::Result<f32> {
+        self.call(function).await
+    }
+
+    async fn call_f64(&mut self, function: &str) -> anyhow::Result<f64> {
+        self.call(function).await
+    }
+
+    async fn call_list_u8(&mut self, function: &str) -> anyhow::Result<Vec<u8>> {
+        self.call(function).await
+    }
+}
@@ -0,0 +1,80 @@
+//! Support for reloading an app while its trigger is running.
+
+use anyhow::Context as _;
+use spin_factors::RuntimeFactors;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{Trigger, TriggerApp};
+
+/// A reloaded app along with the channel on which its outcome is reported.
+type PendingReload<T, F> = (TriggerApp<T, F>, oneshot::Sender<anyhow::Result<()>>);
+
+/// Reloaded versions of the app being run by a [`Trigger`].
+///
+/// See [`Trigger::run_with_reloads`].
+pub struct AppReloads<T: Trigger<F>, F: RuntimeFactors> {
+    receiver: mpsc::Receiver<PendingReload<T, F>>,
+}
+
+impl<T: Trigger<F>, F: RuntimeFactors> AppReloads<T, F> {
+    /// Returns a new [`AppReloads`] along with a sender to publish reloaded
+    /// versions of the app.
+    pub fn channel() -> (AppReloadSender<T, F>, Self) {
+        let (sender, receiver) = mpsc::channel(1);
+        (AppReloadSender { sender }, Self { receiver })
+    }
+
+    /// Waits for the next reloaded app.
+    ///
+    /// Returns `None` once no more reloads can happen.
+    pub async fn next(&mut self) -> Option<AppReload<T, F>> {
+        let (trigger_app, applied) = self.receiver.recv().await?;
+        Some(AppReload {
+            trigger_app,
+            applied,
+        })
+    }
+}
+
+/// A reloaded app which the trigger should switch to.
+///
+/// The sender of the reload waits until [`AppReload::apply`] reports whether
+/// the trigger switched to it.
+pub struct AppReload<T: Trigger<F>, F: RuntimeFactors> {
+    trigger_app: TriggerApp<T, F>,
+    applied: oneshot::Sender<anyhow::Result<()>>,
+}
+
+impl<T: Trigger<F>, F: RuntimeFactors> AppReload<T, F> {
+    /// Returns the reloaded app.
+    pub fn trigger_app(&self) -> &TriggerApp<T, F> {
+        &self.trigger_app
+    }
+
+    /// Switches to the reloaded app with `apply`, reporting its outcome to
+    /// the sender of the reload.
+    pub fn apply(self, apply: impl FnOnce(TriggerApp<T, F>) -> anyhow::Result<()>) {
+        _ = self.applied.send(apply(self.trigger_app));
+    }
+}
+
+/// Publishes reloaded apps to a running [`Trigger`].
+pub struct AppReloadSender<T: Trigger<F>, F: RuntimeFactors> {
+    sender: mpsc::Sender<PendingReload<T, F>>,
+}
+
+impl<T: Trigger<F>, F: RuntimeFactors> AppReloadSender<T, F> {
+    /// Sends a reloaded app to the trigger, returning once the trigger has
+    /// switched to it or failed to.
+    pub async fn send(&self, trigger_app: TriggerApp<T, F>) -> anyhow::Result<()> {
+        let (applied, outcome) = oneshot::channel();
+        self.sender
+            .send((trigger_app, applied))
+            .await
+            .ok()
+            .context("the trigger is no longer accepting reloads")?;
+        outcome
+            .await
+            .context("the trigger did not apply the reloaded app")?
+    }
+}
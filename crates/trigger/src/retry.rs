@@ -0,0 +1,383 @@
+//! Retry and dead-letter handling for trigger invocations.
+//!
+//! Triggers can opt into this by accepting a [`RetryConfig`] in their trigger
+//! config (conventionally as a `retry` table) and wrapping each invocation in
+//! [`RetryPolicy::run`].
+
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use base64::Engine as _;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use spin_core::async_trait;
+use spin_factor_key_value::{KeyValueFactor, Store};
+use spin_factors::{ConfiguredApp, RuntimeFactors};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 1;
+const DEFAULT_INITIAL_BACKOFF_MILLIS: u64 = 100;
+const DEFAULT_MAX_BACKOFF_MILLIS: u64 = 10_000;
+const DEFAULT_BACKOFF_MULTIPLIER: f64 = 2.0;
+const DEFAULT_DEAD_LETTER_KEY_PREFIX: &str = "dead-letter/";
+
+/// Retry configuration as it appears in a trigger config.
+///
+/// Example: `retry = { max_attempts = 5, initial_backoff_ms = 200, jitter = 0.5, dead_letter = { type = "key_value", store = "default" } }`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// The maximum number of attempts, including the first.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// The upper bound on the delay between attempts.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// The factor by which the delay grows after each retry.
+    #[serde(default = "default_backoff_multiplier")]
+    pub backoff_multiplier: f64,
+    /// The fraction (0.0 to 1.0) of each delay which is randomized.
+    #[serde(default)]
+    pub jitter: f64,
+    /// Where to record invocations which fail on every attempt.
+    #[serde(default)]
+    pub dead_letter: Option<DeadLetterConfig>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff_ms: DEFAULT_INITIAL_BACKOFF_MILLIS,
+            max_backoff_ms: DEFAULT_MAX_BACKOFF_MILLIS,
+            backoff_multiplier: DEFAULT_BACKOFF_MULTIPLIER,
+            jitter: 0.0,
+            dead_letter: None,
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    DEFAULT_MAX_ATTEMPTS
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    DEFAULT_INITIAL_BACKOFF_MILLIS
+}
+
+fn default_max_backoff_ms() -> u64 {
+    DEFAULT_MAX_BACKOFF_MILLIS
+}
+
+fn default_backoff_multiplier() -> f64 {
+    DEFAULT_BACKOFF_MULTIPLIER
+}
+
+/// Dead-letter sink configuration.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DeadLetterConfig {
+    /// Append dead letters as JSON lines to a file.
+    File {
+        /// The file to append to. It is created if it does not exist.
+        path: PathBuf,
+    },
+    /// Write dead letters to a key-value store.
+    KeyValue {
+        /// The label of the key-value store.
+        store: String,
+        /// The prefix of dead letter keys. Defaults to `dead-letter/`.
+        key_prefix: Option<String>,
+    },
+}
+
+/// Describes a single trigger invocation for the purposes of dead-lettering.
+#[derive(Clone, Copy, Debug)]
+pub struct Invocation<'a> {
+    /// The type of the trigger, e.g. `redis`.
+    pub trigger_type: &'a str,
+    /// The ID of the invoked component.
+    pub component_id: &'a str,
+    /// The payload the component was invoked with.
+    pub payload: &'a [u8],
+}
+
+/// A record of an invocation which failed on every attempt.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DeadLetter {
+    pub trigger_type: String,
+    pub component_id: String,
+    /// The base64-encoded invocation payload.
+    pub payload: String,
+    /// The error from the final attempt.
+    pub error: String,
+    pub attempts: u32,
+    /// Milliseconds since the Unix epoch at which the final attempt failed.
+    pub failed_at: u64,
+}
+
+impl DeadLetter {
+    fn new(invocation: &Invocation, error: &anyhow::Error, attempts: u32) -> Self {
+        let failed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        Self {
+            trigger_type: invocation.trigger_type.to_owned(),
+            component_id: invocation.component_id.to_owned(),
+            payload: base64::engine::general_purpose::STANDARD.encode(invocation.payload),
+            error: format!("{error:#}"),
+            attempts,
+            failed_at,
+        }
+    }
+
+    /// Returns the decoded invocation payload.
+    pub fn decode_payload(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(base64::engine::general_purpose::STANDARD.decode(&self.payload)?)
+    }
+}
+
+/// A destination for [`DeadLetter`]s.
+#[async_trait]
+pub trait DeadLetterSink: Send + Sync {
+    /// Records the given dead letter.
+    async fn send(&self, letter: &DeadLetter) -> anyhow::Result<()>;
+}
+
+/// A [`DeadLetterSink`] which appends JSON lines to a file.
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl FileDeadLetterSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterSink for FileDeadLetterSink {
+    async fn send(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(letter)?;
+        line.push(b'\n');
+        // Serialize writers so that lines from concurrent failures don't interleave
+        let _guard = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// A [`DeadLetterSink`] which writes to a key-value [`Store`].
+///
+/// Keys are of the form `<prefix><component ID>/<failed at>-<random suffix>`
+/// so that dead letters for a component can be listed in order.
+pub struct KeyValueDeadLetterSink {
+    store: Arc<dyn Store>,
+    key_prefix: String,
+}
+
+impl KeyValueDeadLetterSink {
+    pub fn new(store: Arc<dyn Store>, key_prefix: impl Into<String>) -> Self {
+        Self {
+            store,
+            key_prefix: key_prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterSink for KeyValueDeadLetterSink {
+    async fn send(&self, letter: &DeadLetter) -> anyhow::Result<()> {
+        let suffix: u32 = rand::rng().random();
+        let key = format!(
+            "{}{}/{:013}-{suffix:08x}",
+            self.key_prefix, letter.component_id, letter.failed_at
+        );
+        let value = serde_json::to_vec(letter)?;
+        self.store
+            .set(&key, &value)
+            .await
+            .with_context(|| format!("failed to write dead letter {key:?}"))
+    }
+}
+
+/// Retries failed trigger invocations with exponential backoff, recording
+/// invocations which fail on every attempt in an optional [`DeadLetterSink`].
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    backoff_multiplier: f64,
+    jitter: f64,
+    dead_letter_sink: Option<Arc<dyn DeadLetterSink>>,
+}
+
+impl Default for RetryPolicy {
+    /// Returns a policy which makes a single attempt and discards failures.
+    fn default() -> Self {
+        Self::new(&RetryConfig::default()).expect("default retry config should be valid")
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy from the given config, ignoring any dead-letter sink config.
+    ///
+    /// Use [`RetryPolicy::from_config`] to also create the configured dead-letter sink.
+    pub fn new(config: &RetryConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.max_attempts > 0,
+            "retry max_attempts must be at least 1"
+        );
+        anyhow::ensure!(
+            config.backoff_multiplier >= 1.0,
+            "retry backoff_multiplier must be at least 1.0"
+        );
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&config.jitter),
+            "retry jitter must be between 0.0 and 1.0"
+        );
+        Ok(Self {
+            max_attempts: config.max_attempts,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            backoff_multiplier: config.backoff_multiplier,
+            jitter: config.jitter,
+            dead_letter_sink: None,
+        })
+    }
+
+    /// Creates a policy from the given config, resolving any key-value
+    /// dead-letter store against the given app.
+    ///
+    /// A relative dead-letter file path is resolved against the app directory
+    /// if there is one (i.e. for local apps), else the current directory.
+    pub async fn from_config<F: RuntimeFactors>(
+        config: &RetryConfig,
+        configured_app: &ConfiguredApp<F>,
+    ) -> anyhow::Result<Self> {
+        let policy = Self::new(config)?;
+        let Some(dead_letter) = &config.dead_letter else {
+            return Ok(policy);
+        };
+        let policy = match dead_letter {
+            DeadLetterConfig::File { path } => {
+                let path = match std::env::var(crate::cli::SPIN_LOCAL_APP_DIR) {
+                    Ok(dir) => PathBuf::from(dir).join(path),
+                    Err(_) => path.clone(),
+                };
+                policy.with_dead_letter_sink(FileDeadLetterSink::new(path))
+            }
+            DeadLetterConfig::KeyValue { store, key_prefix } => {
+                let kv = configured_app.app_state::<KeyValueFactor>().context(
+                    "a key-value dead-letter store was configured but the key-value factor is not available",
+                )?;
+                let kv_store = kv
+                    .get_store(store)
+                    .await
+                    .with_context(|| format!("unknown dead-letter key-value store {store:?}"))?;
+                let key_prefix = key_prefix
+                    .as_deref()
+                    .unwrap_or(DEFAULT_DEAD_LETTER_KEY_PREFIX);
+                policy.with_dead_letter_sink(KeyValueDeadLetterSink::new(kv_store, key_prefix))
+            }
+        };
+        Ok(policy)
+    }
+
+    /// Sets the sink for invocations which fail on every attempt.
+    pub fn with_dead_letter_sink(mut self, sink: impl DeadLetterSink + 'static) -> Self {
+        self.dead_letter_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Returns the maximum number of attempts, including the first.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns the delay before the given retry (starting at 1), before jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .powi(retry.saturating_sub(1).min(i32::MAX as u32) as i32);
+        // A delay too long to represent is capped like any other long delay
+        Duration::try_from_secs_f64(self.initial_backoff.as_secs_f64() * factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    fn jittered_backoff(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if self.jitter == 0.0 {
+            return backoff;
+        }
+        let reduction = self.jitter * rand::rng().random::<f64>();
+        backoff.mul_f64(1.0 - reduction)
+    }
+
+    /// Runs `attempt` until it succeeds or the maximum number of attempts is
+    /// reached. In the latter case the invocation is sent to the dead-letter
+    /// sink (if any) and the last error is returned.
+    pub async fn run<T, Fut>(
+        &self,
+        invocation: Invocation<'_>,
+        mut attempt: impl FnMut() -> Fut,
+    ) -> anyhow::Result<T>
+    where
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if attempts >= self.max_attempts {
+                self.dead_letter(&invocation, &err, attempts).await;
+                return Err(err);
+            }
+            let delay = self.jittered_backoff(attempts);
+            tracing::info!(
+                "Component {} failed (attempt {attempts} of {}); retrying in {delay:?}: {err:#}",
+                invocation.component_id,
+                self.max_attempts,
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn dead_letter(&self, invocation: &Invocation<'_>, err: &anyhow::Error, attempts: u32) {
+        let Some(sink) = &self.dead_letter_sink else {
+            return;
+        };
+        let letter = DeadLetter::new(invocation, err, attempts);
+        if let Err(sink_err) = sink.send(&letter).await {
+            tracing::error!(
+                "Failed to record dead letter for component {}: {sink_err:#}",
+                invocation.component_id
+            );
+        }
+    }
+}
@@ -116,7 +116,7 @@ impl TimerTrigger {
         trigger_app: &TriggerApp<Self, F>,
         component_id: &str,
     ) -> anyhow::Result<()> {
-        let instance_builder = trigger_app.prepare(component_id)?;
+        let instance_builder = trigger_app.prepare(component_id).await?;
         let (instance, mut store) = instance_builder.instantiate(()).await?;
         let timer = SpinTimer::new(&mut store, &instance)?;
         timer.call_handle_timer_request(&mut store).await
@@ -1,6 +1,7 @@
 use anyhow::{Context, Error};
 use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use lazy_static::lazy_static;
+use spin_cli::builtin_triggers::BuiltinTriggers;
 use spin_cli::commands::external::predefined_externals;
 use spin_cli::commands::maintenance::MaintenanceCommands;
 use spin_cli::commands::{
@@ -8,9 +9,11 @@ use spin_cli::commands::{
     cloud::{DeployCommand, LoginCommand},
     doctor::DoctorCommand,
     external::execute_external_subcommand,
+    kv::KvCommands,
     new::{AddCommand, NewCommand},
     plugins::PluginCommands,
     registry::RegistryCommands,
+    sqlite::SqliteCommands,
     templates::TemplateCommands,
     up::UpCommand,
     watch::WatchCommand,
@@ -19,7 +22,9 @@ use spin_cli::{build_info::*, subprocess::ExitStatusError};
 use spin_runtime_factors::FactorsBuilder;
 use spin_trigger::cli::help::HelpArgsOnlyTrigger;
 use spin_trigger::cli::FactorsTriggerCommand;
+use spin_trigger_fs::FsTrigger;
 use spin_trigger_http::HttpTrigger;
+use spin_trigger_kv::KvTrigger;
 use spin_trigger_redis::RedisTrigger;
 
 #[tokio::main]
@@ -136,6 +141,10 @@ enum SpinApp {
     #[clap(alias = "w")]
     Watch(WatchCommand),
     Doctor(DoctorCommand),
+    #[clap(subcommand)]
+    Kv(KvCommands),
+    #[clap(subcommand)]
+    Sqlite(SqliteCommands),
     #[clap(subcommand, hide = true)]
     Maintenance(MaintenanceCommands),
 }
@@ -144,6 +153,10 @@ enum SpinApp {
 enum TriggerCommands {
     Http(FactorsTriggerCommand<HttpTrigger, FactorsBuilder>),
     Redis(FactorsTriggerCommand<RedisTrigger, FactorsBuilder>),
+    Fs(FactorsTriggerCommand<FsTrigger, FactorsBuilder>),
+    Kv(FactorsTriggerCommand<KvTrigger, FactorsBuilder>),
+    #[clap(name = spin_cli::BUILTIN_TRIGGERS_TYPE, hide = true)]
+    Builtin(FactorsTriggerCommand<BuiltinTriggers, FactorsBuilder>),
     #[clap(name = spin_cli::HELP_ARGS_ONLY_TRIGGER_TYPE, hide = true)]
     HelpArgsOnly(FactorsTriggerCommand<HelpArgsOnlyTrigger, FactorsBuilder>),
 }
@@ -162,11 +175,16 @@ impl SpinApp {
             Self::Build(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Fs(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Kv(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Builtin(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
             Self::Plugins(cmd) => cmd.run().await,
             Self::External(cmd) => execute_external_subcommand(cmd, app).await,
             Self::Watch(cmd) => cmd.run().await,
             Self::Doctor(cmd) => cmd.run().await,
+            Self::Kv(cmd) => cmd.run().await,
+            Self::Sqlite(cmd) => cmd.run().await,
             Self::Maintenance(cmd) => cmd.run(SpinApp::command()).await,
         }
     }
@@ -0,0 +1,140 @@
+//! Runs all of an application's built-in trigger types in a single process.
+//!
+//! Without this, `spin up` starts one trigger process per trigger type, each
+//! of which loads the app and compiles its components. [`BuiltinTriggers`]
+//! instead shares one engine, configured app and set of compiled components
+//! between the built-in triggers.
+//!
+//! Reloading an app without a restart (on SIGHUP or an admin request) isn't
+//! supported here: it needs the app's triggers all to be of a single type
+//! that supports reload, such as HTTP, in which case that trigger is run
+//! directly rather than through [`BuiltinTriggers`].
+
+use std::{future::Future, pin::Pin};
+
+use clap::Args;
+use futures::FutureExt;
+use spin_core::Linker;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{cli::NoCliArgs, App, Trigger, TriggerApp, TriggerInstanceState};
+use spin_trigger_fs::FsTrigger;
+use spin_trigger_http::HttpTrigger;
+use spin_trigger_kv::KvTrigger;
+use spin_trigger_redis::RedisTrigger;
+
+/// The trigger types which can be run by [`BuiltinTriggers`].
+pub const BUILTIN_TRIGGER_TYPES: &[&str] = &["http", "redis", "fs", "kv"];
+
+/// A [`Trigger`] which runs each of the built-in trigger types used by an
+/// app, sharing a single [`TriggerApp`] between them.
+pub struct BuiltinTriggers {
+    http: Option<HttpTrigger>,
+    redis: Option<RedisTrigger>,
+    fs: Option<FsTrigger>,
+    kv: Option<KvTrigger>,
+}
+
+/// The combined CLI arguments of the built-in triggers.
+#[derive(Args)]
+pub struct CliArgs {
+    #[clap(flatten)]
+    http: spin_trigger_http::CliArgs,
+}
+
+type RunFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+impl<F: RuntimeFactors> Trigger<F> for BuiltinTriggers {
+    const TYPE: &'static str = crate::opts::BUILTIN_TRIGGERS_TYPE;
+
+    type CliArgs = CliArgs;
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            http: new_if_used::<HttpTrigger, F>(cli_args.http, app)?,
+            redis: new_if_used::<RedisTrigger, F>(NoCliArgs, app)?,
+            fs: new_if_used::<FsTrigger, F>(NoCliArgs, app)?,
+            kv: new_if_used::<KvTrigger, F>(NoCliArgs, app)?,
+        })
+    }
+
+    fn update_core_config(&mut self, config: &mut spin_core::Config) -> anyhow::Result<()> {
+        if let Some(http) = &mut self.http {
+            <HttpTrigger as Trigger<F>>::update_core_config(http, config)?;
+        }
+        if let Some(redis) = &mut self.redis {
+            <RedisTrigger as Trigger<F>>::update_core_config(redis, config)?;
+        }
+        if let Some(fs) = &mut self.fs {
+            <FsTrigger as Trigger<F>>::update_core_config(fs, config)?;
+        }
+        if let Some(kv) = &mut self.kv {
+            <KvTrigger as Trigger<F>>::update_core_config(kv, config)?;
+        }
+        Ok(())
+    }
+
+    fn add_to_linker(
+        &mut self,
+        linker: &mut Linker<TriggerInstanceState<Self, F>>,
+    ) -> anyhow::Result<()> {
+        if let Some(http) = &mut self.http {
+            <HttpTrigger as Trigger<F>>::add_to_linker(http, linker)?;
+        }
+        if let Some(redis) = &mut self.redis {
+            <RedisTrigger as Trigger<F>>::add_to_linker(redis, linker)?;
+        }
+        if let Some(fs) = &mut self.fs {
+            <FsTrigger as Trigger<F>>::add_to_linker(fs, linker)?;
+        }
+        if let Some(kv) = &mut self.kv {
+            <KvTrigger as Trigger<F>>::add_to_linker(kv, linker)?;
+        }
+        Ok(())
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let mut runs: Vec<RunFuture> = Vec::new();
+        if let Some(http) = self.http {
+            runs.push(<HttpTrigger as Trigger<F>>::run(http, trigger_app.clone()).boxed());
+        }
+        if let Some(redis) = self.redis {
+            runs.push(<RedisTrigger as Trigger<F>>::run(redis, trigger_app.clone()).boxed());
+        }
+        if let Some(fs) = self.fs {
+            runs.push(<FsTrigger as Trigger<F>>::run(fs, trigger_app.clone()).boxed());
+        }
+        if let Some(kv) = self.kv {
+            runs.push(<KvTrigger as Trigger<F>>::run(kv, trigger_app.clone()).boxed());
+        }
+        if runs.is_empty() {
+            return Ok(());
+        }
+
+        // As with separate trigger processes, the app stops when any trigger does.
+        let (res, _, _) = futures::future::select_all(runs).await;
+        res
+    }
+
+    fn supported_host_requirements() -> Vec<&'static str> {
+        let mut supported = <HttpTrigger as Trigger<F>>::supported_host_requirements();
+        supported.extend(<RedisTrigger as Trigger<F>>::supported_host_requirements());
+        supported.extend(<FsTrigger as Trigger<F>>::supported_host_requirements());
+        supported.extend(<KvTrigger as Trigger<F>>::supported_host_requirements());
+        supported
+    }
+}
+
+/// Constructs the trigger `T` if the app has any triggers of its type.
+fn new_if_used<T: Trigger<F>, F: RuntimeFactors>(
+    cli_args: T::CliArgs,
+    app: &App,
+) -> anyhow::Result<Option<T>> {
+    if app.triggers_with_type(T::TYPE).next().is_none() {
+        return Ok(None);
+    }
+    if let Err(unmet) = app.ensure_needs_only(T::TYPE, &T::supported_host_requirements()) {
+        anyhow::bail!("This application requires the following features that are not available in this version of the '{}' trigger: {unmet}", T::TYPE);
+    }
+    T::new(cli_args, app).map(Some)
+}
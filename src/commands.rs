@@ -8,6 +8,10 @@ pub mod cloud;
 pub mod doctor;
 /// Commands for external subcommands (i.e. plugins)
 pub mod external;
+/// Commands for inspecting and seeding key-value stores.
+pub mod kv;
+/// Options for commands which operate on an application's local state.
+pub mod local_state;
 /// Commands for Spin maintenance tasks.
 pub mod maintenance;
 /// Command for creating a new application.
@@ -16,6 +20,8 @@ pub mod new;
 pub mod plugins;
 /// Commands for working with OCI registries.
 pub mod registry;
+/// Commands for working with SQLite databases.
+pub mod sqlite;
 /// Commands for working with templates.
 pub mod templates;
 /// Commands for starting the runtime.
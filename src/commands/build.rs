@@ -1,9 +1,13 @@
-use std::{ffi::OsString, path::PathBuf};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 
 use crate::{
+    builtin_triggers::BUILTIN_TRIGGER_TYPES,
     directory_rels::notify_if_nondefault_rel,
     opts::{APP_MANIFEST_FILE_OPT, BUILD_UP_OPT},
 };
@@ -39,6 +43,14 @@ pub struct BuildCommand {
     )]
     skip_target_checks: bool,
 
+    /// Compile the application's components ahead of time after building, so
+    /// that `spin up` on this machine does not need to compile them on
+    /// startup. Precompiled components are only used by a compatible version
+    /// and configuration of Spin; otherwise they are compiled as usual. Only
+    /// Spin's built-in triggers support precompilation.
+    #[clap(long = "precompile", takes_value = false)]
+    pub precompile: bool,
+
     /// Run the application after building.
     #[clap(name = BUILD_UP_OPT, short = 'u', long = "up")]
     pub up: bool,
@@ -61,6 +73,10 @@ impl BuildCommand {
         )
         .await?;
 
+        if self.precompile {
+            precompile(&manifest_file, &self.up_args).await?;
+        }
+
         if self.up {
             let mut cmd = UpCommand::parse_from(
                 std::iter::once(OsString::from(format!(
@@ -84,3 +100,30 @@ impl BuildCommand {
         }
     }
 }
+
+/// Compiles the app's components as `spin up` with the given arguments would,
+/// so that `spin up` finds them already compiled.
+async fn precompile(manifest_file: &Path, up_args: &[OsString]) -> Result<()> {
+    // Trigger plugins would ignore the request to only precompile, and run the app
+    let manifest = spin_manifest::manifest_from_file(manifest_file)?;
+    if let Some(trigger_type) = manifest
+        .triggers
+        .keys()
+        .find(|t| !BUILTIN_TRIGGER_TYPES.contains(&t.as_str()))
+    {
+        anyhow::bail!("cannot precompile an application with '{trigger_type}' triggers: only Spin's built-in triggers support precompilation");
+    }
+
+    let mut cmd = UpCommand::parse_from(
+        std::iter::once(OsString::from(format!(
+            "{} up",
+            std::env::args().next().unwrap()
+        )))
+        .chain(up_args.iter().cloned())
+        .chain(std::iter::once(OsString::from("--precompile-only"))),
+    );
+    cmd.file_source = Some(manifest_file.to_owned());
+    cmd.run()
+        .await
+        .context("failed to precompile the application")
+}
@@ -0,0 +1,490 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine as _;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use spin_factor_key_value::Store;
+use spin_runtime_config::{key_value_config_resolver, variables};
+
+use super::local_state::LocalStateOptions;
+
+/// The number of keys to request from a store at a time when listing keys.
+const LIST_PAGE_SIZE: u32 = 1000;
+
+/// The number of values to request from a store at a time when exporting,
+/// which is the most DynamoDB returns from a single batch read.
+const EXPORT_PAGE_SIZE: u32 = 100;
+
+/// Commands for inspecting and seeding an application's key-value stores.
+#[derive(Subcommand, Debug)]
+pub enum KvCommands {
+    /// Print the value of a key.
+    Get(Get),
+    /// Set the value of a key.
+    Set(Set),
+    /// Delete a key.
+    Delete(Delete),
+    /// List the keys in a store.
+    List(List),
+    /// Export the keys and values in a store.
+    Export(Export),
+    /// Import keys and values into a store.
+    Import(Import),
+}
+
+impl KvCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            KvCommands::Get(cmd) => cmd.run().await,
+            KvCommands::Set(cmd) => cmd.run().await,
+            KvCommands::Delete(cmd) => cmd.run().await,
+            KvCommands::List(cmd) => cmd.run().await,
+            KvCommands::Export(cmd) => cmd.run().await,
+            KvCommands::Import(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Options identifying the store to operate on.
+#[derive(Args, Debug)]
+pub struct StoreOptions {
+    #[clap(flatten)]
+    pub app: LocalStateOptions,
+
+    /// The label of the store.
+    #[clap(short = 's', long = "store", default_value = "default")]
+    pub store: String,
+}
+
+impl StoreOptions {
+    /// Opens the store as `spin up` would for the application.
+    async fn open(&self) -> Result<Arc<dyn Store>> {
+        let state = self.app.resolve()?;
+        let variables_providers =
+            variables::runtime_config_from_toml(&state.runtime_config)?.providers;
+        let resolver = key_value_config_resolver(
+            state.runtime_config_dir,
+            state.state_dir,
+            variables_providers,
+        );
+        let stores = resolver
+            .resolve(Some(&state.runtime_config))
+            .context("failed to resolve key-value store runtime config")?;
+
+        let label = &self.store;
+        let Some(store_manager) = stores.get_store_manager(label) else {
+            bail!("No key-value store labelled '{label}' is configured");
+        };
+        let store = store_manager
+            .get(label)
+            .await
+            .with_context(|| format!("failed to open key-value store '{label}'"))?;
+        store
+            .after_open()
+            .await
+            .with_context(|| format!("failed to open key-value store '{label}'"))?;
+        Ok(store)
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Get {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// The key whose value to print.
+    pub key: String,
+}
+
+impl Get {
+    pub async fn run(self) -> Result<()> {
+        let store = self.store.open().await?;
+        let value = store
+            .get(&self.key)
+            .await
+            .with_context(|| format!("failed to get key '{}'", self.key))?;
+        let Some(value) = value else {
+            bail!("Key '{}' not found", self.key);
+        };
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&value)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Set {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// The key whose value to set.
+    pub key: String,
+
+    /// The value to set.
+    #[clap(required_unless_present = "value_file")]
+    pub value: Option<String>,
+
+    /// Read the value from a file instead of the command line. Use `-` to read
+    /// from standard input.
+    #[clap(long = "value-file", conflicts_with = "value")]
+    pub value_file: Option<PathBuf>,
+}
+
+impl Set {
+    pub async fn run(self) -> Result<()> {
+        let value = match (self.value, &self.value_file) {
+            (Some(value), _) => value.into_bytes(),
+            (None, Some(path)) if path == Path::new("-") => {
+                let mut value = vec![];
+                std::io::stdin()
+                    .read_to_end(&mut value)
+                    .context("failed to read value from standard input")?;
+                value
+            }
+            (None, Some(path)) => std::fs::read(path)
+                .with_context(|| format!("failed to read value file '{}'", path.display()))?,
+            (None, None) => unreachable!("clap requires a value or a value file"),
+        };
+        let store = self.store.open().await?;
+        store
+            .set(&self.key, &value)
+            .await
+            .with_context(|| format!("failed to set key '{}'", self.key))?;
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Delete {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// The key to delete.
+    pub key: String,
+}
+
+impl Delete {
+    pub async fn run(self) -> Result<()> {
+        let store = self.store.open().await?;
+        store
+            .delete(&self.key)
+            .await
+            .with_context(|| format!("failed to delete key '{}'", self.key))?;
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct List {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// List only keys starting with this prefix.
+    #[clap(long = "prefix")]
+    pub prefix: Option<String>,
+}
+
+impl List {
+    pub async fn run(self) -> Result<()> {
+        let store = self.store.open().await?;
+        for key in list_keys(&*store, self.prefix.as_deref()).await? {
+            println!("{key}");
+        }
+        Ok(())
+    }
+}
+
+/// The format of exported and imported key-value pairs.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// A JSON array of entries.
+    Json,
+    /// One JSON entry per line.
+    Ndjson,
+}
+
+impl DataFormat {
+    /// Infers the format of a file from its extension, defaulting to JSON.
+    fn infer(path: Option<&Path>) -> Self {
+        match path.and_then(Path::extension).and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// An exported key-value pair.
+///
+/// Values which are valid UTF-8 are exported as `value`; other values are
+/// exported base64-encoded as `value_base64`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Entry {
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value_base64: Option<String>,
+}
+
+impl Entry {
+    fn new(key: String, value: Vec<u8>) -> Self {
+        match String::from_utf8(value) {
+            Ok(value) => Self {
+                key,
+                value: Some(value),
+                value_base64: None,
+            },
+            Err(err) => Self {
+                key,
+                value: None,
+                value_base64: Some(
+                    base64::engine::general_purpose::STANDARD.encode(err.into_bytes()),
+                ),
+            },
+        }
+    }
+
+    fn into_key_value(self) -> Result<(String, Vec<u8>)> {
+        let value = match (self.value, self.value_base64) {
+            (Some(value), None) => value.into_bytes(),
+            (None, Some(encoded)) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .with_context(|| format!("invalid base64 value for key '{}'", self.key))?,
+            _ => bail!(
+                "entry for key '{}' must have exactly one of 'value' and 'value_base64'",
+                self.key
+            ),
+        };
+        Ok((self.key, value))
+    }
+}
+
+fn write_entries(mut writer: impl Write, entries: &[Entry], format: DataFormat) -> Result<()> {
+    match format {
+        DataFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, entries)?;
+            writeln!(writer)?;
+        }
+        DataFormat::Ndjson => {
+            for entry in entries {
+                serde_json::to_writer(&mut writer, entry)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_entries(reader: impl BufRead, format: DataFormat) -> Result<Vec<Entry>> {
+    match format {
+        DataFormat::Json => Ok(serde_json::from_reader(reader)?),
+        DataFormat::Ndjson => reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|(index, line)| {
+                serde_json::from_str(&line?)
+                    .with_context(|| format!("invalid entry on line {}", index + 1))
+            })
+            .collect(),
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Export {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// Export only keys starting with this prefix.
+    #[clap(long = "prefix")]
+    pub prefix: Option<String>,
+
+    /// The file to export to. If omitted, entries are written to standard output.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// The format in which to export entries. If omitted, it is inferred from
+    /// the output file extension, defaulting to JSON.
+    #[clap(value_enum, long = "format")]
+    pub format: Option<DataFormat>,
+}
+
+impl Export {
+    pub async fn run(self) -> Result<()> {
+        let format = self
+            .format
+            .unwrap_or_else(|| DataFormat::infer(self.output.as_deref()));
+        let store = self.store.open().await?;
+
+        // Values are fetched a page of keys at a time, so that no request to
+        // the store exceeds its batch limits
+        let mut entries = vec![];
+        let mut cursor = None;
+        loop {
+            let page = store
+                .list_keys(self.prefix.as_deref(), cursor.as_deref(), EXPORT_PAGE_SIZE)
+                .await
+                .context("failed to list keys")?;
+            if !page.keys.is_empty() {
+                let values = store
+                    .get_many(page.keys)
+                    .await
+                    .context("failed to get values")?;
+                // Keys deleted since they were listed are skipped
+                entries.extend(
+                    values
+                        .into_iter()
+                        .filter_map(|(key, value)| Some(Entry::new(key, value?))),
+                );
+            }
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        match &self.output {
+            Some(path) => {
+                let file = std::fs::File::create(path).with_context(|| {
+                    format!("failed to create export file '{}'", path.display())
+                })?;
+                write_entries(std::io::BufWriter::new(file), &entries, format)?;
+                eprintln!("Exported {} entries to {}", entries.len(), path.display());
+            }
+            None => write_entries(std::io::stdout().lock(), &entries, format)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct Import {
+    #[clap(flatten)]
+    pub store: StoreOptions,
+
+    /// The file to import from, as written by `spin kv export`. Use `-` to read
+    /// from standard input.
+    pub input: PathBuf,
+
+    /// The format of the file to import. If omitted, it is inferred from the
+    /// file extension, defaulting to JSON.
+    #[clap(value_enum, long = "format")]
+    pub format: Option<DataFormat>,
+}
+
+impl Import {
+    pub async fn run(self) -> Result<()> {
+        let entries = if self.input == Path::new("-") {
+            let format = self.format.unwrap_or(DataFormat::Json);
+            read_entries(std::io::stdin().lock(), format)
+        } else {
+            let format = self
+                .format
+                .unwrap_or_else(|| DataFormat::infer(Some(&self.input)));
+            let file = std::fs::File::open(&self.input).with_context(|| {
+                format!("failed to open import file '{}'", self.input.display())
+            })?;
+            read_entries(BufReader::new(file), format)
+        }
+        .context("failed to read entries to import")?;
+        let key_values = entries
+            .into_iter()
+            .map(Entry::into_key_value)
+            .collect::<Result<Vec<_>>>()?;
+
+        let count = key_values.len();
+        let store = self.store.open().await?;
+        store
+            .set_many(key_values)
+            .await
+            .context("failed to set values")?;
+        eprintln!("Imported {count} entries");
+        Ok(())
+    }
+}
+
+/// Lists all the keys in `store` starting with `prefix`, a page at a time.
+async fn list_keys(store: &dyn Store, prefix: Option<&str>) -> Result<Vec<String>> {
+    let mut keys = vec![];
+    let mut cursor = None;
+    loop {
+        let page = store
+            .list_keys(prefix, cursor.as_deref(), LIST_PAGE_SIZE)
+            .await
+            .context("failed to list keys")?;
+        keys.extend(page.keys);
+        cursor = page.cursor;
+        if cursor.is_none() {
+            return Ok(keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_in_both_formats() {
+        let entries = vec![
+            Entry::new("text".into(), b"hello".to_vec()),
+            Entry::new("binary".into(), vec![0xff, 0x00]),
+        ];
+        assert_eq!(entries[0].value.as_deref(), Some("hello"));
+        assert_eq!(entries[1].value_base64.as_deref(), Some("/wA="));
+
+        for format in [DataFormat::Json, DataFormat::Ndjson] {
+            let mut buf = vec![];
+            write_entries(&mut buf, &entries, format).unwrap();
+            let read = read_entries(buf.as_slice(), format).unwrap();
+            assert_eq!(read, entries);
+        }
+    }
+
+    #[test]
+    fn entries_need_exactly_one_value() {
+        let entries = read_entries(
+            br#"{"key": "k", "value": "v", "value_base64": "dg=="}"#.as_slice(),
+            DataFormat::Ndjson,
+        )
+        .unwrap();
+        assert!(entries
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_key_value()
+            .is_err());
+
+        let entries = read_entries(br#"[{"key": "k"}]"#.as_slice(), DataFormat::Json).unwrap();
+        assert!(entries
+            .into_iter()
+            .next()
+            .unwrap()
+            .into_key_value()
+            .is_err());
+    }
+
+    #[test]
+    fn format_is_inferred_from_extension() {
+        assert_eq!(
+            DataFormat::infer(Some(Path::new("a.ndjson"))),
+            DataFormat::Ndjson
+        );
+        assert_eq!(
+            DataFormat::infer(Some(Path::new("a.jsonl"))),
+            DataFormat::Ndjson
+        );
+        assert_eq!(
+            DataFormat::infer(Some(Path::new("a.json"))),
+            DataFormat::Json
+        );
+        assert_eq!(DataFormat::infer(None), DataFormat::Json);
+    }
+}
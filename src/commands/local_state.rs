@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use spin_runtime_config::TomlResolver;
+use spin_trigger::cli::{UserProvidedPath, RUNTIME_CONFIG_FILE};
+
+use crate::{directory_rels::notify_if_nondefault_rel, opts::APP_MANIFEST_FILE_OPT};
+
+/// Options identifying an application and the runtime configuration and state
+/// directory with which it is run.
+#[derive(Args, Debug)]
+pub struct LocalStateOptions {
+    /// The application whose state to use. This may be a manifest (spin.toml)
+    /// file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The runtime config file with which the application is run, if any.
+    #[clap(
+        name = RUNTIME_CONFIG_FILE,
+        long = "runtime-config-file",
+        env = RUNTIME_CONFIG_FILE,
+    )]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The application state directory with which the application is run, if
+    /// not the default.
+    #[clap(long)]
+    pub state_dir: Option<String>,
+}
+
+/// An application's manifest and runtime configuration, located as `spin up` would.
+pub(crate) struct LocalState {
+    /// The path to the application manifest.
+    pub manifest_file: PathBuf,
+    /// The runtime config, or an empty table if there is no runtime config file.
+    pub runtime_config: toml::Table,
+    /// The directory containing the runtime config file, if any.
+    pub runtime_config_dir: Option<PathBuf>,
+    /// The application state directory, if any.
+    pub state_dir: Option<PathBuf>,
+}
+
+impl LocalStateOptions {
+    /// Locates the application and reads its runtime config.
+    pub(crate) fn resolve(&self) -> Result<LocalState> {
+        let (manifest_file, distance) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        notify_if_nondefault_rel(&manifest_file, distance);
+        let local_app_dir = manifest_file.parent().map(ToOwned::to_owned);
+
+        let runtime_config = match &self.runtime_config_file {
+            Some(path) => {
+                let file = std::fs::read_to_string(path).with_context(|| {
+                    format!("failed to read runtime config file '{}'", path.display())
+                })?;
+                toml::from_str(&file).with_context(|| {
+                    format!(
+                        "failed to parse runtime config file '{}' as toml",
+                        path.display()
+                    )
+                })?
+            }
+            None => toml::Table::new(),
+        };
+        let state_dir = match &self.state_dir {
+            Some(s) if s.is_empty() => UserProvidedPath::Unset,
+            Some(s) => UserProvidedPath::Provided(PathBuf::from(s)),
+            None => UserProvidedPath::Default,
+        };
+        let state_dir = TomlResolver::new(
+            &runtime_config,
+            local_app_dir,
+            state_dir,
+            UserProvidedPath::Default,
+        )
+        .state_dir()?;
+
+        let runtime_config_dir = self
+            .runtime_config_file
+            .as_deref()
+            .and_then(Path::parent)
+            .map(ToOwned::to_owned);
+
+        Ok(LocalState {
+            manifest_file,
+            runtime_config,
+            runtime_config_dir,
+            state_dir,
+        })
+    }
+}
@@ -0,0 +1,587 @@
+use std::{
+    io::{BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use comfy_table::Table;
+use spin_factor_sqlite::{
+    migrations::{
+        apply_migrations, migration_status, read_migrations_dir, resolve_migrations,
+        MigrationStatus,
+    },
+    Connection, RuntimeConfig,
+};
+use spin_runtime_config::sqlite_config_resolver;
+use spin_world::spin::sqlite::sqlite as v3;
+
+use super::local_state::{LocalState, LocalStateOptions};
+
+/// Commands for working with an application's SQLite databases.
+#[derive(Subcommand, Debug)]
+pub enum SqliteCommands {
+    /// Apply pending schema migrations to the application's databases.
+    Migrate(Migrate),
+    /// Run SQL statements against a database interactively.
+    Shell(Shell),
+    /// Run SQL statements against a database.
+    Exec(Exec),
+    /// Write the schema and contents of a database as SQL statements.
+    Dump(Dump),
+    /// Run the SQL statements written by `spin sqlite dump` against a database.
+    Restore(Restore),
+}
+
+impl SqliteCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            SqliteCommands::Migrate(cmd) => cmd.run().await,
+            SqliteCommands::Shell(cmd) => cmd.run().await,
+            SqliteCommands::Exec(cmd) => cmd.run().await,
+            SqliteCommands::Dump(cmd) => cmd.run().await,
+            SqliteCommands::Restore(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Options identifying the database to operate on.
+#[derive(Args, Debug)]
+pub struct DatabaseOptions {
+    #[clap(flatten)]
+    pub app: LocalStateOptions,
+
+    /// The label of the database.
+    pub label: String,
+}
+
+impl DatabaseOptions {
+    /// Connects to the database as `spin up` would for the application.
+    async fn connect(&self) -> Result<Box<dyn Connection>> {
+        let state = self.app.resolve()?;
+        let databases = resolve_databases(&state)?;
+        let connection = connect(&databases, &self.label).await?;
+        if let Some(summary) = connection.summary() {
+            terminal::einfo!("Database:", "{summary}");
+        }
+        Ok(connection)
+    }
+}
+
+/// Resolves the SQLite databases configured for the application as `spin up` would.
+fn resolve_databases(state: &LocalState) -> Result<RuntimeConfig> {
+    sqlite_config_resolver(state.state_dir.clone())?
+        .resolve(&state.runtime_config)
+        .context("failed to resolve SQLite runtime config")
+}
+
+/// Opens a new connection to the database with the given label.
+async fn connect(databases: &RuntimeConfig, label: &str) -> Result<Box<dyn Connection>> {
+    let Some(creator) = databases.connection_creators.get(label) else {
+        bail!("No SQLite database labelled '{label}' is configured");
+    };
+    creator
+        .create_connection(label)
+        .await
+        .with_context(|| format!("failed to connect to SQLite database '{label}'"))
+}
+
+/// Apply pending schema migrations to the application's databases.
+#[derive(Parser, Debug)]
+pub struct Migrate {
+    #[clap(flatten)]
+    pub app: LocalStateOptions,
+
+    /// The label of the database to migrate. If omitted, all databases with
+    /// migrations are migrated.
+    #[clap(short = 'd', long = "database")]
+    pub database: Option<String>,
+
+    /// Show which migrations have been applied rather than applying any.
+    #[clap(long)]
+    pub status: bool,
+}
+
+impl Migrate {
+    pub async fn run(self) -> Result<()> {
+        let state = self.app.resolve()?;
+        let mut databases = resolve_databases(&state)?;
+
+        let manifest = spin_manifest::manifest_from_file(&state.manifest_file)?;
+        let app_dir = state
+            .manifest_file
+            .parent()
+            .context("manifest file has no parent directory")?;
+        let mut declared = Vec::new();
+        for (id, component) in manifest.components {
+            if let Some(dir) = &component.sqlite_migrations {
+                let migrations = read_migrations_dir(&app_dir.join(dir))
+                    .with_context(|| format!("component {id} has invalid `sqlite_migrations`"))?;
+                declared.push((id.to_string(), component.sqlite_databases, migrations));
+            }
+        }
+        let migrations = resolve_migrations(declared, std::mem::take(&mut databases.migrations))?;
+
+        let mut labels = match &self.database {
+            Some(label) if !migrations.contains_key(label) => {
+                bail!("No migrations are declared for SQLite database '{label}'")
+            }
+            Some(label) => vec![label],
+            None => migrations.keys().collect(),
+        };
+        if labels.is_empty() {
+            println!("No SQLite migrations are declared for this application");
+            return Ok(());
+        }
+        labels.sort();
+
+        for label in labels {
+            let connection = connect(&databases, label).await?;
+            let migrations = &migrations[label];
+            if self.status {
+                println!("Database '{label}':");
+                for (name, status) in migration_status(connection.as_ref(), migrations).await? {
+                    match status {
+                        MigrationStatus::Pending => println!("  pending   {name}"),
+                        MigrationStatus::Applied { applied_at } => {
+                            println!("  applied   {name} ({applied_at})")
+                        }
+                        MigrationStatus::Modified { applied_at } => {
+                            println!("  modified  {name} (applied {applied_at}, changed since)")
+                        }
+                        MigrationStatus::Missing { applied_at } => {
+                            println!("  missing   {name} (applied {applied_at}, file removed)")
+                        }
+                    }
+                }
+            } else {
+                let applied = apply_migrations(connection.as_ref(), migrations)
+                    .await
+                    .with_context(|| format!("failed to migrate SQLite database '{label}'"))?;
+                if applied.is_empty() {
+                    println!("Database '{label}' is up to date");
+                } else {
+                    println!(
+                        "Applied {} migration(s) to database '{label}':",
+                        applied.len()
+                    );
+                    for name in applied {
+                        println!("  {name}");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run SQL statements against a database interactively.
+#[derive(Parser, Debug)]
+pub struct Shell {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+}
+
+impl Shell {
+    pub async fn run(self) -> Result<()> {
+        let connection = self.database.connect().await?;
+        let interactive = std::io::stdin().is_terminal();
+        if interactive {
+            println!("Enter SQL statements terminated with ';', or '.help' for help.");
+        }
+
+        let mut lines = std::io::stdin().lock().lines();
+        let mut statement = String::new();
+        loop {
+            if interactive {
+                print!(
+                    "{}",
+                    if statement.is_empty() {
+                        "sqlite> "
+                    } else {
+                        "   ...> "
+                    }
+                );
+                std::io::stdout().flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else {
+                break;
+            };
+            let line = line.trim_end();
+
+            if statement.is_empty() && line.starts_with('.') {
+                match dot_command(connection.as_ref(), line).await {
+                    Ok(ShellAction::Continue) => {}
+                    Ok(ShellAction::Exit) => break,
+                    Err(e) => terminal::error!("{e:#}"),
+                }
+                continue;
+            }
+
+            statement.push_str(line);
+            statement.push('\n');
+            if !line.ends_with(';') {
+                continue;
+            }
+            if let Err(e) = run_statement(connection.as_ref(), statement.trim()).await {
+                terminal::error!("{e:#}");
+            }
+            statement.clear();
+        }
+        Ok(())
+    }
+}
+
+/// What the shell should do after a dot command.
+enum ShellAction {
+    Continue,
+    Exit,
+}
+
+/// Runs one of the shell's dot commands.
+async fn dot_command(connection: &dyn Connection, line: &str) -> Result<ShellAction> {
+    let mut words = line.split_whitespace();
+    match (words.next().unwrap_or_default(), words.next()) {
+        (".quit" | ".exit", _) => return Ok(ShellAction::Exit),
+        (".help", _) => {
+            println!(".exit, .quit      Exit the shell");
+            println!(".help             Show this message");
+            println!(".schema [TABLE]   Show the statements which created the schema");
+            println!(".tables           List the tables in the database");
+        }
+        (".tables", _) => {
+            let result = query(
+                connection,
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+                vec![],
+            )
+            .await?;
+            for row in result.rows {
+                println!("{}", display_value(&row.values[0]));
+            }
+        }
+        (".schema", table) => {
+            let result = match table {
+                Some(table) => {
+                    query(
+                        connection,
+                        "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND tbl_name = ? ORDER BY rowid",
+                        vec![v3::Value::Text(table.to_owned())],
+                    )
+                    .await?
+                }
+                None => {
+                    query(
+                        connection,
+                        "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' ORDER BY rowid",
+                        vec![],
+                    )
+                    .await?
+                }
+            };
+            for row in result.rows {
+                println!("{};", display_value(&row.values[0]));
+            }
+        }
+        (command, _) => bail!("Unknown command '{command}'. Enter '.help' for help."),
+    }
+    Ok(ShellAction::Continue)
+}
+
+/// Run SQL statements against a database.
+#[derive(Parser, Debug)]
+pub struct Exec {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The SQL statement to run. Any rows it returns are printed.
+    #[clap(required_unless_present = "file", conflicts_with = "file")]
+    pub statement: Option<String>,
+
+    /// A file of SQL statements to run, or '-' to read from stdin. Rows
+    /// returned by the statements are not printed.
+    #[clap(long = "file")]
+    pub file: Option<PathBuf>,
+}
+
+impl Exec {
+    pub async fn run(self) -> Result<()> {
+        let connection = self.database.connect().await?;
+        match (self.statement, self.file) {
+            (Some(statement), _) => run_statement(connection.as_ref(), &statement).await,
+            (None, Some(file)) => {
+                let sql = read_input(&file)?;
+                connection
+                    .execute_batch(&sql)
+                    .await
+                    .with_context(|| format!("failed to run statements from {}", file.display()))
+            }
+            (None, None) => bail!("Either a statement or --file must be provided"),
+        }
+    }
+}
+
+/// Write the schema and contents of a database as SQL statements.
+#[derive(Parser, Debug)]
+pub struct Dump {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The file to write the statements to. If omitted, they are written to stdout.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+impl Dump {
+    pub async fn run(self) -> Result<()> {
+        let connection = self.database.connect().await?;
+        let mut out: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(std::io::BufWriter::new(
+                std::fs::File::create(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?,
+            )),
+            None => Box::new(std::io::stdout()),
+        };
+        dump(connection.as_ref(), &mut out).await?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Run the SQL statements written by `spin sqlite dump` against a database.
+#[derive(Parser, Debug)]
+pub struct Restore {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The file of statements to run, or '-' to read from stdin.
+    pub input: PathBuf,
+
+    /// Restore even if the database already contains tables.
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl Restore {
+    pub async fn run(self) -> Result<()> {
+        let connection = self.database.connect().await?;
+        let label = &self.database.label;
+        if !self.force {
+            let result = query(
+                connection.as_ref(),
+                "SELECT count(*) FROM sqlite_master WHERE name NOT LIKE 'sqlite_%'",
+                vec![],
+            )
+            .await?;
+            if !matches!(result.rows[0].values[..], [v3::Value::Integer(0)]) {
+                bail!("Database '{label}' is not empty. Use --force to restore into it anyway.");
+            }
+        }
+        let sql = read_input(&self.input)?;
+        connection
+            .execute_batch(&sql)
+            .await
+            .with_context(|| format!("failed to restore database '{label}'"))?;
+        println!("Restored database '{label}' from {}", self.input.display());
+        Ok(())
+    }
+}
+
+/// Reads a file, or stdin if the path is '-'.
+fn read_input(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+    }
+}
+
+/// Runs a query, converting any error.
+async fn query(
+    connection: &dyn Connection,
+    statement: &str,
+    parameters: Vec<v3::Value>,
+) -> Result<v3::QueryResult> {
+    connection
+        .query(statement, parameters)
+        .await
+        .with_context(|| format!("failed to run '{statement}'"))
+}
+
+/// Runs a single statement, printing the rows it returns or the number of rows it changed.
+async fn run_statement(connection: &dyn Connection, statement: &str) -> Result<()> {
+    let result = query(connection, statement, vec![]).await?;
+    if result.columns.is_empty() {
+        let changes = connection.changes().await?;
+        println!("{changes} row(s) changed");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED);
+    table.set_header(&result.columns);
+    for row in &result.rows {
+        table.add_row(row.values.iter().map(display_value));
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// Formats a value for display.
+fn display_value(value: &v3::Value) -> String {
+    match value {
+        v3::Value::Integer(i) => i.to_string(),
+        v3::Value::Real(r) => r.to_string(),
+        v3::Value::Text(s) => s.clone(),
+        v3::Value::Blob(b) => format!("<{} byte blob>", b.len()),
+        v3::Value::Null => "NULL".to_owned(),
+    }
+}
+
+/// Writes the statements that recreate the schema and contents of a database.
+async fn dump(connection: &dyn Connection, out: &mut impl Write) -> Result<()> {
+    // Tables come first so that their contents can be inserted before indexes,
+    // triggers and views, which may refer to them, are created.
+    let schema = query(
+        connection,
+        "SELECT type, name, sql FROM sqlite_master \
+            WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' \
+            ORDER BY type != 'table', rowid",
+        vec![],
+    )
+    .await?;
+
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    for row in schema.rows {
+        let [v3::Value::Text(type_), v3::Value::Text(name), v3::Value::Text(sql)] = &row.values[..]
+        else {
+            bail!("unexpected row in sqlite_master: {:?}", row.values);
+        };
+        writeln!(out, "{sql};")?;
+        if type_ != "table" {
+            continue;
+        }
+        let table = quote_identifier(name);
+        let contents = query(connection, &format!("SELECT * FROM {table}"), vec![]).await?;
+        for row in contents.rows {
+            let values = row.values.iter().map(sql_literal).collect::<Vec<_>>();
+            writeln!(out, "INSERT INTO {table} VALUES({});", values.join(","))?;
+        }
+    }
+    writeln!(out, "COMMIT;")?;
+    Ok(())
+}
+
+/// Quotes a name for use as an SQL identifier.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Formats a value as an SQL literal.
+fn sql_literal(value: &v3::Value) -> String {
+    match value {
+        v3::Value::Integer(i) => i.to_string(),
+        v3::Value::Real(r) if r.is_nan() => "NULL".to_owned(),
+        v3::Value::Real(r) if r.is_infinite() => {
+            if *r > 0.0 { "1e999" } else { "-1e999" }.to_owned()
+        }
+        v3::Value::Real(r) => format!("{r:?}"),
+        v3::Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+        v3::Value::Blob(b) => {
+            let hex = b
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<String>();
+            format!("X'{hex}'")
+        }
+        v3::Value::Null => "NULL".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    /// A connection with a single table `t` holding one row.
+    struct MockConnection;
+
+    #[async_trait]
+    impl Connection for MockConnection {
+        async fn query(
+            &self,
+            query: &str,
+            _parameters: Vec<v3::Value>,
+        ) -> Result<v3::QueryResult, v3::Error> {
+            let text = |s: &str| v3::Value::Text(s.to_owned());
+            let (columns, values) = if query.contains("sqlite_master") {
+                (
+                    vec!["type", "name", "sql"],
+                    vec![
+                        vec![text("table"), text("t"), text("CREATE TABLE t (a, b)")],
+                        vec![
+                            text("index"),
+                            text("t_a"),
+                            text("CREATE INDEX t_a ON t (a)"),
+                        ],
+                    ],
+                )
+            } else if query == "SELECT * FROM \"t\"" {
+                (
+                    vec!["a", "b"],
+                    vec![vec![v3::Value::Integer(1), text("it's")]],
+                )
+            } else {
+                return Err(v3::Error::Io(format!("unexpected query {query}")));
+            };
+            Ok(v3::QueryResult {
+                columns: columns.into_iter().map(ToOwned::to_owned).collect(),
+                rows: values
+                    .into_iter()
+                    .map(|values| v3::RowResult { values })
+                    .collect(),
+            })
+        }
+
+        async fn execute_batch(&self, _statements: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn changes(&self) -> Result<u64, v3::Error> {
+            Ok(0)
+        }
+
+        async fn last_insert_rowid(&self) -> Result<i64, v3::Error> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn dumps_schema_and_contents() {
+        let mut out = Vec::new();
+        dump(&MockConnection, &mut out).await.unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "PRAGMA foreign_keys=OFF;\n\
+             BEGIN TRANSACTION;\n\
+             CREATE TABLE t (a, b);\n\
+             INSERT INTO \"t\" VALUES(1,'it''s');\n\
+             CREATE INDEX t_a ON t (a);\n\
+             COMMIT;\n"
+        );
+    }
+
+    #[test]
+    fn formats_sql_literals() {
+        assert_eq!(sql_literal(&v3::Value::Integer(-3)), "-3");
+        assert_eq!(sql_literal(&v3::Value::Real(1.0)), "1.0");
+        assert_eq!(sql_literal(&v3::Value::Real(f64::INFINITY)), "1e999");
+        assert_eq!(sql_literal(&v3::Value::Text("a'b".into())), "'a''b'");
+        assert_eq!(sql_literal(&v3::Value::Blob(vec![0, 171])), "X'00AB'");
+        assert_eq!(sql_literal(&v3::Value::Null), "NULL");
+        assert_eq!(quote_identifier("my \"table\""), "\"my \"\"table\"\"\"");
+    }
+}
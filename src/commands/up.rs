@@ -19,7 +19,9 @@ use spin_oci::OciLoader;
 use spin_trigger::cli::{LaunchMetadata, SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_WORKING_DIR};
 use tempfile::TempDir;
 
-use crate::{directory_rels::notify_if_nondefault_rel, opts::*};
+use crate::{
+    builtin_triggers::BUILTIN_TRIGGER_TYPES, directory_rels::notify_if_nondefault_rel, opts::*,
+};
 
 use self::app_source::{AppSource, ResolvedAppSource};
 
@@ -193,36 +195,15 @@ impl UpCommand {
         if self.build {
             app_source.build(&self.cache_dir).await?;
         }
-        let mut locked_app = self
-            .load_resolved_app_source(resolved_app_source, &working_dir)
-            .await
-            .context("Failed to load application")?;
+        let mut locked_app = self.lock_app(resolved_app_source, &working_dir).await?;
 
-        if !self.components.is_empty() {
-            locked_app = spin_app::retain_components(
-                locked_app,
-                &self
-                    .components
-                    .iter()
-                    .map(|s| s.as_str())
-                    .collect::<Vec<&str>>(),
-                &[&validate_service_chaining_for_components],
-            )
-            .context(
-                "failed to resolve application with only components selected with --component",
-            )?;
-        }
-
-        let trigger_types: HashSet<&str> = locked_app
-            .triggers
-            .iter()
-            .map(|t| t.trigger_type.as_ref())
-            .collect();
+        let trigger_types = app_trigger_types(&locked_app);
 
         ensure!(!trigger_types.is_empty(), "No triggers in app");
 
-        let trigger_cmds = trigger_commands_for_trigger_types(trigger_types.into_iter().collect())
-            .with_context(|| format!("Couldn't find trigger executor for {app_source}"))?;
+        let trigger_cmds =
+            trigger_commands_for_trigger_types(trigger_types.iter().map(|t| t.as_str()).collect())
+                .with_context(|| format!("Couldn't find trigger executor for {app_source}"))?;
         let is_multi = trigger_cmds.len() > 1;
 
         self.update_locked_app(&mut locked_app);
@@ -236,8 +217,18 @@ impl UpCommand {
             local_app_dir,
         };
 
-        let trigger_processes = self.start_trigger_processes(trigger_cmds, run_opts).await?;
+        let trigger_processes = self
+            .start_trigger_processes(trigger_cmds.clone(), run_opts)
+            .await?;
         let pids = get_pids(&trigger_processes);
+        // Other trigger processes don't handle reload signals, and would exit
+        let reloadable_pids = get_pids(
+            trigger_processes
+                .iter()
+                .zip(&trigger_cmds)
+                .filter(|(_, cmd)| is_reloadable_trigger_command(cmd))
+                .map(|(process, _)| process),
+        );
 
         set_kill_on_ctrl_c(&pids)?;
 
@@ -250,7 +241,12 @@ impl UpCommand {
             tokio::time::sleep(MULTI_TRIGGER_LET_ALL_START).await;
         }
 
-        let (first_to_finish, _index, _rest) = futures::future::select_all(trigger_tasks).await;
+        let (first_to_finish, _index, _rest) = tokio::select! {
+            finished = futures::future::select_all(trigger_tasks) => finished,
+            () = self.reload_on_hangup(&app_source, &working_dir, &trigger_types, &reloadable_pids) => {
+                unreachable!("reload_on_hangup never completes")
+            }
+        };
 
         if let Ok(process_result) = first_to_finish {
             let status = process_result?;
@@ -266,6 +262,105 @@ impl UpCommand {
         Ok(())
     }
 
+    /// Re-locks the application and tells the trigger processes to reload it
+    /// whenever `spin up` receives SIGHUP. This never completes.
+    ///
+    /// If none of the trigger processes can reload, SIGHUP isn't handled, so
+    /// that a hangup still ends `spin up` as usual.
+    #[cfg(not(windows))]
+    async fn reload_on_hangup(
+        &self,
+        app_source: &AppSource,
+        working_dir: &Path,
+        trigger_types: &HashSet<String>,
+        pids: &[nix::unistd::Pid],
+    ) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if pids.is_empty() {
+            return std::future::pending().await;
+        }
+        match signal(SignalKind::hangup()) {
+            Ok(mut hangups) => {
+                while hangups.recv().await.is_some() {
+                    if let Err(err) = self
+                        .relock_app(app_source, working_dir, trigger_types)
+                        .await
+                    {
+                        terminal::error!("Failed to reload application: {err:#}");
+                        continue;
+                    }
+                    for pid in pids {
+                        if let Err(err) = nix::sys::signal::kill(*pid, nix::sys::signal::SIGHUP) {
+                            tracing::warn!("Failed to signal trigger process to reload: {err:?}")
+                        }
+                    }
+                }
+            }
+            Err(err) => tracing::warn!("Failed to listen for SIGHUP; reloading is disabled: {err}"),
+        }
+        std::future::pending().await
+    }
+
+    #[cfg(windows)]
+    async fn reload_on_hangup(
+        &self,
+        _app_source: &AppSource,
+        _working_dir: &Path,
+        _trigger_types: &HashSet<String>,
+        _pids: &[usize],
+    ) {
+        std::future::pending().await
+    }
+
+    /// Re-reads the application and overwrites the locked app which the
+    /// trigger processes were started with.
+    async fn relock_app(
+        &self,
+        app_source: &AppSource,
+        working_dir: &Path,
+        trigger_types: &HashSet<String>,
+    ) -> Result<()> {
+        let resolved_app_source = self.resolve_app_source(app_source, working_dir).await?;
+        let mut locked_app = self.lock_app(resolved_app_source, working_dir).await?;
+        ensure!(
+            &app_trigger_types(&locked_app) == trigger_types,
+            "the application's trigger types have changed; restart `spin up` to pick up the change"
+        );
+        self.update_locked_app(&mut locked_app);
+        self.write_locked_app(&locked_app, working_dir).await?;
+        Ok(())
+    }
+
+    /// Loads the resolved application and applies `--component` filtering.
+    async fn lock_app(
+        &self,
+        resolved_app_source: ResolvedAppSource,
+        working_dir: &Path,
+    ) -> Result<LockedApp> {
+        let mut locked_app = self
+            .load_resolved_app_source(resolved_app_source, working_dir)
+            .await
+            .context("Failed to load application")?;
+
+        if !self.components.is_empty() {
+            locked_app = spin_app::retain_components(
+                locked_app,
+                &self
+                    .components
+                    .iter()
+                    .map(|s| s.as_str())
+                    .collect::<Vec<&str>>(),
+                &[&validate_service_chaining_for_components],
+            )
+            .context(
+                "failed to resolve application with only components selected with --component",
+            )?;
+        }
+
+        Ok(locked_app)
+    }
+
     fn get_canonical_working_dir(&self) -> Result<WorkingDirectory, anyhow::Error> {
         let working_dir_holder = match &self.tmp {
             None => WorkingDirectory::Temporary(TempDir::with_prefix("spinup-")?),
@@ -573,16 +668,20 @@ fn set_kill_on_ctrl_c(pids: &[nix::unistd::Pid]) -> Result<(), anyhow::Error> {
 }
 
 #[cfg(windows)]
-fn get_pids(_trigger_processes: &[tokio::process::Child]) -> Vec<usize> {
+fn get_pids<'a>(
+    _trigger_processes: impl IntoIterator<Item = &'a tokio::process::Child>,
+) -> Vec<usize> {
     vec![]
 }
 
 #[cfg(not(windows))]
-fn get_pids(trigger_processes: &[tokio::process::Child]) -> Vec<nix::unistd::Pid> {
+fn get_pids<'a>(
+    trigger_processes: impl IntoIterator<Item = &'a tokio::process::Child>,
+) -> Vec<nix::unistd::Pid> {
     use itertools::Itertools;
     // https://github.com/nix-rust/nix/issues/656
     trigger_processes
-        .iter()
+        .into_iter()
         .flat_map(|child| child.id().map(|id| nix::unistd::Pid::from_raw(id as i32)))
         .collect_vec()
 }
@@ -667,16 +766,43 @@ fn trigger_command(trigger_type: &str) -> Vec<String> {
     vec!["trigger".to_owned(), trigger_type.to_owned()]
 }
 
-fn trigger_commands_for_trigger_types(trigger_types: Vec<&str>) -> Result<Vec<Vec<String>>> {
-    trigger_types
+/// Whether the command runs a trigger which reloads the app on SIGHUP. Of
+/// Spin's built-in triggers, only the HTTP trigger supports reloading, and
+/// only when it runs on its own.
+fn is_reloadable_trigger_command(cmd: &[String]) -> bool {
+    *cmd == trigger_command("http")
+}
+
+fn app_trigger_types(locked_app: &LockedApp) -> HashSet<String> {
+    locked_app
+        .triggers
         .iter()
-        .map(|&t| match t {
-            "http" | "redis" => Ok(trigger_command(t)),
-            _ => {
-                let cmd = resolve_trigger_plugin(t)?;
-                Ok(vec![cmd])
-            }
-        })
+        .map(|t| t.trigger_type.clone())
+        .collect()
+}
+
+fn trigger_commands_for_trigger_types(trigger_types: Vec<&str>) -> Result<Vec<Vec<String>>> {
+    let (builtin_types, plugin_types): (Vec<&str>, Vec<&str>) = trigger_types
+        .into_iter()
+        .partition(|t| BUILTIN_TRIGGER_TYPES.contains(t));
+
+    // Multiple built-in trigger types share a single trigger process, so that
+    // the app is only loaded and compiled once.
+    let builtin_cmds = match builtin_types.as_slice() {
+        [] => vec![],
+        [t] => vec![trigger_command(t)],
+        _ => vec![trigger_command(BUILTIN_TRIGGERS_TYPE)],
+    };
+
+    let plugin_cmds = plugin_types.into_iter().map(|t| -> Result<Vec<String>> {
+        let cmd = resolve_trigger_plugin(t)?;
+        Ok(vec![cmd])
+    });
+
+    builtin_cmds
+        .into_iter()
+        .map(Ok)
+        .chain(plugin_cmds)
         .collect()
 }
 
@@ -916,4 +1042,13 @@ mod test {
         assert_eq!("-L", groups[2][0]);
         assert_eq!("/fie", groups[2][1]);
     }
+
+    #[test]
+    fn builtin_trigger_types_share_a_process() {
+        let cmds = trigger_commands_for_trigger_types(vec!["http"]).unwrap();
+        assert_eq!(vec![trigger_command("http")], cmds);
+
+        let cmds = trigger_commands_for_trigger_types(vec!["redis", "http", "fs", "kv"]).unwrap();
+        assert_eq!(vec![trigger_command(BUILTIN_TRIGGERS_TYPE)], cmds);
+    }
 }
@@ -1,4 +1,5 @@
 pub mod build_info;
+pub mod builtin_triggers;
 pub mod commands;
 mod directory_rels;
 pub(crate) mod opts;
@@ -10,4 +11,4 @@ pub mod subprocess;
 #[allow(clippy::all, dead_code)]
 mod clap_markdown;
 
-pub use opts::HELP_ARGS_ONLY_TRIGGER_TYPE;
+pub use opts::{BUILTIN_TRIGGERS_TYPE, HELP_ARGS_ONLY_TRIGGER_TYPE};
@@ -23,7 +23,7 @@ impl Component {
         ensure_matches!(store.exists("bar"), Ok(true));
         ensure_matches!(store.get("bar"), Ok(Some(v)) if v == b"baz");
         ensure_matches!(keys(&store.list_keys(None)), Ok([bar]) if bar == "bar");
-        ensure_matches!(keys(&store.list_keys(Some("0"))), Err(Error::Other(_))); // "list_keys: cursor not supported"
+        ensure_matches!(keys(&store.list_keys(Some("bar"))), Ok(&[])); // no keys after the "bar" cursor
 
         // Override `bar` key
         ensure_ok!(store.set("bar", b"wow"));